@@ -1,46 +1,156 @@
+extern crate bytes;
+#[macro_use]
+extern crate clap;
 extern crate dotenv;
+#[macro_use]
 extern crate failure;
 extern crate firestarter;
+extern crate firestarter_generated;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 #[macro_use]
 extern crate slog;
 extern crate slog_async;
 extern crate slog_json;
 extern crate slog_term;
+extern crate tokio_codec;
+extern crate toml;
 
+#[path = "vanilla_server/commands.rs"]
+mod commands;
+#[path = "vanilla_server/config.rs"]
+mod config;
+
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
 use dotenv::dotenv;
 use slog::Drain;
-use std::env;
-use std::ffi::{OsStr, OsString};
-use std::fs::OpenOptions;
-use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
 use std::path::Path;
+use std::sync::Arc;
 
+use config::Config;
+use firestarter::rpc::util::parse_hex;
 use firestarter::server::lobby;
 
-const KEY_SERVER_MOUNT: &str = "SERVER_ADDRESS";
-const KEY_LOG_PATH: &str = "LOG_FILEPATH";
+/// Environment variable holding the path of the configuration file.
+const KEY_CONFIG_PATH: &str = "CONFIG_FILEPATH";
+
+fn cli() -> App<'static, 'static> {
+    App::new("vanilla-server")
+        .version(crate_version!())
+        .about("Lobby server for the vanilla Hearthstone client")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .env(KEY_CONFIG_PATH)
+                .global(true)
+                .help("Path of the configuration file"),
+        ).arg(
+            Arg::with_name("bind")
+                .long("bind")
+                .value_name("ADDRESS")
+                .global(true)
+                .help("Primary address to bind on, overrides server.bind_address"),
+        ).arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .global(true)
+                .help("Minimum level of messages printed to the terminal, overrides log.console_level"),
+        ).subcommand(SubCommand::with_name("run").about("Run the server (default)"))
+        .subcommand(
+            SubCommand::with_name("check-config")
+                .about("Validate the configuration and print the resulting values"),
+        ).subcommand(
+            SubCommand::with_name("print-services")
+                .about("Print the exported and imported service binding tables"),
+        ).subcommand(
+            SubCommand::with_name("decode")
+                .about("Decode captured BNet frames and print their contents")
+                .arg(
+                    Arg::with_name("hex")
+                        .long("hex")
+                        .value_name("HEX")
+                        .help("Frames as hexadecimal string"),
+                ).arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("File containing the raw frames"),
+                ).group(
+                    ArgGroup::with_name("input")
+                        .args(&["hex", "file"])
+                        .required(true),
+                ),
+        )
+}
 
-const DEFAULT_SERVER_MOUNT: &str = "127.0.0.1:1119";
-const DEFAULT_LOG_PATH: &str = "./server.log";
+fn load_config(matches: &ArgMatches) -> Result<Config, failure::Error> {
+    // Command line flags take precedence over the environment.
+    let mut overrides = HashMap::new();
+    if let Some(address) = matches.value_of("bind") {
+        overrides.insert("SERVER_ADDRESS", address.to_string());
+    }
+    if let Some(level) = matches.value_of("log-level") {
+        overrides.insert("LOG_CONSOLE_LEVEL", level.to_string());
+    }
+
+    // The configuration file is optional, defaults are used for all missing keys.
+    let config_path = matches.value_of_os("config").map(Path::new);
+    Ok(Config::load(config_path, &overrides)?)
+}
 
 fn main() -> Result<(), failure::Error> {
     // Read environment variables from directory structure.
     dotenv().ok();
 
-    // Load required environment variables (or defaults).
-    let server_mount: SocketAddr = env::var(KEY_SERVER_MOUNT)
-        .unwrap_or_else(|_| String::from(DEFAULT_SERVER_MOUNT))
-        .parse()?;
-    let log_path: OsString =
-        env::var_os(KEY_LOG_PATH).unwrap_or_else(|| OsString::from(OsStr::new(DEFAULT_LOG_PATH)));
-    let log_path = Path::new(&log_path);
+    let matches = cli().get_matches();
+    match matches.subcommand() {
+        ("check-config", _) => {
+            let config = load_config(&matches)?;
+            let accounts = config.load_accounts()?;
+            println!("{:#?}", config);
+            println!(
+                "Loaded {} account(s) from {:?}",
+                accounts.len(),
+                config.accounts_path()
+            );
+            // The storage isn't opened, because that creates and migrates its files.
+            match config.data.storage.as_str() {
+                "memory" => println!("Player state is kept in memory"),
+                _ => println!("Player state is stored at {:?}", config.storage_path()),
+            }
+            println!("Configuration is valid");
+            Ok(())
+        }
+        ("print-services", _) => {
+            print!("{}", commands::print_services());
+            Ok(())
+        }
+        ("decode", Some(decode_matches)) => {
+            let data = match decode_matches.value_of("hex") {
+                Some(hex) => parse_hex(hex)
+                    .ok_or_else(|| format_err!("Invalid hexadecimal input {:?}", hex))?,
+                None => fs::read(decode_matches.value_of_os("file").unwrap())?,
+            };
+            print!("{}", commands::decode(&data)?);
+            Ok(())
+        }
+        _ => run(load_config(&matches)?),
+    }
+}
 
+fn run(config: Config) -> Result<(), failure::Error> {
     // Setup file logger
     let log_file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(log_path)?;
+        .open(&config.log.file_path)?;
     let file_logger = slog_json::Json::default(log_file);
 
     // Setup console logging
@@ -54,8 +164,8 @@ fn main() -> Result<(), failure::Error> {
     // This approach causes storage media to fill slowly while the terminal simply cycles
     // through its buffer, favoring newer messages.
     let multiplex_logger = slog::Duplicate::new(
-        slog::LevelFilter::new(console_logger, slog::Level::Trace),
-        slog::LevelFilter::new(file_logger, slog::Level::Info),
+        slog::LevelFilter::new(console_logger, config.console_level()),
+        slog::LevelFilter::new(file_logger, config.file_level()),
     );
     let async_logger = slog_async::Async::new(multiplex_logger.ignore_res()).build();
 
@@ -66,21 +176,30 @@ fn main() -> Result<(), failure::Error> {
 
     /* Prepare for launching the server */
 
-    // Allow the server to retry binding to the mount point.
-    // If binding fails, it's allowed to try the next port.
-    // This results in the server being bound to one of the following ports
-    // [1119; 1124[ , depending on which ports are available and which are not.
-    let retry_config = lobby::BindRetryConfig::builder()
-        .max_retries(5)
-        .try_next_port(true)
-        .build();
+    let accounts = config.load_accounts()?;
+    if accounts.is_empty() && config.authentication.modules.is_empty() {
+        warn!(root_logger, "No accounts are configured, nobody can log on";
+            "path" => ?config.accounts_path(),
+        );
+    }
+
+    if config.authentication.sso_key.is_empty() {
+        warn!(
+            root_logger,
+            "No single sign-on key is configured, tokens are invalidated when restarting"
+        );
+    }
+
+    let storage = config.open_storage()?;
+    if config.data.storage == "memory" {
+        warn!(
+            root_logger,
+            "Player state is kept in memory, it's lost when the server stops"
+        );
+    }
 
     // Configuration details for the server itself.
-    let config = lobby::ServerConfig::builder()
-        .bind_address(server_mount)
-        .bind_fallback(retry_config)
-        .logger(root_logger)
-        .build();
+    let server_config = config.server_config(Arc::new(accounts), storage, root_logger);
 
     // Build server and 'just run' it.
     // This uses the default Tokio runtime, which uses a threadpool executor and
@@ -90,7 +209,7 @@ fn main() -> Result<(), failure::Error> {
     // shut down.
     // See [`LobbyServer::split`] for a [`ServerHandle`] which can control the server
     // while it's running on the reactor.
-    lobby::LobbyServer::with(config)?.run();
+    lobby::LobbyServer::with(server_config)?.run();
 
     Ok(())
 }
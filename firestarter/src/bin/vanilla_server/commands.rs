@@ -0,0 +1,223 @@
+//! Implementation of the subcommands which don't run the server.
+
+use bytes::BytesMut;
+use failure;
+use std::fmt::Write;
+use tokio_codec::Decoder;
+
+use firestarter::protocol::bnet::frame::BNetCodec;
+use firestarter::protocol::bnet::packet_extension::RESPONSE_SERVICE_ID;
+use firestarter::rpc::util::hash_service_name;
+use firestarter::service::bnet::service_info::{
+    SERVICES_EXPORTED_BINDING, SERVICES_IMPORTED_BINDING,
+};
+
+// Maximum depth when interpreting length-delimited fields as nested messages.
+const MAX_NESTING: usize = 8;
+
+/// Build a table of all services the server exports and imports.
+///
+/// The hash of each service name is recalculated, so mistakes within the binding
+/// tables are flagged.
+pub fn print_services() -> String {
+    let mut exported: Vec<_> = SERVICES_EXPORTED_BINDING
+        .iter()
+        .map(|(hash, id)| (*id as u32, *hash, id.name()))
+        .collect();
+    let mut imported: Vec<_> = SERVICES_IMPORTED_BINDING
+        .iter()
+        .map(|(hash, id)| (*id as u32, *hash, id.name()))
+        .collect();
+    exported.sort();
+    imported.sort();
+
+    let mut output = String::new();
+    for (title, services) in &[
+        ("Exported (served by the server)", exported),
+        ("Imported (served by the client)", imported),
+    ] {
+        writeln!(output, "{}", title).unwrap();
+        writeln!(output, "  ID        HASH  NAME").unwrap();
+        for &(id, hash, name) in services {
+            let marker = if hash_service_name(name) == hash {
+                ""
+            } else {
+                "  (hash mismatch!)"
+            };
+            writeln!(output, "{:>4}  {:>10}  {}{}", id, hash, name, marker).unwrap();
+        }
+        writeln!(output).unwrap();
+    }
+    output
+}
+
+/// Decode all BNet frames within the provided data into a readable description.
+///
+/// Bodies are printed as raw protobuf fields, because the message type is not
+/// transmitted.
+pub fn decode(data: &[u8]) -> Result<String, failure::Error> {
+    let mut codec = BNetCodec::new();
+    let mut buffer = BytesMut::from(data);
+    let mut output = String::new();
+
+    while !buffer.is_empty() {
+        let packet = match codec.decode(&mut buffer)? {
+            Some(packet) => packet,
+            None => bail!("Incomplete frame, {} bytes remaining", buffer.len()),
+        };
+        let header = packet.header();
+        writeln!(output, "{:#?}", header).unwrap();
+
+        if header.service_id == RESPONSE_SERVICE_ID {
+            writeln!(output, "Service: response for token {}", header.token).unwrap();
+        } else {
+            // The direction of the packet is unknown.
+            let exported = SERVICES_EXPORTED_BINDING
+                .values()
+                .find(|id| **id as u32 == header.service_id);
+            let imported = SERVICES_IMPORTED_BINDING
+                .values()
+                .find(|id| **id as u32 == header.service_id);
+            if let Some(id) = exported {
+                writeln!(output, "Service (client -> server): {}", id.name()).unwrap();
+            }
+            if let Some(id) = imported {
+                writeln!(output, "Service (server -> client): {}", id.name()).unwrap();
+            }
+        }
+
+        writeln!(output, "Body ({} bytes):", packet.body().len()).unwrap();
+        if describe_message(packet.body(), 1, &mut output).is_none() {
+            writeln!(
+                output,
+                "  <not a protobuf message> {}",
+                to_hex(packet.body())
+            )
+            .unwrap();
+        }
+        writeln!(output).unwrap();
+    }
+    Ok(output)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in 0..10 {
+        let byte = *data.get(*position)?;
+        *position += 1;
+        value |= u64::from(byte & 0x7F) << (shift * 7);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn read_bytes<'a>(data: &'a [u8], position: &mut usize, length: usize) -> Option<&'a [u8]> {
+    let end = position.checked_add(length)?;
+    let bytes = data.get(*position..end)?;
+    *position = end;
+    Some(bytes)
+}
+
+// Writes one line per field into the output, None is returned if the data is
+// not a valid protobuf message.
+fn describe_message(data: &[u8], depth: usize, output: &mut String) -> Option<()> {
+    let indent = "  ".repeat(depth);
+    let mut description = String::new();
+    let mut position = 0;
+
+    while position < data.len() {
+        let key = read_varint(data, &mut position)?;
+        let (tag, wire_type) = (key >> 3, key & 0x7);
+        if tag == 0 {
+            return None;
+        }
+
+        match wire_type {
+            0 => {
+                let value = read_varint(data, &mut position)?;
+                writeln!(description, "{}{}: {}", indent, tag, value).unwrap();
+            }
+            1 => {
+                let bytes = read_bytes(data, &mut position, 8)?;
+                let mut value = [0u8; 8];
+                value.copy_from_slice(bytes);
+                writeln!(
+                    description,
+                    "{}{}: {}",
+                    indent,
+                    tag,
+                    u64::from_le_bytes(value)
+                )
+                .unwrap();
+            }
+            2 => {
+                let length = read_varint(data, &mut position)? as usize;
+                let bytes = read_bytes(data, &mut position, length)?;
+                describe_length_delimited(tag, bytes, depth, &mut description);
+            }
+            5 => {
+                let bytes = read_bytes(data, &mut position, 4)?;
+                let mut value = [0u8; 4];
+                value.copy_from_slice(bytes);
+                writeln!(
+                    description,
+                    "{}{}: {}",
+                    indent,
+                    tag,
+                    u32::from_le_bytes(value)
+                )
+                .unwrap();
+            }
+            _ => return None,
+        }
+    }
+
+    output.push_str(&description);
+    Some(())
+}
+
+fn describe_length_delimited(tag: u64, bytes: &[u8], depth: usize, output: &mut String) {
+    let indent = "  ".repeat(depth);
+    // Printable text is preferred, because short strings are often valid messages too.
+    let text = ::std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.chars().any(char::is_control));
+    if let Some(text) = text {
+        writeln!(output, "{}{}: {:?}", indent, tag, text).unwrap();
+        return;
+    }
+
+    let mut nested = String::new();
+    let is_message = !bytes.is_empty()
+        && depth < MAX_NESTING
+        && describe_message(bytes, depth + 1, &mut nested).is_some();
+    if is_message {
+        writeln!(output, "{}{} {{", indent, tag).unwrap();
+        output.push_str(&nested);
+        writeln!(output, "{}}}", indent).unwrap();
+    } else {
+        writeln!(output, "{}{}: 0x{}", indent, tag, to_hex(bytes)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter::rpc::util::parse_hex;
+
+    #[test]
+    fn decodes_frame() {
+        // Header: service 0, method 5, token 7, size 4; body: field 1 = "hi".
+        let frame = parse_hex("0008 0800 1005 1807 2804  0a026869").unwrap();
+        let output = decode(&frame).unwrap();
+
+        assert!(output.contains("ConnectionService"));
+        assert!(output.contains("1: \"hi\""));
+    }
+}
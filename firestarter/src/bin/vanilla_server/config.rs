@@ -0,0 +1,722 @@
+//! Configuration file for the vanilla server.
+//!
+//! The configuration is written in TOML, all keys are optional. See
+//! [`Config::default`] for the values which are used when a key is missing.
+//!
+//! Individual keys can be overridden through environment variables, see
+//! [`ENV_OVERRIDES`].
+
+use slog;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use toml;
+
+use firestarter::protocol::bnet::session::SessionConfig;
+use firestarter::rpc::util::{hash_service_name, parse_hex};
+use firestarter::server::accounts::{AccountList, SharedAccountStore};
+use firestarter::server::lobby::{BindRetryConfig, ServerConfig};
+use firestarter::server::logon_queue::{LogonQueueConfig, DEFAULT_QUEUE_UPDATE_INTERVAL};
+use firestarter::server::storage::file::FileStorage;
+use firestarter::server::storage::memory::MemoryStorage;
+use firestarter::server::storage::SharedStorage;
+use firestarter::server::tokens::{TokenSigner, DEFAULT_TOKEN_LIFETIME};
+use firestarter::service::bnet::auth_module::{AcceptAnyModule, BoxedAuthModule, TokenModule};
+use firestarter::service::bnet::service_info::SERVICES_EXPORTED_BINDING;
+use firestarter_generated::proto::bnet::protocol::ContentHandle;
+
+pub use self::error::*;
+
+// Region ("US") and usage ("auth") of the content handle of the token module.
+const TOKEN_MODULE_REGION: u32 = 0x5553;
+const TOKEN_MODULE_USAGE: u32 = 0x6175_7468;
+
+/// Environment variables which override a key of the configuration file.
+///
+/// Each entry holds the name of the variable, the table and the key.
+/// Values are interpreted as TOML values when possible, and as strings otherwise.
+pub const ENV_OVERRIDES: &[(&str, &str, &str)] = &[
+    ("SERVER_ADDRESS", "server", "bind_address"),
+    ("SERVER_MAX_CONNECTIONS", "server", "max_connections"),
+    (
+        "SERVER_MAX_CONNECTIONS_PER_IP",
+        "server",
+        "max_connections_per_ip",
+    ),
+    ("SERVER_MAX_RETRIES", "server", "max_retries"),
+    ("SERVER_TRY_NEXT_PORT", "server", "try_next_port"),
+    (
+        "SESSION_HANDSHAKE_TIMEOUT",
+        "session",
+        "handshake_timeout_ms",
+    ),
+    ("SESSION_IDLE_TIMEOUT", "session", "idle_timeout_ms"),
+    ("LOG_FILEPATH", "log", "file_path"),
+    ("LOG_CONSOLE_LEVEL", "log", "console_level"),
+    ("LOG_FILE_LEVEL", "log", "file_level"),
+    ("DATA_DIRECTORY", "data", "directory"),
+    ("DATA_ACCOUNTS_FILE", "data", "accounts_file"),
+    ("DATA_STORAGE", "data", "storage"),
+    ("DATA_STORAGE_DIRECTORY", "data", "storage_directory"),
+    ("AUTHENTICATION_SSO_KEY", "authentication", "sso_key"),
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings for the listening sockets and accepting clients.
+pub struct ServerSection {
+    /// Primary address to bind on.
+    pub bind_address: SocketAddr,
+    /// Additional addresses to bind on.
+    pub additional_bind_addresses: Vec<SocketAddr>,
+    /// Amount of times binding on an address is retried.
+    pub max_retries: u8,
+    /// Retry binding on the next port, instead of the same port.
+    pub try_next_port: bool,
+    /// Delay between binding attempts on the same port, in milliseconds.
+    pub retry_delay_ms: u64,
+    /// Maximum amount of open connections.
+    pub max_connections: usize,
+    /// Maximum amount of open connections from one IP-address.
+    pub max_connections_per_ip: Option<usize>,
+    /// Pause after failing to accept a client, in milliseconds.
+    pub accept_error_timeout_ms: u64,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([127, 0, 0, 1], 1119)),
+            additional_bind_addresses: vec![],
+            // This results in the server being bound to one of the following ports
+            // [1119; 1124[ , depending on which ports are available and which are not.
+            max_retries: 5,
+            try_next_port: true,
+            retry_delay_ms: 500,
+            max_connections: 1000,
+            max_connections_per_ip: None,
+            accept_error_timeout_ms: 100,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings applied to each client session.
+pub struct SessionSection {
+    /// Maximum duration of the handshake, in milliseconds.
+    pub handshake_timeout_ms: u64,
+    /// Maximum duration between two packets from the client, in milliseconds.
+    /// Zero disables the timeout.
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for SessionSection {
+    fn default() -> Self {
+        Self {
+            handshake_timeout_ms: 5_000,
+            idle_timeout_ms: 120_000,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings for the log drains.
+pub struct LogSection {
+    /// Path of the file receiving log messages.
+    pub file_path: PathBuf,
+    /// Minimum level of messages printed to the terminal.
+    pub console_level: String,
+    /// Minimum level of messages written to the log file.
+    pub file_level: String,
+}
+
+impl Default for LogSection {
+    fn default() -> Self {
+        Self {
+            file_path: PathBuf::from("./server.log"),
+            console_level: String::from("trace"),
+            file_level: String::from("info"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings for persistent data.
+pub struct DataSection {
+    /// Directory holding all persistent data of the server.
+    pub directory: PathBuf,
+    /// File listing the accounts which are allowed to log on, relative to `directory`.
+    /// See [`AccountList`] for its format.
+    pub accounts_file: PathBuf,
+    /// Backend holding the state of players, either "file" or "memory".
+    /// State kept in memory is lost when the server stops.
+    pub storage: String,
+    /// Directory of the "file" storage, relative to `directory`.
+    pub storage_directory: PathBuf,
+}
+
+impl Default for DataSection {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("./data"),
+            accounts_file: PathBuf::from("accounts.txt"),
+            storage: "file".to_string(),
+            storage_directory: PathBuf::from("storage"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings for the services provided to clients.
+pub struct ServicesSection {
+    /// Fully qualified names of the services which are enabled, eg
+    /// `bnet.protocol.authentication.AuthenticationServer`.
+    /// All known services are enabled when this list is empty. The connection service
+    /// is always enabled.
+    pub enabled: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Settings for authenticating clients.
+pub struct AuthenticationSection {
+    /// Modules which each client must pass during logon, in order.
+    /// One of `accept_any` (development only!) or `token`.
+    /// Clients log on with the credentials within their logon request when this list is empty.
+    pub modules: Vec<String>,
+    /// Hash of the client module which is loaded by the `token` module, in hexadecimal.
+    pub token_module_hash: String,
+    /// Key for signing single sign-on tokens, in hexadecimal.
+    /// A random key is used when empty, which invalidates all tokens when restarting.
+    pub sso_key: String,
+    /// Amount of time single sign-on tokens remain valid, in seconds.
+    pub sso_lifetime_s: u64,
+    /// Amount of logons admitted each second, the logon queue is disabled when zero.
+    pub queue_admissions_per_second: u32,
+    /// Interval between two position updates towards a queued client, in milliseconds.
+    pub queue_update_interval_ms: u64,
+}
+
+impl Default for AuthenticationSection {
+    fn default() -> Self {
+        Self {
+            modules: vec![],
+            token_module_hash: String::new(),
+            sso_key: String::new(),
+            sso_lifetime_s: DEFAULT_TOKEN_LIFETIME.as_secs(),
+            queue_admissions_per_second: 0,
+            queue_update_interval_ms: DEFAULT_QUEUE_UPDATE_INTERVAL.as_secs() * 1000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Complete configuration of the vanilla server.
+pub struct Config {
+    /// See [`ServerSection`].
+    pub server: ServerSection,
+    /// See [`SessionSection`].
+    pub session: SessionSection,
+    /// See [`LogSection`].
+    pub log: LogSection,
+    /// See [`DataSection`].
+    pub data: DataSection,
+    /// See [`ServicesSection`].
+    pub services: ServicesSection,
+    /// See [`AuthenticationSection`].
+    pub authentication: AuthenticationSection,
+}
+
+impl Config {
+    /// Load the configuration from the provided file, if any.
+    ///
+    /// Environment variable overrides are applied and the result is validated.
+    /// Values within `overrides` are keyed by variable name and take precedence over
+    /// the environment.
+    pub fn load(
+        path: Option<&Path>,
+        overrides: &HashMap<&str, String>,
+    ) -> Result<Self, ConfigError> {
+        let contents = match path {
+            Some(path) => fs::read_to_string(path).map_err(|error| ConfigError::Io {
+                path: path.to_path_buf(),
+                error,
+            })?,
+            None => String::new(),
+        };
+        Self::parse(&contents, |key| {
+            overrides.get(key).cloned().or_else(|| env::var(key).ok())
+        })
+    }
+
+    /// Parse the configuration from the provided TOML document.
+    ///
+    /// Values for the keys listed in [`ENV_OVERRIDES`] are retrieved through `lookup`.
+    pub fn parse<F>(contents: &str, lookup: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut document: toml::Value = contents.parse()?;
+        for &(variable, table, key) in ENV_OVERRIDES {
+            if let Some(value) = lookup(variable) {
+                apply_override(&mut document, table, key, &value)?;
+            }
+        }
+
+        let config: Config = document.try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        for &(drain, ref level) in &[
+            ("console", &self.log.console_level),
+            ("file", &self.log.file_level),
+        ] {
+            if parse_level(level).is_none() {
+                Err(ConfigError::InvalidLevel {
+                    drain,
+                    level: level.to_string(),
+                })?;
+            }
+        }
+
+        if self.server.max_connections == 0 {
+            Err(ConfigError::Invalid {
+                key: "server.max_connections",
+                reason: "must be larger than zero",
+            })?;
+        }
+        if self.server.max_connections_per_ip == Some(0) {
+            Err(ConfigError::Invalid {
+                key: "server.max_connections_per_ip",
+                reason: "must be larger than zero",
+            })?;
+        }
+        if self.session.handshake_timeout_ms == 0 {
+            Err(ConfigError::Invalid {
+                key: "session.handshake_timeout_ms",
+                reason: "must be larger than zero",
+            })?;
+        }
+
+        match self.data.storage.as_str() {
+            "file" | "memory" => {}
+            _ => Err(ConfigError::Invalid {
+                key: "data.storage",
+                reason: "must be 'file' or 'memory'",
+            })?,
+        }
+
+        for name in &self.services.enabled {
+            if !SERVICES_EXPORTED_BINDING.contains_key(&hash_service_name(name)) {
+                Err(ConfigError::UnknownService { name: name.clone() })?;
+            }
+        }
+
+        for name in &self.authentication.modules {
+            match name.as_str() {
+                "accept_any" => {}
+                "token" if self.token_module_hash().is_none() => Err(ConfigError::Invalid {
+                    key: "authentication.token_module_hash",
+                    reason: "must be a hexadecimal hash when the token module is enabled",
+                })?,
+                "token" => {}
+                _ => Err(ConfigError::Invalid {
+                    key: "authentication.modules",
+                    reason: "must only contain 'accept_any' or 'token'",
+                })?,
+            }
+        }
+
+        let sso_key = &self.authentication.sso_key;
+        if !sso_key.is_empty() && parse_hex(sso_key).map(|key| key.len()).unwrap_or(0) < 16 {
+            Err(ConfigError::Invalid {
+                key: "authentication.sso_key",
+                reason: "must be empty or at least 16 bytes in hexadecimal",
+            })?;
+        }
+        if self.authentication.sso_lifetime_s == 0 {
+            Err(ConfigError::Invalid {
+                key: "authentication.sso_lifetime_s",
+                reason: "must be larger than zero",
+            })?;
+        }
+        if self.authentication.queue_update_interval_ms == 0 {
+            Err(ConfigError::Invalid {
+                key: "authentication.queue_update_interval_ms",
+                reason: "must be larger than zero",
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Build the signer of single sign-on tokens.
+    ///
+    /// A random key is used when no key is configured.
+    pub fn token_signer(&self) -> TokenSigner {
+        let lifetime = Duration::from_secs(self.authentication.sso_lifetime_s);
+        match parse_hex(&self.authentication.sso_key) {
+            // Validated during parsing.
+            Some(ref key) if !key.is_empty() => TokenSigner::new(key.clone(), lifetime),
+            _ => TokenSigner::random(lifetime),
+        }
+    }
+
+    /// Build the configuration of the logon queue, if enabled.
+    pub fn logon_queue(&self) -> Option<LogonQueueConfig> {
+        let authentication = &self.authentication;
+        if authentication.queue_admissions_per_second == 0 {
+            return None;
+        }
+        let config = LogonQueueConfig::builder()
+            .admissions_per_second(authentication.queue_admissions_per_second)
+            .update_interval(Duration::from_millis(
+                authentication.queue_update_interval_ms,
+            ))
+            .build();
+        Some(config)
+    }
+
+    fn token_module_hash(&self) -> Option<Vec<u8>> {
+        parse_hex(&self.authentication.token_module_hash).filter(|hash| !hash.is_empty())
+    }
+
+    /// Build the modules which each client must pass during logon.
+    pub fn auth_modules(&self) -> Vec<BoxedAuthModule> {
+        self.authentication
+            .modules
+            .iter()
+            .map(|name| -> BoxedAuthModule {
+                match name.as_str() {
+                    "token" => {
+                        // Validated during parsing.
+                        let handle = ContentHandle {
+                            region: TOKEN_MODULE_REGION,
+                            usage: TOKEN_MODULE_USAGE,
+                            hash: self.token_module_hash().unwrap(),
+                            proto_url: None,
+                        };
+                        Box::new(TokenModule::new(handle))
+                    }
+                    _ => Box::new(AcceptAnyModule::new()),
+                }
+            })
+            .collect()
+    }
+
+    /// Retrieve the minimum level of messages printed to the terminal.
+    pub fn console_level(&self) -> slog::Level {
+        // Validated during parsing.
+        parse_level(&self.log.console_level).unwrap()
+    }
+
+    /// Retrieve the minimum level of messages written to the log file.
+    pub fn file_level(&self) -> slog::Level {
+        // Validated during parsing.
+        parse_level(&self.log.file_level).unwrap()
+    }
+
+    /// Retrieve the hashes of the enabled services, `None` if all services are enabled.
+    pub fn enabled_services(&self) -> Option<HashSet<u32>> {
+        if self.services.enabled.is_empty() {
+            return None;
+        }
+        let services = self
+            .services
+            .enabled
+            .iter()
+            .map(hash_service_name)
+            .collect();
+        Some(services)
+    }
+
+    /// Retrieve the path of the account file.
+    pub fn accounts_path(&self) -> PathBuf {
+        self.data.directory.join(&self.data.accounts_file)
+    }
+
+    /// Load the accounts which are allowed to log on.
+    ///
+    /// A missing account file results in an empty list.
+    pub fn load_accounts(&self) -> Result<AccountList, ConfigError> {
+        let path = self.accounts_path();
+        if !path.exists() {
+            return Ok(AccountList::new());
+        }
+        AccountList::load(&path).map_err(|error| ConfigError::Accounts { path, error })
+    }
+
+    /// Retrieve the directory of the file storage.
+    pub fn storage_path(&self) -> PathBuf {
+        self.data.directory.join(&self.data.storage_directory)
+    }
+
+    /// Open the storage holding the state of players.
+    ///
+    /// Files written by older versions of the server are migrated.
+    pub fn open_storage(&self) -> Result<SharedStorage, ConfigError> {
+        match self.data.storage.as_str() {
+            "memory" => Ok(Arc::new(MemoryStorage::new())),
+            _ => {
+                let path = self.storage_path();
+                let storage = FileStorage::open(&path)
+                    .map_err(|error| ConfigError::Storage { path, error })?;
+                Ok(Arc::new(storage))
+            }
+        }
+    }
+
+    /// Build the configuration for the lobby server.
+    ///
+    /// Clients are authenticated against the provided accounts, the state of players is
+    /// kept within the provided storage.
+    pub fn server_config(
+        &self,
+        accounts: SharedAccountStore,
+        storage: SharedStorage,
+        logger: slog::Logger,
+    ) -> ServerConfig {
+        let server = &self.server;
+        let retry_config = BindRetryConfig::builder()
+            .max_retries(server.max_retries)
+            .try_next_port(server.try_next_port)
+            .retry_delay(Duration::from_millis(server.retry_delay_ms))
+            .build();
+
+        let idle_timeout = match self.session.idle_timeout_ms {
+            0 => None,
+            timeout => Some(Duration::from_millis(timeout)),
+        };
+        let session_config = SessionConfig::builder()
+            .handshake_deadline(Duration::from_millis(self.session.handshake_timeout_ms))
+            .idle_timeout(idle_timeout)
+            .build();
+
+        ServerConfig::builder()
+            .bind_address(server.bind_address)
+            .additional_bind_addresses(server.additional_bind_addresses.clone())
+            .bind_fallback(retry_config)
+            .max_connections(server.max_connections)
+            .max_connections_per_ip(server.max_connections_per_ip)
+            .accept_error_timeout(Duration::from_millis(server.accept_error_timeout_ms))
+            .session(session_config)
+            .enabled_services(self.enabled_services())
+            .accounts(accounts)
+            .storage(storage)
+            .auth_modules(self.auth_modules())
+            .tokens(self.token_signer())
+            .logon_queue(self.logon_queue())
+            .logger(logger)
+            .build()
+    }
+}
+
+fn apply_override(
+    document: &mut toml::Value,
+    table: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), ConfigError> {
+    // Bare strings are not valid TOML values, so they're wrapped when parsing fails.
+    let value = format!("value = {}", value)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|mut parsed| parsed.as_table_mut().and_then(|t| t.remove("value")))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+    let root = document.as_table_mut().ok_or(ConfigError::Invalid {
+        key: "<root>",
+        reason: "must be a table",
+    })?;
+    let section = root
+        .entry(table.to_string())
+        .or_insert_with(|| toml::Value::Table(Default::default()));
+    match section.as_table_mut() {
+        Some(section) => {
+            section.insert(key.to_string(), value);
+            Ok(())
+        }
+        None => Err(ConfigError::Invalid {
+            key: "<section>",
+            reason: "must be a table",
+        }),
+    }
+}
+
+fn parse_level(level: &str) -> Option<slog::Level> {
+    match level.to_lowercase().as_str() {
+        "critical" => Some(slog::Level::Critical),
+        "error" => Some(slog::Level::Error),
+        "warning" | "warn" => Some(slog::Level::Warning),
+        "info" => Some(slog::Level::Info),
+        "debug" => Some(slog::Level::Debug),
+        "trace" => Some(slog::Level::Trace),
+        _ => None,
+    }
+}
+
+mod error {
+    use firestarter::server::accounts::AccountError;
+    use firestarter::server::storage::StorageError;
+    use std::io;
+    use std::path::PathBuf;
+    use toml;
+
+    #[derive(Debug, Fail)]
+    /// Error type related to loading the configuration.
+    pub enum ConfigError {
+        #[fail(display = "Failed to read configuration file {:?}: {}", path, error)]
+        /// Failure to read the configuration file.
+        Io {
+            /// The path of the configuration file.
+            path: PathBuf,
+            /// The underlying error.
+            #[cause]
+            error: io::Error,
+        },
+
+        #[fail(display = "Failed to load account file {:?}: {}", path, error)]
+        /// Failure to load the account file.
+        Accounts {
+            /// The path of the account file.
+            path: PathBuf,
+            /// The underlying error.
+            #[cause]
+            error: AccountError,
+        },
+
+        #[fail(display = "Failed to open storage {:?}: {}", path, error)]
+        /// Failure to open the storage of player state.
+        Storage {
+            /// The directory of the storage.
+            path: PathBuf,
+            /// The underlying error.
+            #[cause]
+            error: StorageError,
+        },
+
+        #[fail(display = "Malformed configuration: {}", _0)]
+        /// Failure to parse the document or to map it onto the configuration.
+        Parse(#[cause] toml::de::Error),
+
+        #[fail(display = "Invalid log level {:?} for the {} drain", level, drain)]
+        /// Failure to interpret the log level of a drain.
+        InvalidLevel {
+            /// The drain for which the level was configured.
+            drain: &'static str,
+            /// The configured level.
+            level: String,
+        },
+
+        #[fail(display = "Unknown service {:?}", name)]
+        /// Failure to enable a service which doesn't exist.
+        UnknownService {
+            /// The configured service name.
+            name: String,
+        },
+
+        #[fail(display = "Invalid value for {}: {}", key, reason)]
+        /// Failure to validate a configured value.
+        Invalid {
+            /// The offending key.
+            key: &'static str,
+            /// Description of the constraint.
+            reason: &'static str,
+        },
+    }
+
+    // Usability improvement
+    impl From<toml::de::Error> for ConfigError {
+        fn from(x: toml::de::Error) -> Self {
+            ConfigError::Parse(x)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn applies_overrides() {
+        let contents = r#"
+            [server]
+            bind_address = "0.0.0.0:1119"
+            max_connections = 10
+        "#;
+        let config = Config::parse(contents, |key| match key {
+            "SERVER_MAX_CONNECTIONS" => Some("20".to_string()),
+            "LOG_FILEPATH" => Some("/tmp/server.log".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            SocketAddr::from(([0, 0, 0, 0], 1119)),
+            config.server.bind_address
+        );
+        assert_eq!(20, config.server.max_connections);
+        assert_eq!(PathBuf::from("/tmp/server.log"), config.log.file_path);
+        assert_eq!(slog::Level::Info, config.file_level());
+    }
+
+    #[test]
+    fn enables_listed_services() {
+        let parse = |contents| Config::parse(contents, |_| None).unwrap();
+        assert_eq!(None, parse("").enabled_services());
+
+        let name = "bnet.protocol.account.AccountService";
+        let config = parse(&format!("[services]\nenabled = [\"{}\"]", name));
+        let services = config.enabled_services().unwrap();
+        assert_eq!(1, services.len());
+        assert!(services.contains(&hash_service_name(name)));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let parse = |contents| Config::parse(contents, |_| None);
+
+        match parse("[log]\nconsole_level = \"loud\"") {
+            Err(ConfigError::InvalidLevel { drain, .. }) => assert_eq!("console", drain),
+            _ => panic!("Expected an invalid level error"),
+        }
+        match parse("[services]\nenabled = [\"bnet.protocol.Unknown\"]") {
+            Err(ConfigError::UnknownService { .. }) => {}
+            _ => panic!("Expected an unknown service error"),
+        }
+        match parse("[server]\nunknown_key = 1") {
+            Err(ConfigError::Parse(_)) => {}
+            _ => panic!("Expected a parse error"),
+        }
+        match parse("[authentication]\nmodules = [\"token\"]") {
+            Err(ConfigError::Invalid { key, .. }) => {
+                assert_eq!("authentication.token_module_hash", key)
+            }
+            _ => panic!("Expected an invalid token module hash"),
+        }
+        match parse("[authentication]\nsso_key = \"0a1b\"") {
+            Err(ConfigError::Invalid { key, .. }) => assert_eq!("authentication.sso_key", key),
+            _ => panic!("Expected an invalid key"),
+        }
+        match parse("[authentication]\nqueue_update_interval_ms = 0") {
+            Err(ConfigError::Invalid { key, .. }) => {
+                assert_eq!("authentication.queue_update_interval_ms", key)
+            }
+            _ => panic!("Expected an invalid update interval"),
+        }
+        match parse("[data]\nstorage = \"database\"") {
+            Err(ConfigError::Invalid { key, .. }) => assert_eq!("data.storage", key),
+            _ => panic!("Expected an invalid storage backend"),
+        }
+    }
+}
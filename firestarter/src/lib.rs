@@ -20,11 +20,16 @@ extern crate error_chain;
 extern crate lazy_static;
 #[macro_use]
 extern crate maplit;
+#[macro_use]
+extern crate prost_derive;
 
 extern crate bytes;
 extern crate chrono;
 extern crate futures;
+extern crate hmac;
 extern crate prost;
+extern crate rand;
+extern crate sha2;
 extern crate slog_stdlog;
 extern crate tokio;
 extern crate tokio_codec;
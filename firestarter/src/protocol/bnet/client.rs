@@ -0,0 +1,402 @@
+//! Module with the RPC client for calling services exported by the connected client.
+//!
+//! Calls are sent through the session task as normal packets. Each call which expects
+//! a response is tracked by its token until the client answers through the
+//! ResponseService.
+//! Calls which aren't answered in time fail with [`RPCError::Timeout`], the deadline
+//! for each call is looked up in [`CallTimeouts`].
+
+use bytes::Bytes;
+use firestarter_generated::proto::bnet::protocol::Header;
+use firestarter_generated::rpc::{RpcChannel, RpcFuture};
+use futures::future;
+use futures::prelude::*;
+use futures::sync::{mpsc, oneshot};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::Deadline;
+
+use protocol::bnet::frame::BNetPacket;
+use protocol::bnet::session::SessionCommand;
+use rpc::system::RPCError;
+use rpc::transport::Response;
+use service::bnet::router::ServiceBindings;
+
+// Receiving half of a pending call.
+type ResponseReceiver = oneshot::Receiver<Response<BNetPacket>>;
+
+/// Maximum duration to wait for a response if no specific timeout was configured.
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+/// Deadlines for calls towards the client.
+///
+/// A timeout configured for a specific method takes precedence over a timeout for
+/// the entire service, which takes precedence over the default timeout.
+pub struct CallTimeouts {
+    default: Duration,
+    services: HashMap<u32, Duration>,
+    methods: HashMap<(u32, u32), Duration>,
+}
+
+impl CallTimeouts {
+    /// Creates a new set of timeouts where each call uses the provided default.
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            services: HashMap::new(),
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Set the timeout for all methods of the service with the provided hash.
+    pub fn with_service(mut self, service_hash: u32, timeout: Duration) -> Self {
+        self.services.insert(service_hash, timeout);
+        self
+    }
+
+    /// Set the timeout for one method of the service with the provided hash.
+    pub fn with_method(mut self, service_hash: u32, method_id: u32, timeout: Duration) -> Self {
+        self.methods.insert((service_hash, method_id), timeout);
+        self
+    }
+
+    /// Retrieve the timeout for a call towards the provided service method.
+    pub fn timeout(&self, service_hash: u32, method_id: u32) -> Duration {
+        self.methods
+            .get(&(service_hash, method_id))
+            .or_else(|| self.services.get(&service_hash))
+            .cloned()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Default for CallTimeouts {
+    fn default() -> Self {
+        Self::new(DEFAULT_CALL_TIMEOUT)
+    }
+}
+
+#[derive(Debug, Default)]
+/// Table of calls which are waiting for a response from the client.
+pub struct PendingCalls {
+    next_token: u32,
+    calls: HashMap<u32, oneshot::Sender<Response<BNetPacket>>>,
+}
+
+impl PendingCalls {
+    /// Creates a new and empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the amount of calls waiting for a response.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns true if no calls are waiting for a response.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Allocate a token which is not used by any pending call.
+    fn allocate_token(&mut self) -> u32 {
+        loop {
+            let token = self.next_token;
+            self.next_token = self.next_token.wrapping_add(1);
+            if !self.calls.contains_key(&token) {
+                return token;
+            }
+        }
+    }
+
+    fn register(&mut self, token: u32) -> ResponseReceiver {
+        let (sender, receiver) = oneshot::channel();
+        self.calls.insert(token, sender);
+        receiver
+    }
+
+    /// Forget about the call with the provided token.
+    ///
+    /// Returns true if a call was pending for this token.
+    pub fn cancel(&mut self, token: u32) -> bool {
+        self.calls.remove(&token).is_some()
+    }
+
+    /// Forget about all pending calls, these fail with [`RPCError::Canceled`].
+    pub fn cancel_all(&mut self) {
+        self.calls.clear();
+    }
+
+    /// Complete the pending call matching the token of the response.
+    pub fn resolve(&mut self, response: Response<BNetPacket>) -> Result<(), RPCError> {
+        let token = response.as_ref().into_inner().header().token;
+        let sender = self
+            .calls
+            .remove(&token)
+            .ok_or(RPCError::InvalidResponse { token })?;
+        // The caller could have lost interest in the response, which is fine.
+        let _ = sender.send(response);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Client for invoking services exported by the connected client.
+///
+/// Use the generated stubs on top of this client for typed calls, eg:
+/// `AuthenticationClientStub::new(handle.client())`.
+pub struct RPCClient {
+    bindings: Arc<Mutex<ServiceBindings>>,
+    pending: Arc<Mutex<PendingCalls>>,
+    timeouts: Arc<CallTimeouts>,
+    commands: mpsc::UnboundedSender<SessionCommand>,
+}
+
+impl RPCClient {
+    /// Creates a new client which sends requests through the provided command channel.
+    ///
+    /// Services are addressed with the IDs negotiated within the bindings.
+    pub fn new(
+        bindings: Arc<Mutex<ServiceBindings>>,
+        pending: Arc<Mutex<PendingCalls>>,
+        timeouts: Arc<CallTimeouts>,
+        commands: mpsc::UnboundedSender<SessionCommand>,
+    ) -> Self {
+        Self {
+            bindings,
+            pending,
+            timeouts,
+            commands,
+        }
+    }
+
+    fn send_request(
+        &mut self,
+        service_hash: u32,
+        method_id: u32,
+        body: Bytes,
+        expects_response: bool,
+        timeout: Duration,
+    ) -> Result<Option<(u32, ResponseReceiver)>, RPCError> {
+        let service_id = self
+            .bindings
+            .lock()
+            .unwrap()
+            .imported_id(service_hash)
+            .ok_or(RPCError::UnboundService { service_hash })?;
+
+        let mut pending = self.pending.lock().unwrap();
+        let token = pending.allocate_token();
+        let mut header = Header {
+            service_id,
+            method_id: Some(method_id),
+            token,
+            size: Some(body.len() as u32),
+            ..Default::default()
+        };
+        if expects_response {
+            header.timeout = Some(as_millis(timeout));
+        }
+
+        let receiver_opt = if expects_response {
+            Some((token, pending.register(token)))
+        } else {
+            None
+        };
+
+        let packet = BNetPacket::new(header, body);
+        if self
+            .commands
+            .unbounded_send(SessionCommand::Send(packet))
+            .is_err()
+        {
+            pending.cancel(token);
+            return Err(RPCError::Canceled);
+        }
+
+        Ok(receiver_opt)
+    }
+}
+
+impl RpcChannel for RPCClient {
+    type Error = RPCError;
+
+    fn call(
+        &mut self,
+        service_hash: u32,
+        method_id: u32,
+        body: Bytes,
+        expects_response: bool,
+    ) -> RpcFuture<Option<Bytes>, Self::Error> {
+        let timeout = self.timeouts.timeout(service_hash, method_id);
+        let send_result =
+            self.send_request(service_hash, method_id, body, expects_response, timeout);
+        let (token, receiver) = match send_result {
+            Ok(Some(call)) => call,
+            // Fire-and-forget, the request was queued.
+            Ok(None) => return Box::new(future::ok(None)),
+            Err(error) => return Box::new(future::err(error)),
+        };
+
+        let pending = self.pending.clone();
+        let response = Deadline::new(receiver, Instant::now() + timeout)
+            .map_err(move |deadline_err| match deadline_err.into_inner() {
+                // The sender is dropped when the session stops.
+                Some(_) => RPCError::Canceled,
+                _ => {
+                    // Late responses for this token are unexpected from now on.
+                    pending.lock().unwrap().cancel(token);
+                    RPCError::Timeout { token }
+                }
+            })
+            .and_then(|response| {
+                let (header, body) = response.into_inner().split();
+                match header.status {
+                    Some(status) if status != 0 => Err(RPCError::FailedResponse {
+                        token: header.token,
+                        status,
+                    }),
+                    _ => Ok(Some(body)),
+                }
+            });
+        Box::new(response)
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpc::status::StatusCode;
+    use service::bnet::service_info::ImportedServiceID;
+
+    const SERVICE_HASH: u32 = 1898188341;
+
+    fn build_client() -> (
+        RPCClient,
+        Arc<Mutex<PendingCalls>>,
+        mpsc::UnboundedReceiver<SessionCommand>,
+    ) {
+        let mut bindings = ServiceBindings::new();
+        bindings.bind_imported(SERVICE_HASH, ImportedServiceID::AuthenticationClient as u32);
+        let pending = Arc::new(Mutex::new(PendingCalls::new()));
+        let (sender, receiver) = mpsc::unbounded();
+        let timeouts =
+            CallTimeouts::default().with_service(SERVICE_HASH, Duration::from_millis(50));
+        let client = RPCClient::new(
+            Arc::new(Mutex::new(bindings)),
+            pending.clone(),
+            Arc::new(timeouts),
+            sender,
+        );
+        (client, pending, receiver)
+    }
+
+    fn next_packet(receiver: mpsc::UnboundedReceiver<SessionCommand>) -> BNetPacket {
+        match receiver.into_future().wait() {
+            Ok((Some(SessionCommand::Send(packet)), _)) => packet,
+            _ => panic!("Expected a queued packet"),
+        }
+    }
+
+    #[test]
+    fn resolves_matching_response() {
+        let (mut client, pending, receiver) = build_client();
+        let call = client.call(SERVICE_HASH, 2, Bytes::from(&b"request"[..]), true);
+
+        let request = next_packet(receiver);
+        assert_eq!(
+            ImportedServiceID::AuthenticationClient as u32,
+            request.header().service_id
+        );
+        assert_eq!(Some(2), request.header().method_id);
+        assert_eq!(Some(50), request.header().timeout);
+        assert_eq!(1, pending.lock().unwrap().len());
+
+        let request = request.try_as_request().unwrap();
+        let response = Response::from_request(request, Bytes::from(&b"response"[..]));
+        pending.lock().unwrap().resolve(response).unwrap();
+
+        let body = call.wait().unwrap().unwrap();
+        assert_eq!(&b"response"[..], &body[..]);
+        assert!(pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fire_and_forget() {
+        let (mut client, pending, receiver) = build_client();
+        let result = client.call(SERVICE_HASH, 1, Bytes::new(), false).wait();
+
+        assert_eq!(None, result.unwrap());
+        assert!(pending.lock().unwrap().is_empty());
+        next_packet(receiver);
+    }
+
+    #[test]
+    fn rejects_unknown_token() {
+        let (_, pending, _) = build_client();
+        let header = Header {
+            token: 77,
+            ..Default::default()
+        };
+        let response = Response::from_status(&header, StatusCode::Ok);
+
+        let result = pending.lock().unwrap().resolve(response);
+        match result {
+            Err(RPCError::InvalidResponse { token }) => assert_eq!(77, token),
+            _ => panic!("Expected an invalid response error"),
+        }
+    }
+
+    #[test]
+    fn rejects_unbound_service() {
+        let (mut client, _, _) = build_client();
+        match client.call(12345, 1, Bytes::new(), true).wait() {
+            Err(RPCError::UnboundService { service_hash }) => assert_eq!(12345, service_hash),
+            _ => panic!("Expected an unbound service error"),
+        }
+    }
+
+    #[test]
+    fn times_out_without_response() {
+        let (mut client, pending, _receiver) = build_client();
+        let call = client.call(SERVICE_HASH, 2, Bytes::new(), true);
+
+        let mut runtime = ::tokio::runtime::current_thread::Runtime::new().unwrap();
+        match runtime.block_on(call) {
+            Err(RPCError::Timeout { .. }) => {}
+            _ => panic!("Expected a timeout error"),
+        }
+        assert!(pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancels_pending_calls() {
+        let (mut client, pending, _receiver) = build_client();
+        let call = client.call(SERVICE_HASH, 2, Bytes::new(), true);
+        pending.lock().unwrap().cancel_all();
+
+        match call.wait() {
+            Err(RPCError::Canceled) => {}
+            _ => panic!("Expected a canceled error"),
+        }
+        assert!(pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn timeout_precedence() {
+        let timeouts = CallTimeouts::new(Duration::from_secs(1))
+            .with_service(1, Duration::from_secs(2))
+            .with_method(1, 3, Duration::from_secs(3));
+
+        assert_eq!(Duration::from_secs(1), timeouts.timeout(2, 3));
+        assert_eq!(Duration::from_secs(2), timeouts.timeout(1, 1));
+        assert_eq!(Duration::from_secs(3), timeouts.timeout(1, 3));
+    }
+}
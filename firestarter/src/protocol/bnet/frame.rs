@@ -19,7 +19,10 @@ pub struct BNetPacket {
 
 impl BNetPacket {
     /// Constructs a new BNetPacket.
-    fn new(header: Header, body: Bytes) -> Self {
+    ///
+    /// The caller is responsible for keeping the size field of the header in sync
+    /// with the length of the body.
+    pub fn new(header: Header, body: Bytes) -> Self {
         Self { header, body }
     }
 
@@ -3,46 +3,68 @@
 //! Clients must succesfully complete the handshake before the server allocates memory
 //! for a new session.
 
+use firestarter_generated::proto::bnet::protocol::account::{
+    AccountServiceDispatcher, AccountServiceMethod,
+};
+use firestarter_generated::proto::bnet::protocol::authentication::{
+    AuthenticationServerDispatcher, AuthenticationServerMethod,
+};
+use firestarter_generated::proto::bnet::protocol::game_utilities::{
+    GameUtilitiesDispatcher, GameUtilitiesMethod,
+};
 use futures::prelude::*;
 use slog;
 use std::io;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use tokio_codec::Decoder;
 use tokio_tcp::TcpStream;
 use tokio_timer::Deadline;
 
 pub use self::error::*;
 use protocol::bnet::frame::BNetCodec;
-use protocol::bnet::session::LightWeightSession;
-use protocol::bnet::session::SessionError;
+use protocol::bnet::session::{ClientSession, LightWeightSession};
+use protocol::bnet::session::{SessionConfig, SessionError};
 use server::lobby::ServerShared;
-
-/// Maximum duration between accepting a new client and completing the handshake.
-/// The connection is closed when the deadline expires.
-const HANDSHAKE_DURATION_DEADLINE: u64 = 5;
+use service::bnet::account_service::AccountService;
+use service::bnet::authentication_service::AuthenticationService;
+use service::bnet::game_utilities_service::GameUtilitiesService;
 
 /// Perform the BNet protocol handshake with the provided client.
 pub fn handle_client(
     client: TcpStream,
     shared: Arc<Mutex<ServerShared>>,
+    config: SessionConfig,
     logger: slog::Logger,
 ) -> Result<impl Future<Item = (), Error = ()>, io::Error> {
     let peer_addr = client.peer_addr()?;
+    let (server_id, client_id, registry) = {
+        let mut shared = shared.lock().unwrap();
+        let client_id = shared.allocate_client_id();
+        (
+            shared.server_id().clone(),
+            client_id,
+            shared.sessions().clone(),
+        )
+    };
     // Values provided to new logger instances must be Owned+Send, so a String is created
     // from the peer address.
-    let peer_logger = logger.new(o!("peer" => format!("{:?}", peer_addr)));
+    let peer_logger = logger.new(o!(
+        "peer" => format!("{:?}", peer_addr),
+        "client_id" => client_id.label,
+    ));
     let handshake_logger = peer_logger.clone();
     let handler_logger = peer_logger.clone();
     trace!(peer_logger, "Client connected");
 
+    let handshake_deadline = Instant::now() + config.handshake_deadline();
     let codec = BNetCodec::new().framed(client);
-    let session = LightWeightSession::new(peer_addr, codec, peer_logger);
+    let session =
+        LightWeightSession::new(peer_addr, codec, config, server_id, client_id, peer_logger);
     let handshake = handshake_operation(session);
 
     // Wrap the handshake procedure in a deadline. The client connection is closed when the
     // deadline passes. All allocated resources are cleaned up as well.
-    let handshake_deadline = Instant::now() + Duration::from_secs(HANDSHAKE_DURATION_DEADLINE);
     let handshake = Deadline::new(handshake, handshake_deadline);
     let handshake = handshake
         .map_err(|deadline_err| match deadline_err.into_inner() {
@@ -56,7 +78,21 @@ pub fn handle_client(
             error
         })
         // The full session is a future itself. It will only complete when asked or errored (including timeout).
-        .and_then(|session| session.into_full_session())
+        .and_then(move |session| {
+            let mut session = session.into_full_session();
+            register_services(&mut session, &shared.lock().unwrap());
+            // The session is reachable for other sessions while it's running.
+            let client_id = session.client_id().clone();
+            if let Err(error) = registry.register(session.handle()) {
+                // The server is shutting down.
+                info!(session.logger(), "Session refused"; "reason" => %error);
+                let _ = session.handle().close();
+            }
+            session.then(move |result| {
+                registry.unregister(&client_id);
+                result
+            })
+        })
         .map_err(
             move |error| error!(handler_logger, "Client handler returned error"; "error" => ?error),
         )
@@ -64,6 +100,49 @@ pub fn handle_client(
     Ok(handshake)
 }
 
+/// Register the services which handle the requests of the client.
+///
+/// Services which aren't enabled within the server configuration are skipped.
+fn register_services(session: &mut ClientSession, shared: &ServerShared) {
+    if shared.service_enabled(AuthenticationServerMethod::SERVICE_HASH) {
+        register_authentication_service(session, shared);
+    }
+
+    if shared.service_enabled(AccountServiceMethod::SERVICE_HASH) {
+        let account_service = AccountService::new(
+            session.handle(),
+            shared.accounts().clone(),
+            shared.storage().clone(),
+        );
+        session.register_service(AccountServiceDispatcher(account_service));
+    }
+
+    if shared.service_enabled(GameUtilitiesMethod::SERVICE_HASH) {
+        let mut game_utilities_service =
+            GameUtilitiesService::new(session.handle(), shared.storage().clone());
+        for (&packet_id, handler) in shared.util_handlers() {
+            game_utilities_service.register_handler(packet_id, handler.clone());
+        }
+        session.register_service(GameUtilitiesDispatcher(game_utilities_service));
+    }
+}
+
+fn register_authentication_service(session: &mut ClientSession, shared: &ServerShared) {
+    let mut authentication_service = AuthenticationService::new(
+        session.handle(),
+        shared.accounts().clone(),
+        shared.sessions().clone(),
+        shared.token_signer().clone(),
+    );
+    for module in shared.auth_modules() {
+        authentication_service.register_module(module.clone());
+    }
+    if let Some(queue) = shared.logon_queue() {
+        authentication_service.set_logon_queue(queue.clone());
+    }
+    session.register_service(AuthenticationServerDispatcher(authentication_service));
+}
+
 fn handshake_operation(
     session: LightWeightSession,
 ) -> impl Future<Item = LightWeightSession, Error = HandshakeError> {
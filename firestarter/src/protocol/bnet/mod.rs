@@ -1,6 +1,8 @@
 //! Module containing code for interacting with BNet compatible clients.
 
+pub mod client;
 pub mod frame;
 pub mod handshake;
 pub mod packet_extension;
+pub mod process_id;
 pub mod session;
@@ -1,25 +1,146 @@
 //! Additionall methods for operating on [`BNetPacket`]s.
 
 use bytes::Bytes;
+use firestarter_generated::proto::bnet::protocol::{ErrorInfo, Header};
 
 use protocol::bnet::frame::BNetPacket;
+use rpc::status::StatusCode;
 use rpc::transport::{Request, Response};
+use service::bnet::service_info::ExportedServiceID;
+
+/// The service ID which is used to address responses.
+///
+/// The ResponseService is both imported and exported with the same ID, so this
+/// value is valid for both directions.
+pub const RESPONSE_SERVICE_ID: u32 = ExportedServiceID::ResponseService as u32;
 
 impl BNetPacket {
+    /// Returns true if this packet is addressed to the response service.
+    pub fn is_response(&self) -> bool {
+        self.header().service_id == RESPONSE_SERVICE_ID
+    }
+
     /// Try to parse a [`Request`] from this packet.
+    ///
+    /// Every packet which is not addressed to the response service is
+    /// considered a request.
     pub fn try_as_request(self) -> Result<Request<Self>, Self> {
-        unimplemented!()
+        if self.is_response() {
+            return Err(self);
+        }
+
+        Ok(Request::new(self))
     }
 
     /// Try to parse a [`Response`] from this packet.
+    ///
+    /// Only packets addressed to the response service are considered a response.
     pub fn try_as_response(self) -> Result<Response<Self>, Self> {
-        unimplemented!()
+        if !self.is_response() {
+            return Err(self);
+        }
+
+        Ok(Response::new(self))
     }
 }
 
 impl Response<BNetPacket> {
     /// Build a packet which is a direct [`Response`] to the mentioned [`Request`].
+    ///
+    /// The token and object id are copied from the request, so the receiver can link
+    /// this response to its request.
     pub fn from_request(request: Request<BNetPacket>, body: Bytes) -> Self {
-        unimplemented!()
+        let (request_header, _) = request.into_inner().split();
+        let mut header = Self::reply_header(&request_header);
+        header.size = Some(body.len() as u32);
+
+        Response::new(BNetPacket::new(header, body))
+    }
+
+    /// Build a packet which notifies the requester that its request failed.
+    ///
+    /// The response has no body and carries the provided status code.
+    pub fn from_status(request_header: &Header, status: StatusCode) -> Self {
+        let mut header = Self::reply_header(request_header);
+        header.size = Some(0);
+        header.status = Some(status.code());
+
+        Response::new(BNetPacket::new(header, Bytes::new()))
+    }
+
+    /// Attach additional information about a failure to this response.
+    pub fn with_error(self, error: ErrorInfo) -> Self {
+        let (mut header, body) = self.into_inner().split();
+        header.error.push(error);
+        Response::new(BNetPacket::new(header, body))
+    }
+
+    fn reply_header(request_header: &Header) -> Header {
+        Header {
+            service_id: RESPONSE_SERVICE_ID,
+            token: request_header.token,
+            object_id: request_header.object_id,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_packet(service_id: u32, token: u32, object_id: Option<u64>) -> BNetPacket {
+        let header = Header {
+            service_id,
+            method_id: Some(1),
+            token,
+            object_id,
+            size: Some(0),
+            ..Default::default()
+        };
+        BNetPacket::new(header, Bytes::new())
+    }
+
+    #[test]
+    fn classification() {
+        let request = build_packet(0, 1, None);
+        assert!(request.try_as_request().is_ok());
+
+        let request = build_packet(0, 1, None);
+        assert!(request.try_as_response().is_err());
+
+        let response = build_packet(RESPONSE_SERVICE_ID, 1, None);
+        assert!(response.try_as_response().is_ok());
+
+        let response = build_packet(RESPONSE_SERVICE_ID, 1, None);
+        assert!(response.try_as_request().is_err());
+    }
+
+    #[test]
+    fn response_from_request() {
+        let request = build_packet(3, 42, Some(7)).try_as_request().unwrap();
+        let body = Bytes::from(&b"payload"[..]);
+        let response = Response::from_request(request, body.clone()).into_inner();
+
+        let header = response.header();
+        assert_eq!(RESPONSE_SERVICE_ID, header.service_id);
+        assert_eq!(42, header.token);
+        assert_eq!(Some(7), header.object_id);
+        assert_eq!(Some(body.len() as u32), header.size);
+        assert_eq!(&body, response.body());
+    }
+
+    #[test]
+    fn response_from_status() {
+        let request = build_packet(3, 42, Some(7));
+        let response =
+            Response::from_status(request.header(), StatusCode::RpcInvalidMethod).into_inner();
+
+        let header = response.header();
+        assert_eq!(RESPONSE_SERVICE_ID, header.service_id);
+        assert_eq!(42, header.token);
+        assert_eq!(Some(3011), header.status);
+        assert_eq!(Some(0), header.size);
+        assert!(response.body().is_empty());
     }
 }
@@ -0,0 +1,91 @@
+//! Module for allocating process identifiers.
+//!
+//! Each endpoint of a BNet connection is identified by a [`ProcessId`]. The server
+//! generates its own identifier at startup and hands out a unique identifier to each
+//! connected client.
+//! These identifiers are used as host when addressing objects, eg within `ErrorInfo`.
+
+use chrono::Local;
+use firestarter_generated::proto::bnet::protocol::ProcessId;
+use rand::{self, Rng};
+
+#[derive(Debug)]
+/// Object handing out unique process identifiers for connected clients.
+///
+/// All identifiers share the epoch of the server identifier, the label is unique for
+/// each allocated identifier. Labels wrap around after exhausting the 32-bit space.
+pub struct ProcessIdAllocator {
+    server_id: ProcessId,
+    next_label: u32,
+}
+
+impl ProcessIdAllocator {
+    /// Creates a new allocator with a freshly generated server identifier.
+    ///
+    /// The label of the server is random, the epoch is the current timestamp.
+    pub fn new() -> Self {
+        let server_id = ProcessId {
+            label: random_label(),
+            epoch: Local::now().timestamp() as u32,
+        };
+        Self::with_server_id(server_id)
+    }
+
+    /// Creates a new allocator for the server with the provided identifier.
+    pub fn with_server_id(server_id: ProcessId) -> Self {
+        Self {
+            server_id,
+            next_label: 1,
+        }
+    }
+
+    /// Retrieve the identifier of the server.
+    pub fn server_id(&self) -> &ProcessId {
+        &self.server_id
+    }
+
+    /// Allocate a new identifier for a connected client.
+    pub fn allocate(&mut self) -> ProcessId {
+        loop {
+            let label = self.next_label;
+            self.next_label = self.next_label.wrapping_add(1);
+            // Zero is not a valid label and the server label is taken.
+            if label != 0 && label != self.server_id.label {
+                return ProcessId {
+                    label,
+                    epoch: self.server_id.epoch,
+                };
+            }
+        }
+    }
+}
+
+impl Default for ProcessIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn random_label() -> u32 {
+    // Zero is not a valid label.
+    rand::thread_rng().gen::<u32>().max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allocates_unique_ids() {
+        let mut allocator = ProcessIdAllocator::with_server_id(ProcessId {
+            label: 2,
+            epoch: 10,
+        });
+
+        let first = allocator.allocate();
+        let second = allocator.allocate();
+        assert_eq!((1, 10), (first.label, first.epoch));
+        // The label of the server is skipped.
+        assert_eq!((3, 10), (second.label, second.epoch));
+    }
+}
@@ -1,16 +1,111 @@
 //! Module with types that represent a client session.
 
+use firestarter_generated::proto::bnet::protocol::connection::{
+    ConnectionServiceDispatcher, ConnectionServiceStub, DisconnectNotification,
+};
+use firestarter_generated::proto::bnet::protocol::{EntityId, ProcessId};
 use futures::prelude::*;
+use futures::stream::FuturesUnordered;
+use futures::sync::mpsc;
 use slog;
+use std::collections::VecDeque;
+use std::fmt;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use tokio_codec::Framed;
 use tokio_tcp::TcpStream;
+use tokio_timer::Delay;
 
+use protocol::bnet::client::{CallTimeouts, PendingCalls, RPCClient};
 use protocol::bnet::frame::{BNetCodec, BNetPacket};
+use rpc::status::StatusCode;
+use rpc::system::{RPCError, Service, ServiceFuture};
 use rpc::transport::{Request, Response};
+use service::bnet::connection_service::{BindMode, ConnectionService};
+use service::bnet::router::{ServiceBindings, ServiceRouter};
 
 pub use self::error::*;
 
+/// Default maximum duration between accepting a new client and completing the handshake.
+pub const DEFAULT_HANDSHAKE_DEADLINE: Duration = Duration::from_secs(5);
+
+/// Default maximum duration between two packets received from the client.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, TypedBuilder)]
+/// Object for configuring the sessions of connected clients.
+///
+/// Construction is handled through the typed-builder crate, see
+/// [`SessionConfig::builder`].
+pub struct SessionConfig {
+    #[default = "DEFAULT_HANDSHAKE_DEADLINE"]
+    /// Maximum duration between accepting a new client and completing the handshake.
+    /// The connection is closed when the deadline expires.
+    handshake_deadline: Duration,
+
+    #[default = "CallTimeouts::default()"]
+    /// Deadlines for calls from the server towards services of the client.
+    call_timeouts: CallTimeouts,
+
+    #[default = "None"]
+    /// Artificial delay before answering echo requests, useful for simulating latency.
+    echo_delay: Option<Duration>,
+
+    #[default = "BindMode::Lenient"]
+    /// Defines how services, which are unknown to the server, are handled while binding.
+    bind_mode: BindMode,
+
+    #[default = "Vec::new()"]
+    /// Hashes of client services which the server depends on.
+    /// Clients which don't provide all of these are notified through the bind result
+    /// of the handshake.
+    required_services: Vec<u32>,
+
+    #[default = "Some(DEFAULT_IDLE_TIMEOUT)"]
+    /// Maximum duration between two packets received from the client.
+    /// The client is disconnected when this duration expires, `None` disables the check.
+    idle_timeout: Option<Duration>,
+}
+
+impl SessionConfig {
+    /// Retrieve the maximum duration of the handshake.
+    pub fn handshake_deadline(&self) -> Duration {
+        self.handshake_deadline
+    }
+
+    /// Retrieve the deadlines for calls towards the client.
+    pub fn call_timeouts(&self) -> &CallTimeouts {
+        &self.call_timeouts
+    }
+
+    /// Retrieve the delay before answering echo requests.
+    pub fn echo_delay(&self) -> Option<Duration> {
+        self.echo_delay
+    }
+
+    /// Retrieve how unknown services are handled while binding.
+    pub fn bind_mode(&self) -> BindMode {
+        self.bind_mode
+    }
+
+    /// Retrieve the hashes of client services which the server depends on.
+    pub fn required_services(&self) -> &[u32] {
+        &self.required_services
+    }
+
+    /// Retrieve the maximum duration a client is allowed to be idle.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
 #[derive(Debug)]
 /// A lightweight session is the smallest allocation necessary to handle a newly connected
 /// client.
@@ -20,21 +115,35 @@ pub use self::error::*;
 pub struct LightWeightSession {
     address: SocketAddr,
     codec: Option<Framed<TcpStream, BNetCodec>>,
+    config: SessionConfig,
     logger: slog::Logger,
+    bindings: ServiceBindings,
+    server_id: ProcessId,
+    client_id: ProcessId,
 }
 
 impl LightWeightSession {
     /// Creates a new session object.
+    ///
+    /// The server and client are identified towards each other by the provided
+    /// process identifiers.
     pub fn new(
         address: SocketAddr,
         codec: Framed<TcpStream, BNetCodec>,
+        config: SessionConfig,
+        server_id: ProcessId,
+        client_id: ProcessId,
         logger: slog::Logger,
     ) -> Self {
         let codec = Some(codec);
         Self {
             address,
             codec,
+            config,
             logger,
+            bindings: ServiceBindings::new(),
+            server_id,
+            client_id,
         }
     }
 
@@ -48,6 +157,26 @@ impl LightWeightSession {
         &self.logger
     }
 
+    /// Retrieve the configuration for this session.
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Store the service bindings which were negotiated with the client.
+    pub fn set_bindings(&mut self, bindings: ServiceBindings) {
+        self.bindings = bindings;
+    }
+
+    /// Retrieve the process ID which identifies the server towards the client.
+    pub fn server_id(&self) -> &ProcessId {
+        &self.server_id
+    }
+
+    /// Retrieve the process ID which was allocated for the client.
+    pub fn client_id(&self) -> &ProcessId {
+        &self.client_id
+    }
+
     fn reinstall_codec(&mut self, codec: Framed<TcpStream, BNetCodec>) {
         self.codec = Some(codec);
     }
@@ -93,23 +222,548 @@ impl LightWeightSession {
     /// Transforms the current lightweight session in a complete user session.
     ///
     /// This transformation comes with big allocations.
-    pub fn into_full_session(self) -> ClientSession {
-        unimplemented!()
+    pub fn into_full_session(mut self) -> ClientSession {
+        let codec = self.codec.take().unwrap();
+        let LightWeightSession {
+            address,
+            config,
+            logger,
+            bindings,
+            server_id,
+            client_id,
+            ..
+        } = self;
+        let mut session = ClientSession::new(address, codec, config, client_id, logger, bindings);
+        session.router.set_server_id(server_id);
+        session
     }
 }
 
 #[derive(Debug)]
+/// Commands which can be queued onto a running [`ClientSession`].
+pub enum SessionCommand {
+    /// Send the packet to the client.
+    Send(BNetPacket),
+    /// Flush all queued packets and close the connection afterwards.
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Authenticated identity of the player behind a session.
+pub struct Identity {
+    account: EntityId,
+    game_account: Option<EntityId>,
+    battle_tag: Option<String>,
+}
+
+impl Identity {
+    /// Creates a new identity for the provided account, without a selected game account.
+    pub fn new(account: EntityId, battle_tag: Option<String>) -> Self {
+        Self {
+            account,
+            game_account: None,
+            battle_tag,
+        }
+    }
+
+    /// Retrieve the account the client logged on with.
+    pub fn account(&self) -> &EntityId {
+        &self.account
+    }
+
+    /// Retrieve the game account which was selected by the client, if any.
+    pub fn game_account(&self) -> Option<&EntityId> {
+        self.game_account.as_ref()
+    }
+
+    /// Store the game account which was selected by the client.
+    pub fn set_game_account(&mut self, game_account: EntityId) {
+        self.game_account = Some(game_account);
+    }
+
+    /// Retrieve the display name of the account, if any.
+    pub fn battle_tag(&self) -> Option<&str> {
+        self.battle_tag.as_deref()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Handle for interacting with a running [`ClientSession`].
+///
+/// Handles are cheap to clone and can be moved to other tasks. Commands sent
+/// through a handle are executed by the session task itself.
+pub struct SessionHandle {
+    address: SocketAddr,
+    client_id: ProcessId,
+    logger: slog::Logger,
+    commands: mpsc::UnboundedSender<SessionCommand>,
+    client: RPCClient,
+    identity: Arc<RwLock<Option<Identity>>>,
+}
+
+impl SessionHandle {
+    /// Retrieve the address endpoint of the client.
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Retrieve the process ID which was allocated for the client.
+    pub fn client_id(&self) -> &ProcessId {
+        &self.client_id
+    }
+
+    /// Retrieve a specialized logger for the session.
+    pub fn logger(&self) -> &slog::Logger {
+        &self.logger
+    }
+
+    /// Retrieve a client for calling services exported by the connected client.
+    pub fn client(&self) -> RPCClient {
+        self.client.clone()
+    }
+
+    /// Retrieve the identity of the player, which is known after logon.
+    pub fn identity(&self) -> Option<Identity> {
+        self.identity.read().unwrap().clone()
+    }
+
+    /// Attach the provided identity to the session, `None` logs the player off.
+    ///
+    /// The identity is shared by all handles of the session.
+    pub fn set_identity(&self, identity: Option<Identity>) {
+        *self.identity.write().unwrap() = identity;
+    }
+
+    /// Queue a packet for delivery to the client.
+    ///
+    /// This is the method to use for server-initiated communication, like notifications.
+    pub fn send_packet(&self, packet: BNetPacket) -> Result<(), SessionError> {
+        self.send_command(SessionCommand::Send(packet))
+    }
+
+    /// Request the session to close the connection after all queued packets are sent.
+    pub fn close(&self) -> Result<(), SessionError> {
+        self.send_command(SessionCommand::Close)
+    }
+
+    /// Notify the client that it's being disconnected and close the session afterwards.
+    ///
+    /// The provided status and reason are presented to the client.
+    pub fn force_disconnect(
+        &self,
+        status: StatusCode,
+        reason: Option<String>,
+    ) -> impl Future<Item = (), Error = SessionError> {
+        let notification = DisconnectNotification {
+            error_code: status.code(),
+            reason,
+        };
+        let handle = self.clone();
+        ConnectionServiceStub::new(self.client())
+            .force_disconnect(&notification)
+            .map_err(SessionError::from)
+            .and_then(move |_| handle.close())
+    }
+
+    /// Check the results of notifications, which were sent through a stub on the client of
+    /// this session. Failures are logged.
+    ///
+    /// Only methods without response are accepted, because waiting for a response would
+    /// block the calling task.
+    pub fn check_notifications<I>(&self, results: I)
+    where
+        I: IntoIterator,
+        I::Item: Future<Item = (), Error = RPCError>,
+    {
+        // Notifications without response are queued when calling the stub, the returned
+        // futures complete immediately.
+        for result in results {
+            if let Err(error) = result.wait() {
+                warn!(self.logger(), "Failed to notify client"; "error" => %error);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    /// Creates a handle which isn't connected to a running session.
+    pub(crate) fn detached(address: SocketAddr, client_id: ProcessId) -> Self {
+        Self::detached_with_bindings(address, client_id, ServiceBindings::new()).0
+    }
+
+    #[cfg(test)]
+    /// Creates a handle which isn't connected to a running session, the returned
+    /// receiver yields all commands sent through the handle.
+    pub(crate) fn detached_with_bindings(
+        address: SocketAddr,
+        client_id: ProcessId,
+        bindings: ServiceBindings,
+    ) -> (Self, mpsc::UnboundedReceiver<SessionCommand>) {
+        let (commands, receiver) = mpsc::unbounded();
+        let client = RPCClient::new(
+            Arc::new(Mutex::new(bindings)),
+            Arc::new(Mutex::new(PendingCalls::new())),
+            Arc::new(CallTimeouts::default()),
+            commands.clone(),
+        );
+        let handle = SessionHandle {
+            address,
+            client_id,
+            logger: ::log::default_logger(),
+            commands,
+            client,
+            identity: Arc::new(RwLock::new(None)),
+        };
+        (handle, receiver)
+    }
+
+    #[cfg(test)]
+    /// Creates a handle for a test client which isn't connected to a running session.
+    ///
+    /// The client imports the services within `imports`, given as pairs of service hash
+    /// and imported service ID. See [`sent_method_ids`] for the commands of the receiver.
+    pub(crate) fn detached_importing(
+        imports: &[(u32, u32)],
+    ) -> (Self, mpsc::UnboundedReceiver<SessionCommand>) {
+        let mut bindings = ServiceBindings::new();
+        for &(hash, id) in imports {
+            bindings.bind_imported(hash, id);
+        }
+        Self::detached_with_bindings(
+            SocketAddr::from(([127, 0, 0, 1], 1000)),
+            ProcessId { label: 1, epoch: 1 },
+            bindings,
+        )
+    }
+
+    fn send_command(&self, command: SessionCommand) -> Result<(), SessionError> {
+        self.commands
+            .unbounded_send(command)
+            // An error is only returned when the session task has stopped.
+            .map_err(|_| SessionError::ClientDisconnect)
+    }
+}
+
+#[cfg(test)]
+/// Returns the method IDs of all packets queued by a detached handle.
+pub(crate) fn sent_method_ids(mut receiver: mpsc::UnboundedReceiver<SessionCommand>) -> Vec<u32> {
+    let mut methods = vec![];
+    // The handle holds a sender, so the receiver never finishes.
+    ::futures::future::poll_fn(|| {
+        while let Async::Ready(Some(command)) = receiver.poll()? {
+            if let SessionCommand::Send(packet) = command {
+                methods.push(packet.header().method_id.unwrap());
+            }
+        }
+        Ok::<_, ()>(Async::Ready(()))
+    })
+    .wait()
+    .unwrap();
+    methods
+}
+
 /// A complete user session.
 ///
 /// This structure contains the necessary data to properly communicate with a specific client.
-pub struct ClientSession {}
+/// The session is a future which reads incoming packets, dispatches requests and writes
+/// all responses and queued packets back to the client.
+/// It completes when the client disconnects or when the session is closed through a
+/// [`SessionHandle`].
+/// Clients which stay silent for longer than the configured idle timeout are disconnected.
+pub struct ClientSession {
+    address: SocketAddr,
+    client_id: ProcessId,
+    codec: Framed<TcpStream, BNetCodec>,
+    logger: slog::Logger,
+
+    bindings: Arc<Mutex<ServiceBindings>>,
+    router: ServiceRouter,
+    // Calls towards the client which are waiting for a response.
+    pending: Arc<Mutex<PendingCalls>>,
+    call_timeouts: Arc<CallTimeouts>,
+    identity: Arc<RwLock<Option<Identity>>>,
+
+    // Kept around to construct new handles.
+    command_sender: mpsc::UnboundedSender<SessionCommand>,
+    command_receiver: mpsc::UnboundedReceiver<SessionCommand>,
+    // Requests which are being processed.
+    in_flight: FuturesUnordered<ServiceFuture<BNetPacket>>,
+    // Packets waiting to be written into the codec.
+    outbound: VecDeque<BNetPacket>,
+    // Set when the session must stop reading and wind down.
+    closing: bool,
+    // Moment the last packet was received from the client.
+    last_activity: Instant,
+    idle_timeout: Option<Duration>,
+    // Fires when the client could have exceeded the idle timeout.
+    idle_timer: Option<Delay>,
+}
+
+impl fmt::Debug for ClientSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClientSession")
+            .field("address", &self.address)
+            .field("client_id", &self.client_id)
+            .field("in_flight", &self.in_flight.len())
+            .field("pending", &self.pending.lock().unwrap().len())
+            .field("outbound", &self.outbound.len())
+            .field("closing", &self.closing)
+            .field("last_activity", &self.last_activity)
+            .finish()
+    }
+}
+
+impl ClientSession {
+    fn new(
+        address: SocketAddr,
+        codec: Framed<TcpStream, BNetCodec>,
+        config: SessionConfig,
+        client_id: ProcessId,
+        logger: slog::Logger,
+        bindings: ServiceBindings,
+    ) -> Self {
+        let (command_sender, command_receiver) = mpsc::unbounded();
+        let bindings = Arc::new(Mutex::new(bindings));
+        let router = ServiceRouter::new(bindings.clone());
+        let idle_timeout = config.idle_timeout();
+        let last_activity = Instant::now();
+        let idle_timer = idle_timeout.map(|timeout| Delay::new(last_activity + timeout));
+
+        let mut session = Self {
+            address,
+            client_id,
+            codec,
+            logger,
+            bindings,
+            router,
+            pending: Arc::new(Mutex::new(PendingCalls::new())),
+            call_timeouts: Arc::new(config.call_timeouts().clone()),
+            identity: Arc::new(RwLock::new(None)),
+            command_sender,
+            command_receiver,
+            in_flight: FuturesUnordered::new(),
+            outbound: VecDeque::new(),
+            closing: false,
+            last_activity,
+            idle_timeout,
+            idle_timer,
+        };
+
+        let connection_service =
+            ConnectionService::new(session.handle(), session.bindings.clone(), &config);
+        session
+            .router
+            .register(ConnectionServiceDispatcher(connection_service));
+        session
+    }
+
+    /// Retrieve the address endpoint of the client.
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Retrieve the process ID which was allocated for the client.
+    pub fn client_id(&self) -> &ProcessId {
+        &self.client_id
+    }
+
+    /// Retrieve a specialized logger for this session.
+    pub fn logger(&self) -> &slog::Logger {
+        &self.logger
+    }
+
+    /// Build a new handle to interact with this session.
+    pub fn handle(&self) -> SessionHandle {
+        SessionHandle {
+            address: self.address,
+            client_id: self.client_id.clone(),
+            logger: self.logger.clone(),
+            commands: self.command_sender.clone(),
+            client: RPCClient::new(
+                self.bindings.clone(),
+                self.pending.clone(),
+                self.call_timeouts.clone(),
+                self.command_sender.clone(),
+            ),
+            identity: self.identity.clone(),
+        }
+    }
+
+    /// Retrieve the moment the last packet was received from the client.
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+
+    /// Retrieve the service bindings negotiated with the client.
+    pub fn bindings(&self) -> &Arc<Mutex<ServiceBindings>> {
+        &self.bindings
+    }
+
+    /// Register a service which handles requests of this client.
+    ///
+    /// Any previously registered service with the same hash is replaced.
+    pub fn register_service<S>(&mut self, service: S)
+    where
+        S: Service<BNetPacket> + Send + 'static,
+    {
+        self.router.register(service);
+    }
+
+    /// Route the request to the service handling it.
+    fn dispatch(&mut self, request: Request<BNetPacket>) -> ServiceFuture<BNetPacket> {
+        self.router.dispatch(request, &self.logger)
+    }
+
+    /// Handle a response sent by the client.
+    ///
+    /// The response completes the call which was sent with the same token.
+    fn receive_response(&mut self, response: Response<BNetPacket>) -> Result<(), RPCError> {
+        self.pending.lock().unwrap().resolve(response)
+    }
+
+    /// Disconnects the client when it didn't send anything during the idle timeout.
+    ///
+    /// The timer is not reset for each received packet, instead it's checked against the
+    /// last activity when it fires.
+    fn poll_idle(&mut self) -> Result<(), SessionError> {
+        let timeout = match self.idle_timeout {
+            Some(timeout) if !self.closing => timeout,
+            _ => return Ok(()),
+        };
+
+        loop {
+            match self.idle_timer.as_mut().map(Future::poll) {
+                Some(Ok(Async::Ready(_))) => {}
+                Some(Ok(Async::NotReady)) | None => return Ok(()),
+                // The timer is gone, so there is no way to detect idle clients anymore.
+                Some(Err(_)) => return Err(SessionError::Timeout),
+            }
+
+            let deadline = self.last_activity + timeout;
+            if deadline > Instant::now() {
+                self.idle_timer.as_mut().unwrap().reset(deadline);
+                continue;
+            }
+
+            warn!(self.logger, "Client exceeded idle timeout"; "timeout" => ?timeout);
+            let notification = DisconnectNotification {
+                error_code: StatusCode::RpcDisconnectIdle.code(),
+                reason: Some("Idle timeout".into()),
+            };
+            // The notification is queued when calling the stub, the returned future doesn't
+            // need to be driven.
+            let mut stub = ConnectionServiceStub::new(self.handle().client());
+            let _ = stub.force_disconnect(&notification);
+            self.closing = true;
+            self.idle_timer = None;
+            return Ok(());
+        }
+    }
+
+    /// Executes all queued commands.
+    fn poll_commands(&mut self) {
+        // The receiver never finishes because this session holds a sender itself.
+        while let Ok(Async::Ready(Some(command))) = self.command_receiver.poll() {
+            match command {
+                SessionCommand::Send(packet) => self.outbound.push_back(packet),
+                SessionCommand::Close => {
+                    trace!(self.logger, "Session close requested");
+                    self.closing = true;
+                }
+            }
+        }
+    }
+
+    /// Reads all available packets from the client.
+    ///
+    /// Returns true when the client has disconnected.
+    fn poll_inbound(&mut self) -> Result<bool, SessionError> {
+        while !self.closing {
+            let packet = match self.codec.poll()? {
+                Async::Ready(Some(packet)) => packet,
+                Async::Ready(None) => return Ok(true),
+                Async::NotReady => break,
+            };
+            self.last_activity = Instant::now();
+
+            match packet.try_as_request() {
+                Ok(request) => {
+                    let request_future = self.dispatch(request);
+                    self.in_flight.push(request_future);
+                }
+                Err(packet) => {
+                    // Packets which are not a request are always a response.
+                    let response = packet.try_as_response().unwrap();
+                    // A response can't be answered, so misbehaving clients are only noted.
+                    if let Err(error) = self.receive_response(response) {
+                        warn!(self.logger, "Dropped response"; "error" => %error);
+                    }
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Drives all requests which are being processed.
+    fn poll_in_flight(&mut self) -> Result<(), SessionError> {
+        while let Async::Ready(Some(response_opt)) = self.in_flight.poll()? {
+            if let Some(response) = response_opt {
+                self.outbound.push_back(response.into_inner());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes as many packets into the codec as possible.
+    ///
+    /// Returns true when all packets have been written out to the client.
+    fn poll_outbound(&mut self) -> Result<bool, SessionError> {
+        while let Some(packet) = self.outbound.pop_front() {
+            if let AsyncSink::NotReady(packet) = self.codec.start_send(packet)? {
+                self.outbound.push_front(packet);
+                break;
+            }
+        }
+
+        let flushed = self.codec.poll_complete()?.is_ready();
+        Ok(flushed && self.outbound.is_empty())
+    }
+}
 
 impl Future for ClientSession {
     type Item = ();
     type Error = SessionError;
 
     fn poll(&mut self) -> Poll<(), SessionError> {
-        unimplemented!()
+        // Idle checking comes first, so a disconnect notification is flushed before closing.
+        self.poll_idle()?;
+        self.poll_commands();
+
+        if self.poll_inbound()? {
+            // Nothing can be written to a disconnected client, so all remaining work
+            // is dropped.
+            trace!(self.logger, "Client disconnected");
+            return Ok(Async::Ready(()));
+        }
+
+        self.poll_in_flight()?;
+        let flushed = self.poll_outbound()?;
+
+        if self.closing && flushed && self.codec.close()?.is_ready() {
+            trace!(self.logger, "Session closed");
+            return Ok(Async::Ready(()));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+impl Drop for ClientSession {
+    fn drop(&mut self) {
+        // Handles outlive the session, so their calls would otherwise wait for a
+        // response until they time out.
+        self.pending.lock().unwrap().cancel_all();
     }
 }
 
@@ -155,3 +809,90 @@ mod error {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::BytesMut;
+    use firestarter_generated::proto::bnet::protocol::connection::{
+        ConnectionServiceMethod, EchoRequest,
+    };
+    use log;
+    use prost::Message;
+    use std::io::Read;
+    use std::net;
+    use tokio::reactor::Handle;
+    use tokio::runtime::current_thread::Runtime;
+    use tokio_codec::Decoder;
+
+    #[test]
+    fn cancels_pending_calls_when_finished() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server_stream, address) = listener.accept().unwrap();
+        let server_stream = TcpStream::from_std(server_stream, &Handle::default()).unwrap();
+
+        let codec = BNetCodec::new().framed(server_stream);
+        let session = LightWeightSession::new(
+            address,
+            codec,
+            SessionConfig::default(),
+            ProcessId::default(),
+            ProcessId { label: 1, epoch: 0 },
+            log::default_logger(),
+        )
+        .into_full_session();
+        let call = ConnectionServiceStub::new(session.handle().client())
+            .echo(&EchoRequest::default());
+        // The session finishes because the client disconnects.
+        drop(client);
+        let mut runtime = Runtime::new().unwrap();
+        runtime.block_on(session).unwrap();
+
+        match runtime.block_on(call) {
+            Err(RPCError::Canceled) => {}
+            _ => panic!("Expected a canceled error"),
+        }
+    }
+
+    #[test]
+    fn disconnects_idle_client() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server_stream, address) = listener.accept().unwrap();
+        let server_stream = TcpStream::from_std(server_stream, &Handle::default()).unwrap();
+
+        let config = SessionConfig::builder()
+            .idle_timeout(Some(Duration::from_millis(50)))
+            .build();
+        let codec = BNetCodec::new().framed(server_stream);
+        let session = LightWeightSession::new(
+            address,
+            codec,
+            config,
+            ProcessId::default(),
+            ProcessId { label: 1, epoch: 0 },
+            log::default_logger(),
+        )
+        .into_full_session();
+        Runtime::new().unwrap().block_on(session).unwrap();
+
+        let mut buffer = vec![];
+        client.read_to_end(&mut buffer).unwrap();
+        let packet = BNetCodec::new()
+            .decode(&mut BytesMut::from(buffer))
+            .unwrap()
+            .unwrap();
+        assert_eq!(0, packet.header().service_id);
+        assert_eq!(
+            Some(ConnectionServiceMethod::ForceDisconnect.id()),
+            packet.header().method_id
+        );
+
+        let notification = DisconnectNotification::decode(packet.body().clone()).unwrap();
+        assert_eq!(
+            StatusCode::RpcDisconnectIdle.code(),
+            notification.error_code
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! Module declaring types for handling incoming data and properly responding.
 
+pub mod status;
 pub mod system;
 pub mod transport;
 pub mod util;
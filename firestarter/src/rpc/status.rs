@@ -0,0 +1,171 @@
+//! Status codes which are understood by BNet clients.
+//!
+//! A status code is transmitted within the header of each response, a value of zero
+//! indicates success.
+
+use std::fmt;
+
+use rpc::system::RPCError;
+
+#[allow(missing_docs)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Status codes for indicating the result of a request.
+pub enum StatusCode {
+    // General
+    Ok = 0,
+    Internal = 1,
+    TimedOut = 2,
+    Denied = 3,
+    NotExists = 4,
+    NotStarted = 5,
+    InProgress = 6,
+    InvalidArgs = 7,
+    InvalidSubscriber = 8,
+    WaitTimeout = 9,
+    NotImplemented = 10,
+    OutOfMemory = 11,
+
+    // Logon
+    LogonModuleRequired = 100,
+    LogonModuleNotConfigured = 101,
+    LogonModuleTimeout = 102,
+    LogonAgreementRequired = 110,
+    LogonInvalidServerProof = 120,
+    LogonWebVerifyTimeout = 121,
+    LogonInvalidAuthToken = 122,
+
+    // RPC
+    RpcWriteFailed = 3000,
+    RpcServiceNotBound = 3001,
+    RpcTooManyRequests = 3002,
+    RpcPeerUnknown = 3003,
+    RpcPeerUnavailable = 3004,
+    RpcPeerDisconnected = 3005,
+    RpcRequestTimedOut = 3006,
+    RpcConnectionTimedOut = 3007,
+    RpcMalformedResponse = 3008,
+    RpcAccessDenied = 3009,
+    RpcInvalidService = 3010,
+    RpcInvalidMethod = 3011,
+    RpcInvalidObject = 3012,
+    RpcMalformedRequest = 3013,
+    RpcQuotaExceeded = 3014,
+    RpcNotImplemented = 3015,
+    RpcServerError = 3016,
+    RpcShutdown = 3017,
+    RpcDisconnect = 3018,
+    RpcDisconnectIdle = 3019,
+    RpcProtocolError = 3020,
+    RpcNotReady = 3021,
+}
+
+impl StatusCode {
+    /// Parse a status code from its numeric value.
+    pub fn from_u32(value: u32) -> Option<Self> {
+        use self::StatusCode::*;
+        let status = match value {
+            0 => Ok,
+            1 => Internal,
+            2 => TimedOut,
+            3 => Denied,
+            4 => NotExists,
+            5 => NotStarted,
+            6 => InProgress,
+            7 => InvalidArgs,
+            8 => InvalidSubscriber,
+            9 => WaitTimeout,
+            10 => NotImplemented,
+            11 => OutOfMemory,
+
+            100 => LogonModuleRequired,
+            101 => LogonModuleNotConfigured,
+            102 => LogonModuleTimeout,
+            110 => LogonAgreementRequired,
+            120 => LogonInvalidServerProof,
+            121 => LogonWebVerifyTimeout,
+            122 => LogonInvalidAuthToken,
+
+            3000 => RpcWriteFailed,
+            3001 => RpcServiceNotBound,
+            3002 => RpcTooManyRequests,
+            3003 => RpcPeerUnknown,
+            3004 => RpcPeerUnavailable,
+            3005 => RpcPeerDisconnected,
+            3006 => RpcRequestTimedOut,
+            3007 => RpcConnectionTimedOut,
+            3008 => RpcMalformedResponse,
+            3009 => RpcAccessDenied,
+            3010 => RpcInvalidService,
+            3011 => RpcInvalidMethod,
+            3012 => RpcInvalidObject,
+            3013 => RpcMalformedRequest,
+            3014 => RpcQuotaExceeded,
+            3015 => RpcNotImplemented,
+            3016 => RpcServerError,
+            3017 => RpcShutdown,
+            3018 => RpcDisconnect,
+            3019 => RpcDisconnectIdle,
+            3020 => RpcProtocolError,
+            3021 => RpcNotReady,
+            _ => return None,
+        };
+        Some(status)
+    }
+
+    /// Retrieve the numeric value, as transmitted within the header.
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Returns true if this code indicates success.
+    pub fn is_ok(self) -> bool {
+        self == StatusCode::Ok
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.code())
+    }
+}
+
+impl RPCError {
+    /// The status code which must be returned to the client when a request fails
+    /// with this error.
+    pub fn status(&self) -> StatusCode {
+        match *self {
+            RPCError::UnknownRequest { .. } => StatusCode::RpcInvalidService,
+            RPCError::InvalidRequest { .. } => StatusCode::RpcInvalidMethod,
+            RPCError::NotImplemented { .. } => StatusCode::RpcNotImplemented,
+            RPCError::Status { status } => status,
+            RPCError::InvalidResponse { .. } => StatusCode::RpcMalformedResponse,
+            RPCError::FailedResponse { status, .. } => {
+                StatusCode::from_u32(status).unwrap_or(StatusCode::RpcServerError)
+            }
+            RPCError::UnboundService { .. } => StatusCode::RpcServiceNotBound,
+            RPCError::UnsupportedService { .. } => StatusCode::RpcNotImplemented,
+            RPCError::Timeout { .. } => StatusCode::RpcRequestTimedOut,
+            RPCError::Canceled => StatusCode::RpcPeerDisconnected,
+            RPCError::ProtoDecode(_) => StatusCode::RpcMalformedRequest,
+            RPCError::ProtoEncode(_) => StatusCode::RpcServerError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numeric_roundtrip() {
+        for value in 0..4000 {
+            if let Some(status) = StatusCode::from_u32(value) {
+                assert_eq!(value, status.code());
+            }
+        }
+
+        assert_eq!(Some(StatusCode::RpcNotReady), StatusCode::from_u32(3021));
+        assert_eq!(None, StatusCode::from_u32(3022));
+    }
+}
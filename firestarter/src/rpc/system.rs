@@ -1,9 +1,41 @@
 //! Important types for defining an RPC service.
 
+use futures::Future;
+
+use rpc::transport::{Request, Response};
+use rpc::util::hash_service_name;
+
 pub use self::error::*;
 
+/// Future resolving into the (optional) response for a dispatched request.
+///
+/// Requests which don't expect an answer resolve into `None`.
+pub type ServiceFuture<Packet> =
+    Box<dyn Future<Item = Option<Response<Packet>>, Error = RPCError> + Send>;
+
+/// Trait for objects handling RPC requests for one specific service.
+///
+/// The service is addressed by the (FNV-1a) hash of its fully qualified name, while
+/// individual methods are addressed by their method id.
+pub trait Service<Packet> {
+    /// The fully qualified name of the service.
+    ///
+    /// eg: `bnet.protocol.connection.ConnectionService`
+    fn name(&self) -> &'static str;
+
+    /// The hash identifying this service.
+    fn hash(&self) -> u32 {
+        hash_service_name(self.name())
+    }
+
+    /// Handle the request which was addressed to the provided method.
+    fn call(&mut self, method_id: u32, request: Request<Packet>) -> ServiceFuture<Packet>;
+}
+
 mod error {
+    use firestarter_generated::rpc::UnimplementedMethod;
     use prost;
+    use rpc::status::StatusCode;
 
     #[derive(Debug, Fail)]
     /// Error type related to executing logic trigger by an RPC request or response.
@@ -28,6 +60,26 @@ mod error {
             method_id: u32,
         },
 
+        #[fail(
+            display = "Method {:} of service {:} is not implemented",
+            method_id,
+            service_name
+        )]
+        /// Failure to process the request because the service doesn't implement the method.
+        NotImplemented {
+            /// The name of the service that was addressed.
+            service_name: &'static str,
+            /// The method id that was addressed.
+            method_id: u32,
+        },
+
+        #[fail(display = "The request failed with status {:}", status)]
+        /// The service rejected the request with the provided status.
+        Status {
+            /// The status code which is returned to the requester.
+            status: StatusCode,
+        },
+
         #[fail(display = "The client sent an unrequested response, token {:}", token)]
         /// Failure to process the response because there was no known request linked to the token.
         InvalidResponse {
@@ -35,6 +87,44 @@ mod error {
             token: u32,
         },
 
+        #[fail(
+            display = "The client responded to request {:} with status {:}",
+            token,
+            status
+        )]
+        /// The client answered a request with a failure status.
+        FailedResponse {
+            /// The token of the request.
+            token: u32,
+            /// The status code as found in the response packet.
+            status: u32,
+        },
+
+        #[fail(display = "The client did not bind service with hash {:}", service_hash)]
+        /// Failure to send a request because the client didn't export the service.
+        UnboundService {
+            /// The hash of the addressed service.
+            service_hash: u32,
+        },
+
+        #[fail(display = "The service with hash {:} is not supported", service_hash)]
+        /// Failure to process the request because the client bound a service unknown to us.
+        UnsupportedService {
+            /// The hash of the addressed service.
+            service_hash: u32,
+        },
+
+        #[fail(display = "The request with token {:} did not complete in time", token)]
+        /// Failure to complete a request before its deadline.
+        Timeout {
+            /// The token of the request.
+            token: u32,
+        },
+
+        #[fail(display = "The session stopped before the call completed")]
+        /// Failure to complete a call because the session was closed.
+        Canceled,
+
         #[fail(display = "Error while decoding a Protobuffer payload: {:}", _0)]
         /// Failure to construct an object from a proto message.
         ProtoDecode(#[cause] prost::DecodeError),
@@ -57,4 +147,21 @@ mod error {
             RPCError::ProtoEncode(x)
         }
     }
+
+    // Usability improvement
+    impl From<UnimplementedMethod> for RPCError {
+        fn from(x: UnimplementedMethod) -> Self {
+            RPCError::NotImplemented {
+                service_name: x.service_name,
+                method_id: x.method_id,
+            }
+        }
+    }
+
+    // Usability improvement
+    impl From<StatusCode> for RPCError {
+        fn from(status: StatusCode) -> Self {
+            RPCError::Status { status }
+        }
+    }
 }
@@ -14,6 +14,24 @@ pub fn hash_service_name<S: AsRef<str>>(name: S) -> u32 {
     return hash;
 }
 
+/// Parses the hexadecimal string into bytes, whitespace between digits is ignored.
+///
+/// `None` is returned if the string holds other characters or an odd amount of digits.
+pub fn parse_hex(input: &str) -> Option<Vec<u8>> {
+    let digits = input
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .map(|digit| digit.to_digit(16).map(|value| value as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    digits
+        .chunks(2)
+        .map(|pair| match *pair {
+            [high, low] => Some(high << 4 | low),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -32,4 +50,12 @@ mod test {
         let hash_response = hash_service_name(response_test);
         println!("{:} - {:}", hash_response, response_test);
     }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(Some(vec![0x0a, 0xff, 0x10]), parse_hex("0aFF 10"));
+        assert_eq!(Some(vec![]), parse_hex(""));
+        assert_eq!(None, parse_hex("abc"));
+        assert_eq!(None, parse_hex("0g"));
+    }
 }
@@ -0,0 +1,349 @@
+//! Local store of the accounts which are allowed to log on.
+//!
+//! The authentication service looks up accounts through the [`AccountStore`] trait, so
+//! the source of accounts can be replaced. [`AccountList`] is a simple implementation
+//! which is kept in memory and can be loaded from a text file.
+//!
+//! # File format
+//! Each line describes one account, empty lines and lines starting with `#` are ignored.
+//! Fields are separated by whitespace:
+//! ```text
+//! # id  email                credential          battle tag (optional)
+//! 1     alice@example.com    password:hunter2    Alice#1234
+//! 2     bob@example.com      token:0a1b2c3d
+//! ```
+//! Password credentials are compared against the credentials sent by the client as text,
+//! token credentials are hexadecimal and compared as raw bytes.
+
+use firestarter_generated::proto::bnet::protocol::EntityId;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use rpc::util::parse_hex;
+
+pub use self::error::*;
+
+/// High part of account entity IDs.
+pub const ACCOUNT_ID_HIGH: u64 = 0x0100_0000_0000_0000;
+
+/// Program identifier of Hearthstone ("WTCG").
+pub const HEARTHSTONE_PROGRAM: u32 = 0x5754_4347;
+
+/// High part of game account entity IDs, this is the game account type combined with
+/// the Hearthstone program ("WTCG").
+pub const GAME_ACCOUNT_ID_HIGH: u64 = 0x0200_0000_5754_4347;
+
+/// Region of the game account which comes with each account, "US".
+pub const DEFAULT_REGION: u32 = 1;
+
+#[derive(Clone, PartialEq, Eq)]
+/// Secret which proves the identity of the player.
+pub enum Credential {
+    /// Password in plain text.
+    Password(String),
+    /// Opaque token.
+    Token(Vec<u8>),
+}
+
+impl Credential {
+    /// Returns true if the secret sent by the client matches this credential.
+    pub fn verify(&self, secret: &[u8]) -> bool {
+        let expected = match *self {
+            Credential::Password(ref password) => password.as_bytes(),
+            Credential::Token(ref token) => token.as_slice(),
+        };
+        // The comparison time doesn't depend on the position of the first mismatch.
+        expected.len() == secret.len()
+            && expected
+                .iter()
+                .zip(secret)
+                .fold(0, |diff, (left, right)| diff | (left ^ right))
+                == 0
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Secrets must never end up in logs.
+        match *self {
+            Credential::Password(_) => write!(f, "Password(..)"),
+            Credential::Token(_) => write!(f, "Token(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Account which is allowed to log on.
+pub struct Account {
+    id: u64,
+    email: String,
+    credential: Credential,
+    battle_tag: Option<String>,
+}
+
+impl Account {
+    /// Creates a new account without battle tag.
+    pub fn new(id: u64, email: String, credential: Credential) -> Self {
+        Self {
+            id,
+            email,
+            credential,
+            battle_tag: None,
+        }
+    }
+
+    /// Set the display name of the account.
+    pub fn with_battle_tag(mut self, battle_tag: String) -> Self {
+        self.battle_tag = Some(battle_tag);
+        self
+    }
+
+    /// Retrieve the numeric identifier of the account.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retrieve the email address the player logs on with.
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// Retrieve the secret of the account.
+    pub fn credential(&self) -> &Credential {
+        &self.credential
+    }
+
+    /// Retrieve the display name of the account, if any.
+    pub fn battle_tag(&self) -> Option<&str> {
+        self.battle_tag.as_deref()
+    }
+
+    /// Retrieve the entity ID of the account.
+    pub fn entity_id(&self) -> EntityId {
+        EntityId {
+            high: ACCOUNT_ID_HIGH,
+            low: self.id,
+        }
+    }
+
+    /// Retrieve the entity ID of the Hearthstone game account of this account.
+    ///
+    /// Each account owns exactly one game account.
+    pub fn game_account_id(&self) -> EntityId {
+        EntityId {
+            high: GAME_ACCOUNT_ID_HIGH,
+            low: self.id,
+        }
+    }
+}
+
+/// Account store which is shared between all sessions.
+pub type SharedAccountStore = Arc<dyn AccountStore>;
+
+/// Source of accounts which are allowed to log on.
+pub trait AccountStore: fmt::Debug + Send + Sync {
+    /// Find the account which logs on with the provided email address.
+    ///
+    /// Email addresses are matched case insensitively.
+    fn find_by_email(&self, email: &str) -> Option<Account>;
+
+    /// Find the account with the provided identifier.
+    fn find_by_id(&self, id: u64) -> Option<Account>;
+}
+
+#[derive(Debug, Clone, Default)]
+/// Account store which is kept in memory.
+///
+/// See the module documentation for the file format used by [`AccountList::load`].
+pub struct AccountList {
+    accounts: HashMap<u64, Account>,
+    // Maps lowercase email addresses onto account IDs.
+    by_email: HashMap<String, u64>,
+}
+
+impl AccountList {
+    /// Creates a new and empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load all accounts from the provided file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, AccountError> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Parse all accounts from the contents of an account file.
+    pub fn parse(contents: &str) -> Result<Self, AccountError> {
+        let mut list = Self::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let account = parse_account(line).map_err(|reason| AccountError::Parse {
+                line: index + 1,
+                reason,
+            })?;
+            list.insert(account)?;
+        }
+        Ok(list)
+    }
+
+    /// Add the provided account.
+    ///
+    /// Each account must have a unique identifier and email address.
+    pub fn insert(&mut self, account: Account) -> Result<(), AccountError> {
+        let email = account.email().to_lowercase();
+        if self.accounts.contains_key(&account.id()) {
+            Err(AccountError::DuplicateId { id: account.id() })?;
+        }
+        if self.by_email.contains_key(&email) {
+            return Err(AccountError::DuplicateEmail { email });
+        }
+
+        self.by_email.insert(email, account.id());
+        self.accounts.insert(account.id(), account);
+        Ok(())
+    }
+
+    /// Returns the amount of accounts.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns true if the list contains no accounts.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+impl AccountStore for AccountList {
+    fn find_by_email(&self, email: &str) -> Option<Account> {
+        self.by_email
+            .get(&email.to_lowercase())
+            .and_then(|id| self.accounts.get(id))
+            .cloned()
+    }
+
+    fn find_by_id(&self, id: u64) -> Option<Account> {
+        self.accounts.get(&id).cloned()
+    }
+}
+
+fn parse_account(line: &str) -> Result<Account, &'static str> {
+    let mut fields = line.split_whitespace();
+    let id = fields
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or("invalid account id")?;
+    let email = fields.next().ok_or("missing email address")?;
+    let credential = match fields.next().map(|field| field.splitn(2, ':')) {
+        Some(mut parts) => match (parts.next(), parts.next()) {
+            (Some("password"), Some(password)) => Credential::Password(password.to_string()),
+            (Some("token"), Some(token)) => {
+                let token = parse_hex(token).filter(|token| !token.is_empty());
+                Credential::Token(token.ok_or("invalid hexadecimal token")?)
+            }
+            _ => return Err("credential must start with 'password:' or 'token:'"),
+        },
+        None => return Err("missing credential"),
+    };
+    let battle_tag = fields.next();
+    if fields.next().is_some() {
+        return Err("too many fields");
+    }
+
+    let account = Account::new(id, email.to_string(), credential);
+    Ok(match battle_tag {
+        Some(battle_tag) => account.with_battle_tag(battle_tag.to_string()),
+        None => account,
+    })
+}
+
+mod error {
+    use std::io;
+
+    #[derive(Debug, Fail)]
+    /// Error type related to loading accounts.
+    pub enum AccountError {
+        #[fail(display = "Line {} of the account file is invalid: {}", line, reason)]
+        /// Failure to parse a line of the account file.
+        Parse {
+            /// The line number, starting from 1.
+            line: usize,
+            /// Description of the problem.
+            reason: &'static str,
+        },
+
+        #[fail(display = "Account ID {} is used more than once", id)]
+        /// Failure to add an account because its identifier is already used.
+        DuplicateId {
+            /// The duplicate identifier.
+            id: u64,
+        },
+
+        #[fail(display = "Email address {} is used more than once", email)]
+        /// Failure to add an account because its email address is already used.
+        DuplicateEmail {
+            /// The duplicate email address.
+            email: String,
+        },
+
+        #[fail(display = "{}", _0)]
+        /// Failure to read the account file.
+        Io(#[cause] io::Error),
+    }
+
+    // Usability improvement
+    impl From<io::Error> for AccountError {
+        fn from(x: io::Error) -> Self {
+            AccountError::Io(x)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_account_file() {
+        let contents = "
+            # Comment
+            1 Alice@Example.com password:hunter2 Alice#1234
+            2 bob@example.com   token:0a1b
+        ";
+        let list = AccountList::parse(contents).unwrap();
+        assert_eq!(2, list.len());
+
+        let alice = list.find_by_email("alice@example.com").unwrap();
+        assert_eq!(1, alice.id());
+        assert_eq!(Some("Alice#1234"), alice.battle_tag());
+        assert!(alice.credential().verify(b"hunter2"));
+        assert!(!alice.credential().verify(b"hunter3"));
+
+        let bob = list.find_by_id(2).unwrap();
+        assert!(bob.credential().verify(&[0x0a, 0x1b]));
+        assert!(list.find_by_email("carol@example.com").is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        match AccountList::parse("1 alice@example.com secret") {
+            Err(AccountError::Parse { line: 1, .. }) => {}
+            _ => panic!("Expected a parse error"),
+        }
+        match AccountList::parse("1 a@example.com token:abc") {
+            Err(AccountError::Parse { line: 1, .. }) => {}
+            _ => panic!("Expected a parse error"),
+        }
+        match AccountList::parse("1 a@example.com password:x\n1 b@example.com password:y") {
+            Err(AccountError::DuplicateId { id: 1 }) => {}
+            _ => panic!("Expected a duplicate ID error"),
+        }
+    }
+}
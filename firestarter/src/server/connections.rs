@@ -0,0 +1,189 @@
+//! Bookkeeping of open client connections.
+//!
+//! Each accepted connection occupies a slot until its handler task stops, which
+//! includes the handshake. Connections are refused when the global limit, or the limit
+//! for a single peer IP-address, is reached.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+pub use self::error::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Upper bounds for the amount of open connections.
+pub struct ConnectionLimits {
+    max_connections: usize,
+    max_connections_per_ip: Option<usize>,
+}
+
+impl ConnectionLimits {
+    /// Creates a new set of limits.
+    ///
+    /// A `max_connections_per_ip` of `None` doesn't limit connections per IP-address.
+    pub fn new(max_connections: usize, max_connections_per_ip: Option<usize>) -> Self {
+        Self {
+            max_connections,
+            max_connections_per_ip,
+        }
+    }
+
+    /// Retrieve the maximum amount of open connections.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Retrieve the maximum amount of open connections from one IP-address.
+    pub fn max_connections_per_ip(&self) -> Option<usize> {
+        self.max_connections_per_ip
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+#[derive(Debug, Clone)]
+/// Object counting open connections against the configured limits.
+///
+/// The tracker is cheap to clone, all clones operate on the same counters.
+pub struct ConnectionTracker {
+    limits: ConnectionLimits,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl ConnectionTracker {
+    /// Creates a new tracker enforcing the provided limits.
+    pub fn new(limits: ConnectionLimits) -> Self {
+        Self {
+            limits,
+            counters: Arc::new(Mutex::new(Counters::default())),
+        }
+    }
+
+    /// Retrieve the enforced limits.
+    pub fn limits(&self) -> ConnectionLimits {
+        self.limits
+    }
+
+    /// Returns the amount of open connections.
+    pub fn open_connections(&self) -> usize {
+        self.counters.lock().unwrap().total
+    }
+
+    /// Returns the amount of open connections from the provided IP-address.
+    pub fn open_connections_from(&self, ip: &IpAddr) -> usize {
+        let counters = self.counters.lock().unwrap();
+        counters.per_ip.get(ip).cloned().unwrap_or(0)
+    }
+
+    /// Reserve a slot for a new connection from the provided IP-address.
+    ///
+    /// The slot is released when the returned guard is dropped.
+    pub fn acquire(&self, ip: IpAddr) -> Result<ConnectionGuard, LimitError> {
+        let mut counters = self.counters.lock().unwrap();
+        if counters.total >= self.limits.max_connections {
+            Err(LimitError::TooManyConnections {
+                max: self.limits.max_connections,
+            })?;
+        }
+        let per_ip = counters.per_ip.get(&ip).cloned().unwrap_or(0);
+        if let Some(max) = self.limits.max_connections_per_ip {
+            if per_ip >= max {
+                Err(LimitError::TooManyConnectionsFromPeer { ip, max })?;
+            }
+        }
+
+        counters.total += 1;
+        counters.per_ip.insert(ip, per_ip + 1);
+        Ok(ConnectionGuard {
+            ip,
+            counters: self.counters.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+/// Slot occupied by one open connection.
+///
+/// Move this guard into the task handling the connection, the slot is released
+/// when the guard is dropped.
+pub struct ConnectionGuard {
+    ip: IpAddr,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.total -= 1;
+        let remaining = {
+            let per_ip = counters.per_ip.get_mut(&self.ip).unwrap();
+            *per_ip -= 1;
+            *per_ip
+        };
+        if remaining == 0 {
+            counters.per_ip.remove(&self.ip);
+        }
+    }
+}
+
+mod error {
+    use std::net::IpAddr;
+
+    #[derive(Debug, Fail)]
+    /// Error type related to refusing new connections.
+    pub enum LimitError {
+        #[fail(display = "The maximum of {} open connections is reached", max)]
+        /// Failure to accept a connection because of the global limit.
+        TooManyConnections {
+            /// The configured limit.
+            max: usize,
+        },
+
+        #[fail(
+            display = "The maximum of {} open connections from {} is reached",
+            max,
+            ip
+        )]
+        /// Failure to accept a connection because of the limit for one IP-address.
+        TooManyConnectionsFromPeer {
+            /// The address of the refused peer.
+            ip: IpAddr,
+            /// The configured limit.
+            max: usize,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enforces_limits() {
+        let tracker = ConnectionTracker::new(ConnectionLimits::new(3, Some(2)));
+        let first_ip = IpAddr::from([127, 0, 0, 1]);
+        let second_ip = IpAddr::from([127, 0, 0, 2]);
+
+        let first = tracker.acquire(first_ip).unwrap();
+        let _second = tracker.acquire(first_ip).unwrap();
+        match tracker.acquire(first_ip) {
+            Err(LimitError::TooManyConnectionsFromPeer { max: 2, .. }) => {}
+            _ => panic!("Expected the per IP limit to be reached"),
+        }
+
+        let _third = tracker.acquire(second_ip).unwrap();
+        match tracker.acquire(second_ip) {
+            Err(LimitError::TooManyConnections { max: 3 }) => {}
+            _ => panic!("Expected the global limit to be reached"),
+        }
+
+        drop(first);
+        assert_eq!(2, tracker.open_connections());
+        assert_eq!(1, tracker.open_connections_from(&first_ip));
+        assert!(tracker.acquire(second_ip).is_ok());
+    }
+}
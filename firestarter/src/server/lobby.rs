@@ -3,33 +3,60 @@
 //! A lobby server is the program responsible for authenticating players
 //! and responding to in-game activities.
 
+use firestarter_generated::proto::bnet::protocol::notification::{
+    Notification, NotificationListenerStub,
+};
+use firestarter_generated::proto::bnet::protocol::{EntityId, ProcessId};
 use futures::prelude::*;
+use futures::sync::{mpsc, oneshot};
 use slog;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 use tokio::runtime;
 use tokio_executor as executor;
-use tokio_tcp::TcpListener;
+use tokio_tcp::{Incoming, TcpListener};
+use tokio_timer::{Delay, Interval};
 
 use log;
 use protocol::bnet;
+use protocol::bnet::process_id::ProcessIdAllocator;
+use protocol::bnet::session::{SessionConfig, SessionHandle};
+use rpc::status::StatusCode;
+use server::accounts::{AccountList, SharedAccountStore};
+use server::connections::{ConnectionLimits, ConnectionTracker};
+use server::logon_queue::{LogonQueue, LogonQueueConfig};
+use server::registry::{SessionInfo, SessionRegistry};
+use server::storage::memory::MemoryStorage;
+use server::storage::SharedStorage;
+use server::tokens::{TokenSigner, DEFAULT_TOKEN_LIFETIME};
+use service::bnet::auth_module::BoxedAuthModule;
+use service::bnet::game_utilities_service::UtilHandlerMap;
+use service::bnet::util_handlers;
 
 // Re-export all types defined within the error submodule (see below)
 pub use self::error::*;
 
 /// Amount of time to pause accepting new clients when an I/O error is returned
 /// by the listening socket.
-const _DEFAULT_ERROR_TIMEOUT: Duration = Duration::from_millis(100);
+pub const DEFAULT_ERROR_TIMEOUT: Duration = Duration::from_millis(100);
 
 /// Maximum amount of clients to accept.
 /// This value is set to make sure we don't deplete all system resources.
 ///
 /// There is a hard limit on the amount of connections a system can handle, which
 /// depends on your OS.
-const _DEFAULT_MAX_CONNECTIONS: usize = 1000;
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1000;
 
-#[derive(Debug, Default, Clone, Copy, TypedBuilder)]
+/// Amount of time to wait before retrying to bind on the same port.
+pub const DEFAULT_BIND_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Interval for checking whether all sessions have finished during a graceful shutdown.
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, TypedBuilder)]
 /// Object for defining how a socket binding failure must be resolved.
 pub struct BindRetryConfig {
     /// Allowed amount of retries before returning an error.
@@ -40,7 +67,22 @@ pub struct BindRetryConfig {
     ///
     /// The next port is calculated as current_port + 1. An error is returned
     /// If the result would overflow regardless of retry count.
+    /// The same port is retried after `retry_delay` if this switch is off.
     try_next_port: bool,
+
+    #[default = "DEFAULT_BIND_RETRY_DELAY"]
+    /// Amount of time to wait before retrying to bind on the same port.
+    retry_delay: Duration,
+}
+
+impl Default for BindRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            try_next_port: false,
+            retry_delay: DEFAULT_BIND_RETRY_DELAY,
+        }
+    }
 }
 
 #[derive(Debug, TypedBuilder)]
@@ -58,19 +100,185 @@ pub struct BindRetryConfig {
 pub struct ServerConfig {
     /// A combination of IP-address and port for the server to bind on.
     bind_address: SocketAddr,
+
+    #[default = "Vec::new()"]
+    /// Additional addresses for the server to bind on, eg to listen on both IPv4
+    /// and IPv6.
+    /// Clients accepted on any of these addresses are handled identically.
+    additional_bind_addresses: Vec<SocketAddr>,
+
     /// Controls how a binding failure must be resolved.
+    /// This applies to each bind address individually.
     bind_fallback: BindRetryConfig,
 
+    #[default = "DEFAULT_MAX_CONNECTIONS"]
+    /// Maximum amount of open connections, including connections which are handshaking.
+    max_connections: usize,
+
+    #[default = "None"]
+    /// Maximum amount of open connections from one IP-address, `None` disables this limit.
+    max_connections_per_ip: Option<usize>,
+
+    #[default = "DEFAULT_ERROR_TIMEOUT"]
+    /// Amount of time to pause accepting new clients when the listening socket
+    /// returns an error, eg when the process runs out of file descriptors.
+    accept_error_timeout: Duration,
+
+    #[default = "SessionConfig::default()"]
+    /// Settings applied to the session of each connected client.
+    session: SessionConfig,
+
+    #[default = "None"]
+    /// Hashes of the fully qualified names of the services provided to clients, see
+    /// [`hash_service_name`](::rpc::util::hash_service_name).
+    /// All services are provided when this is `None`. The connection service is always
+    /// provided, because sessions depend on it.
+    enabled_services: Option<HashSet<u32>>,
+
+    #[default = "Arc::new(AccountList::new())"]
+    /// Accounts which are allowed to log on.
+    /// No client can log on with the default, empty, list.
+    accounts: SharedAccountStore,
+
+    #[default = "Vec::new()"]
+    /// Modules which each client must pass during logon, in order.
+    /// Clients log on with the credentials within their logon request when this list is
+    /// empty.
+    auth_modules: Vec<BoxedAuthModule>,
+
+    #[default = "TokenSigner::random(DEFAULT_TOKEN_LIFETIME)"]
+    /// Issues and verifies single sign-on tokens.
+    /// The default signer uses a random key, so tokens are invalidated when the server
+    /// restarts.
+    tokens: TokenSigner,
+
+    #[default = "Arc::new(MemoryStorage::new())"]
+    /// Storage holding the state of players.
+    /// The default storage keeps all state in memory, so it's lost when the server stops.
+    storage: SharedStorage,
+
+    #[default = "util_handlers::login_handlers()"]
+    /// Handlers of Pegasus util packets, by packet ID.
+    /// Packets without handler are refused. The default handlers answer the packets sent
+    /// during logon.
+    util_handlers: UtilHandlerMap,
+
+    #[default = "None"]
+    /// Configuration of the queue which each logon passes.
+    /// Logons are processed immediately when no queue is configured.
+    logon_queue: Option<LogonQueueConfig>,
+
     #[default = "log::default_logger()"]
     /// Root logger instance, used for handling runtime information throughout this
     /// library.
     logger: slog::Logger,
 }
 
+#[derive(Debug, Clone)]
+/// Selects the session(s) a command applies to.
+pub enum SessionTarget {
+    /// The session of the client with the provided process ID.
+    Client(ProcessId),
+    /// The session which is logged on with the provided account.
+    Account(EntityId),
+}
+
 #[derive(Debug)]
+enum ServerCommand {
+    Shutdown {
+        grace: Duration,
+    },
+    ShutdownNow,
+    Kick {
+        target: SessionTarget,
+        reason: Option<String>,
+        found: oneshot::Sender<bool>,
+    },
+    Broadcast(Notification),
+    Snapshot(oneshot::Sender<Vec<SessionInfo>>),
+}
+
+#[derive(Debug, Clone)]
 /// Object for sending commands to a running server.
+///
+/// Commands are executed by the server task, in the order they were sent.
+/// Dropping all handles doesn't stop the server.
 pub struct ServerHandle {
-    _inner: (),
+    commands: mpsc::UnboundedSender<ServerCommand>,
+    connections: ConnectionTracker,
+    bound_addresses: Vec<SocketAddr>,
+}
+
+impl ServerHandle {
+    /// Retrieve the addresses the server is actually listening on.
+    ///
+    /// These can differ from the configured addresses after binding on the next port,
+    /// or when binding on port 0.
+    pub fn bound_addresses(&self) -> &[SocketAddr] {
+        &self.bound_addresses
+    }
+
+    /// Retrieve the limits for open connections.
+    pub fn limits(&self) -> ConnectionLimits {
+        self.connections.limits()
+    }
+
+    /// Returns the amount of open connections, including connections which are
+    /// handshaking.
+    pub fn open_connections(&self) -> usize {
+        self.connections.open_connections()
+    }
+
+    /// Stop accepting new clients and stop the server once all sessions have finished.
+    ///
+    /// Sessions which are still running after the grace period are disconnected.
+    pub fn shutdown(&self, grace: Duration) -> Result<(), ControlError> {
+        self.send_command(ServerCommand::Shutdown { grace })
+    }
+
+    /// Stop accepting new clients and disconnect all sessions right away.
+    pub fn shutdown_now(&self) -> Result<(), ControlError> {
+        self.send_command(ServerCommand::ShutdownNow)
+    }
+
+    /// Disconnect the targeted session.
+    ///
+    /// The returned future resolves into true if a matching session was found.
+    pub fn kick(
+        &self,
+        target: SessionTarget,
+        reason: Option<String>,
+    ) -> impl Future<Item = bool, Error = ControlError> {
+        let (found, receiver) = oneshot::channel();
+        let send_result = self.send_command(ServerCommand::Kick {
+            target,
+            reason,
+            found,
+        });
+        send_result
+            .into_future()
+            .and_then(|_| receiver.map_err(|_| ControlError::Stopped))
+    }
+
+    /// Deliver the provided notification to all connected clients.
+    pub fn broadcast(&self, notification: Notification) -> Result<(), ControlError> {
+        self.send_command(ServerCommand::Broadcast(notification))
+    }
+
+    /// Retrieve information about all connected clients.
+    pub fn snapshot(&self) -> impl Future<Item = Vec<SessionInfo>, Error = ControlError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_command(ServerCommand::Snapshot(sender))
+            .into_future()
+            .and_then(|_| receiver.map_err(|_| ControlError::Stopped))
+    }
+
+    fn send_command(&self, command: ServerCommand) -> Result<(), ControlError> {
+        self.commands
+            .unbounded_send(command)
+            // An error is only returned when the server task has stopped.
+            .map_err(|_| ControlError::Stopped)
+    }
 }
 
 #[derive(Debug)]
@@ -78,8 +286,9 @@ pub struct ServerHandle {
 ///
 /// An instance of this object must be scheduled on an asynchronous runtime. TODO!
 pub struct LobbyServer {
-    listener: TcpListener,
+    listeners: Vec<TcpListener>,
     config: ServerConfig,
+    shared: Arc<Mutex<ServerShared>>,
 }
 
 impl LobbyServer {
@@ -88,9 +297,8 @@ impl LobbyServer {
     /// This method sets up a Tokio runtime and executes the server task.
     /// This method only returns AFTER all tasks have been completed and/or dropped.
     pub fn run(self) {
-        // Do NOT ignore the handle, because it could contain communication channels.
-        // Channel receivers/senders return an error when the other side is dropped which
-        // *could* prematurely finish the future task.
+        // The handle is kept alive for the duration of the task, although dropping it
+        // doesn't stop the server.
         let (_handle, task) = self.split();
         runtime::run(task);
     }
@@ -101,35 +309,89 @@ impl LobbyServer {
     /// must be scheduled on your Tokio runtime.
     /// The handle can be used to interact with the task (=server) while it's running.
     pub fn split(self) -> (ServerHandle, impl Future<Item = (), Error = ()>) {
-        let LobbyServer { listener, config } = self;
-        let ServerConfig { logger, .. } = config;
-
-        let handle = ServerHandle { _inner: () };
-        let shared = Arc::new(Mutex::new(ServerShared {}));
-        let err_logger = logger.clone();
-
-        let task = listener
-            .incoming()
-            .for_each(move |client| {
-                let task_build_result =
-                    bnet::handshake::handle_client(client, shared.clone(), logger.clone());
-
-                match task_build_result {
-                    Ok(task) => executor::spawn(task),
-                    Err(e) => info!(logger, "Handshake task creation failed"; "error" => ?e),
-                };
+        let bound_addresses = self.bound_addresses();
+        let LobbyServer {
+            listeners,
+            config,
+            shared,
+        } = self;
+        let ServerConfig {
+            max_connections,
+            max_connections_per_ip,
+            accept_error_timeout,
+            session,
+            logger,
+            ..
+        } = config;
 
-                Ok(())
-            })
-            .map_err(move |e| error!(err_logger, "Server loop ended with error!"; "error" => ?e));
+        let (command_sender, commands) = mpsc::unbounded();
+        let limits = ConnectionLimits::new(max_connections, max_connections_per_ip);
+        let connections = ConnectionTracker::new(limits);
+        let handle = ServerHandle {
+            commands: command_sender,
+            connections: connections.clone(),
+            bound_addresses,
+        };
+        let sessions = {
+            let shared = shared.lock().unwrap();
+            info!(logger, "Server started";
+                "server_id" => ?shared.server_id(),
+                "addresses" => ?handle.bound_addresses(),
+            );
+            shared.sessions().clone()
+        };
 
+        let task = ServerTask {
+            incoming: listeners.into_iter().map(TcpListener::incoming).collect(),
+            accept_pause: None,
+            accept_error_timeout,
+            connections,
+            commands: Some(commands),
+            shared,
+            sessions,
+            session_config: session,
+            drain: None,
+            logger,
+        };
         (handle, task)
     }
 
     /// Constructs a new handle from the provided configuration.
+    ///
+    /// The server binds on all configured addresses, an error is returned if binding on
+    /// any of them fails.
     pub fn with(config: ServerConfig) -> Result<Self, BindError> {
-        let listener = Self::try_tcp_bind(&config.bind_address, &config.bind_fallback)?;
-        Ok(Self { listener, config })
+        let listeners = Some(&config.bind_address)
+            .into_iter()
+            .chain(config.additional_bind_addresses.iter())
+            .map(|address| Self::try_tcp_bind(address, &config.bind_fallback))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut shared = ServerShared::with_accounts(config.accounts.clone());
+        shared.set_storage(config.storage.clone());
+        shared.set_enabled_services(config.enabled_services.clone());
+        shared.set_auth_modules(config.auth_modules.clone());
+        shared.set_token_signer(config.tokens.clone());
+        shared.set_logon_queue(config.logon_queue.map(LogonQueue::new));
+        shared.set_util_handlers(config.util_handlers.clone());
+        let shared = Arc::new(Mutex::new(shared));
+        Ok(Self {
+            listeners,
+            config,
+            shared,
+        })
+    }
+
+    /// Retrieve the addresses the server is actually listening on.
+    pub fn bound_addresses(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| listener.local_addr().ok())
+            .collect()
+    }
+
+    /// Retrieve the process ID which identifies this server towards its clients.
+    pub fn server_id(&self) -> ProcessId {
+        self.shared.lock().unwrap().server_id().clone()
     }
 
     /// Attempt binding to the provided address.
@@ -152,11 +414,13 @@ impl LobbyServer {
                     break;
                 }
                 (Err(e), 0) => Err(BindError::Io(e))?,
-                (Err(_), _) => {
+                (Err(_), _) if config.try_next_port => {
                     let current_port = try_address.port();
                     let next_port = current_port.checked_add(1).ok_or(BindError::PortOverflow)?;
                     try_address.set_port(next_port);
                 }
+                // The port could be released in the meantime.
+                (Err(_), _) => thread::sleep(config.retry_delay),
             }
         }
 
@@ -167,9 +431,357 @@ impl LobbyServer {
     }
 }
 
+// Timers running while waiting for all sessions to finish.
+#[derive(Debug)]
+struct Drain {
+    deadline: Delay,
+    check: Interval,
+}
+
+#[derive(Debug)]
+/// Future which accepts new clients and executes the commands sent through all
+/// [`ServerHandle`]s.
+struct ServerTask {
+    // Emptied when the server stops accepting new clients.
+    incoming: Vec<Incoming>,
+    // Set while accepting is paused after an error.
+    accept_pause: Option<Delay>,
+    accept_error_timeout: Duration,
+    connections: ConnectionTracker,
+    // Set to None when all handles are dropped.
+    commands: Option<mpsc::UnboundedReceiver<ServerCommand>>,
+    shared: Arc<Mutex<ServerShared>>,
+    sessions: SessionRegistry,
+    session_config: SessionConfig,
+    // Set during a graceful shutdown.
+    drain: Option<Drain>,
+    logger: slog::Logger,
+}
+
+impl ServerTask {
+    fn execute(&mut self, command: ServerCommand) {
+        trace!(self.logger, "Executing command"; "command" => ?command);
+        match command {
+            ServerCommand::Shutdown { grace } => {
+                info!(self.logger, "Graceful shutdown started"; "grace" => ?grace);
+                self.stop_accepting();
+                let now = Instant::now();
+                self.drain = Some(Drain {
+                    deadline: Delay::new(now + grace),
+                    check: Interval::new(now, DRAIN_CHECK_INTERVAL),
+                });
+            }
+            ServerCommand::ShutdownNow => {
+                info!(self.logger, "Immediate shutdown started");
+                self.stop_accepting();
+                self.disconnect_all();
+            }
+            ServerCommand::Kick {
+                target,
+                reason,
+                found,
+            } => {
+                let handle_opt = match target {
+                    SessionTarget::Client(ref client_id) => self.sessions.by_client_id(client_id),
+                    SessionTarget::Account(ref account) => self.sessions.by_account(account),
+                };
+                let _ = found.send(handle_opt.is_some());
+                if let Some(handle) = handle_opt {
+                    info!(self.logger, "Kicking client"; "client_id" => handle.client_id().label);
+                    Self::disconnect(&handle, StatusCode::RpcDisconnect, reason);
+                }
+            }
+            ServerCommand::Broadcast(notification) => {
+                for handle in self.sessions.handles() {
+                    let logger = handle.logger().clone();
+                    let task = NotificationListenerStub::new(handle.client())
+                        .on_notification_received(&notification)
+                        .map(|_| ())
+                        .map_err(move |error| {
+                            warn!(logger, "Failed to deliver broadcast"; "error" => %error)
+                        });
+                    executor::spawn(task);
+                }
+            }
+            ServerCommand::Snapshot(sender) => {
+                let _ = sender.send(self.sessions.snapshot());
+            }
+        }
+    }
+
+    fn stop_accepting(&mut self) {
+        // Dropping the listeners closes the sockets.
+        self.incoming.clear();
+        self.sessions.close();
+    }
+
+    fn disconnect_all(&mut self) {
+        for handle in self.sessions.handles() {
+            Self::disconnect(&handle, StatusCode::RpcShutdown, None);
+        }
+        self.drain = None;
+    }
+
+    fn disconnect(handle: &SessionHandle, status: StatusCode, reason: Option<String>) {
+        let logger = handle.logger().clone();
+        let task = handle
+            .force_disconnect(status, reason)
+            .map_err(move |error| warn!(logger, "Failed to disconnect"; "error" => %error));
+        executor::spawn(task);
+    }
+
+    fn accept(&mut self, client: ::tokio_tcp::TcpStream) {
+        let peer_addr = match client.peer_addr() {
+            Ok(address) => address,
+            Err(e) => {
+                info!(self.logger, "Handshake task creation failed"; "error" => ?e);
+                return;
+            }
+        };
+        let guard = match self.connections.acquire(peer_addr.ip()) {
+            Ok(guard) => guard,
+            Err(e) => {
+                // Dropping the client closes the connection.
+                info!(self.logger, "Connection refused"; "peer" => %peer_addr, "reason" => %e);
+                return;
+            }
+        };
+
+        let task_build_result = bnet::handshake::handle_client(
+            client,
+            self.shared.clone(),
+            self.session_config.clone(),
+            self.logger.clone(),
+        );
+
+        match task_build_result {
+            // The connection slot is released when the task completes.
+            Ok(task) => executor::spawn(task.then(move |result| {
+                drop(guard);
+                result
+            })),
+            Err(e) => info!(self.logger, "Handshake task creation failed"; "error" => ?e),
+        };
+    }
+
+    // Returns true when accepting is allowed.
+    fn poll_accept_pause(&mut self) -> Result<bool, ()> {
+        let resumed = match self.accept_pause {
+            Some(ref mut pause) => pause.poll().map_err(|_| ())?.is_ready(),
+            None => return Ok(true),
+        };
+        if resumed {
+            self.accept_pause = None;
+        }
+        Ok(resumed)
+    }
+
+    // Returns true when the server has finished draining.
+    fn poll_drain(&mut self) -> Result<bool, ()> {
+        let expired = match self.drain {
+            Some(ref mut drain) => {
+                let expired = drain.deadline.poll().map_err(|_| ())?.is_ready();
+                // Registers interest for the next check.
+                while let Async::Ready(Some(_)) = drain.check.poll().map_err(|_| ())? {}
+                expired
+            }
+            None => return Ok(false),
+        };
+
+        if self.sessions.is_empty() {
+            Ok(true)
+        } else if expired {
+            warn!(self.logger, "Grace period expired"; "remaining" => self.sessions.len());
+            self.disconnect_all();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl Future for ServerTask {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        while let Some(poll_result) = self.commands.as_mut().map(Stream::poll) {
+            match poll_result {
+                Ok(Async::Ready(Some(command))) => self.execute(command),
+                // All handles are dropped, the server keeps running.
+                Ok(Async::Ready(None)) | Err(_) => self.commands = None,
+                Ok(Async::NotReady) => break,
+            }
+        }
+
+        if self.poll_drain()? {
+            info!(self.logger, "Server stopped");
+            return Ok(Async::Ready(()));
+        }
+
+        // Each listener is polled until it has no more clients ready.
+        let mut index = 0;
+        while index < self.incoming.len() && self.poll_accept_pause()? {
+            match self.incoming[index].poll() {
+                Ok(Async::Ready(Some(client))) => self.accept(client),
+                Ok(Async::Ready(None)) => {
+                    // The listener won't produce any more clients.
+                    drop(self.incoming.remove(index));
+                }
+                Ok(Async::NotReady) => index += 1,
+                Err(e) => {
+                    // Errors like running out of file descriptors are temporary.
+                    warn!(self.logger, "Failed to accept client, pausing";
+                        "error" => %e,
+                        "pause" => ?self.accept_error_timeout,
+                    );
+                    let resume = Instant::now() + self.accept_error_timeout;
+                    self.accept_pause = Some(Delay::new(resume));
+                }
+            }
+        }
+
+        // The server has stopped when there's nothing left to do.
+        if self.incoming.is_empty() && self.drain.is_none() {
+            info!(self.logger, "Server stopped");
+            return Ok(Async::Ready(()));
+        }
+        Ok(Async::NotReady)
+    }
+}
+
 #[derive(Debug)]
 /// Structure containing data accessible to each client handler.
-pub struct ServerShared {}
+pub struct ServerShared {
+    process_ids: ProcessIdAllocator,
+    sessions: SessionRegistry,
+    accounts: SharedAccountStore,
+    auth_modules: Vec<BoxedAuthModule>,
+    tokens: TokenSigner,
+    logon_queue: Option<LogonQueue>,
+    storage: SharedStorage,
+    util_handlers: UtilHandlerMap,
+    enabled_services: Option<HashSet<u32>>,
+}
+
+impl ServerShared {
+    /// Creates a new structure without accounts, the server identifier is generated
+    /// at this moment.
+    pub fn new() -> Self {
+        Self::with_accounts(Arc::new(AccountList::new()))
+    }
+
+    /// Creates a new structure which authenticates clients against the provided
+    /// accounts.
+    pub fn with_accounts(accounts: SharedAccountStore) -> Self {
+        Self {
+            process_ids: ProcessIdAllocator::new(),
+            sessions: SessionRegistry::new(),
+            accounts,
+            auth_modules: Vec::new(),
+            tokens: TokenSigner::random(DEFAULT_TOKEN_LIFETIME),
+            logon_queue: None,
+            storage: Arc::new(MemoryStorage::new()),
+            util_handlers: util_handlers::login_handlers(),
+            enabled_services: None,
+        }
+    }
+
+    /// Retrieve the storage holding the state of players.
+    pub fn storage(&self) -> &SharedStorage {
+        &self.storage
+    }
+
+    /// Replace the storage holding the state of players.
+    pub fn set_storage(&mut self, storage: SharedStorage) {
+        self.storage = storage;
+    }
+
+    /// Returns true if the service with the provided hash is provided to clients.
+    pub fn service_enabled(&self, service_hash: u32) -> bool {
+        match self.enabled_services {
+            Some(ref services) => services.contains(&service_hash),
+            None => true,
+        }
+    }
+
+    /// Replace the services which are provided to clients, `None` enables all services.
+    pub fn set_enabled_services(&mut self, services: Option<HashSet<u32>>) {
+        self.enabled_services = services;
+    }
+
+    /// Retrieve the handlers of Pegasus util packets, by packet ID.
+    pub fn util_handlers(&self) -> &UtilHandlerMap {
+        &self.util_handlers
+    }
+
+    /// Replace the handlers of Pegasus util packets.
+    pub fn set_util_handlers(&mut self, handlers: UtilHandlerMap) {
+        self.util_handlers = handlers;
+    }
+
+    /// Retrieve the signer of single sign-on tokens.
+    pub fn token_signer(&self) -> &TokenSigner {
+        &self.tokens
+    }
+
+    /// Replace the signer of single sign-on tokens.
+    pub fn set_token_signer(&mut self, tokens: TokenSigner) {
+        self.tokens = tokens;
+    }
+
+    /// Retrieve the queue which each logon passes, if any.
+    pub fn logon_queue(&self) -> Option<&LogonQueue> {
+        self.logon_queue.as_ref()
+    }
+
+    /// Replace the queue which each logon passes.
+    ///
+    /// Clients which are already queued keep their place within the previous queue.
+    pub fn set_logon_queue(&mut self, queue: Option<LogonQueue>) {
+        self.logon_queue = queue;
+    }
+
+    /// Retrieve the modules which each client must pass during logon.
+    ///
+    /// Each session must work on its own clone of these modules.
+    pub fn auth_modules(&self) -> &[BoxedAuthModule] {
+        &self.auth_modules
+    }
+
+    /// Replace the modules which each client must pass during logon.
+    pub fn set_auth_modules(&mut self, modules: Vec<BoxedAuthModule>) {
+        self.auth_modules = modules;
+    }
+
+    /// Retrieve the accounts which are allowed to log on.
+    pub fn accounts(&self) -> &SharedAccountStore {
+        &self.accounts
+    }
+
+    /// Retrieve the registry of all live sessions.
+    ///
+    /// The registry can be cloned and used without holding on to this structure.
+    pub fn sessions(&self) -> &SessionRegistry {
+        &self.sessions
+    }
+
+    /// Retrieve the process ID which identifies the server towards its clients.
+    pub fn server_id(&self) -> &ProcessId {
+        self.process_ids.server_id()
+    }
+
+    /// Allocate a unique process ID for a newly connected client.
+    pub fn allocate_client_id(&mut self) -> ProcessId {
+        self.process_ids.allocate()
+    }
+}
+
+impl Default for ServerShared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 mod error {
     use std::io;
@@ -189,4 +801,69 @@ mod error {
         /// Failure to bind due to some input/output related error.
         Io(#[cause] io::Error),
     }
+
+    #[derive(Debug, Fail)]
+    /// Error type related to controlling a running server.
+    pub enum ControlError {
+        #[fail(display = "The server task has stopped")]
+        /// Failure to deliver a command because the server is not running anymore.
+        Stopped,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn stops_on_shutdown() {
+        let config = ServerConfig::builder()
+            .bind_address(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .bind_fallback(BindRetryConfig::default())
+            .build();
+        let (handle, task) = LobbyServer::with(config).unwrap().split();
+
+        let snapshot = handle.snapshot();
+        handle.shutdown(Duration::from_secs(5)).unwrap();
+        Runtime::new().unwrap().block_on(task).unwrap();
+
+        assert!(snapshot.wait().unwrap().is_empty());
+        match handle.shutdown_now() {
+            Err(ControlError::Stopped) => {}
+            _ => panic!("Expected the server to be stopped"),
+        }
+    }
+
+    #[test]
+    fn binds_multiple_addresses() {
+        let localhost = SocketAddr::from(([127, 0, 0, 1], 0));
+        let config = ServerConfig::builder()
+            .bind_address(localhost)
+            .additional_bind_addresses(vec![localhost])
+            .bind_fallback(BindRetryConfig::default())
+            .build();
+        let (handle, _) = LobbyServer::with(config).unwrap().split();
+
+        let addresses = handle.bound_addresses();
+        assert_eq!(2, addresses.len());
+        assert!(addresses.iter().all(|address| address.port() != 0));
+        assert_ne!(addresses[0], addresses[1]);
+    }
+
+    #[test]
+    fn retries_same_port() {
+        let occupied = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = occupied.local_addr().unwrap();
+        let retry_config = BindRetryConfig::builder()
+            .max_retries(2)
+            .try_next_port(false)
+            .retry_delay(Duration::from_millis(1))
+            .build();
+
+        match LobbyServer::try_tcp_bind(&address, &retry_config) {
+            Err(BindError::ExhaustedRetries(2, port)) => assert_eq!(address.port(), port),
+            _ => panic!("Expected the retries to be exhausted"),
+        }
+    }
 }
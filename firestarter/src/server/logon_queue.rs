@@ -0,0 +1,180 @@
+//! Admission control for logons, simulating the queue of a crowded server.
+//!
+//! The queue admits a fixed amount of logons each second. Each logon receives a
+//! [`QueueTicket`] which tells when the logon is admitted, clients are notified about
+//! their position in the meantime.
+//!
+//! Admission slots are handed out in order at the moment of queueing, so a client
+//! which disconnects while queued doesn't advance the clients behind it.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default interval between two position updates towards a queued client.
+pub const DEFAULT_QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, TypedBuilder)]
+/// Object for configuring a [`LogonQueue`].
+pub struct LogonQueueConfig {
+    /// Amount of logons admitted each second, must be larger than zero.
+    admissions_per_second: u32,
+
+    #[default = "DEFAULT_QUEUE_UPDATE_INTERVAL"]
+    /// Interval between two position updates towards a queued client.
+    update_interval: Duration,
+}
+
+impl LogonQueueConfig {
+    /// Retrieve the amount of logons admitted each second.
+    pub fn admissions_per_second(&self) -> u32 {
+        self.admissions_per_second
+    }
+
+    /// Retrieve the interval between two position updates.
+    pub fn update_interval(&self) -> Duration {
+        self.update_interval
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Queue which is shared between all sessions.
+///
+/// See the module documentation for more information.
+pub struct LogonQueue {
+    config: LogonQueueConfig,
+    // The moment the next ticket is admitted at the earliest.
+    next_slot: Arc<Mutex<Option<Instant>>>,
+}
+
+impl LogonQueue {
+    /// Creates a new and empty queue.
+    ///
+    /// # Panics
+    /// Panics when the configuration admits no logons.
+    pub fn new(config: LogonQueueConfig) -> Self {
+        assert!(
+            config.admissions_per_second > 0,
+            "The logon queue must admit logons"
+        );
+        Self {
+            config,
+            next_slot: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Retrieve the configuration of this queue.
+    pub fn config(&self) -> &LogonQueueConfig {
+        &self.config
+    }
+
+    /// Add a logon to the back of the queue.
+    pub fn enqueue(&self) -> QueueTicket {
+        self.enqueue_at(Instant::now())
+    }
+
+    /// Add a logon to the back of the queue, as if the current time is `now`.
+    pub fn enqueue_at(&self, now: Instant) -> QueueTicket {
+        let slot_duration = self.slot_duration();
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let admission = match *next_slot {
+            Some(slot) if slot > now => slot,
+            _ => now,
+        };
+        *next_slot = Some(admission + slot_duration);
+
+        QueueTicket {
+            admission,
+            slot_duration,
+        }
+    }
+
+    fn slot_duration(&self) -> Duration {
+        Duration::from_secs(1) / self.config.admissions_per_second
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Place of one logon within the queue.
+pub struct QueueTicket {
+    admission: Instant,
+    slot_duration: Duration,
+}
+
+impl QueueTicket {
+    /// Retrieve the moment the logon is admitted.
+    pub fn admission(&self) -> Instant {
+        self.admission
+    }
+
+    /// Returns true if the logon is admitted at the provided moment.
+    pub fn is_admitted(&self, now: Instant) -> bool {
+        now >= self.admission
+    }
+
+    /// Retrieve the remaining time until admission.
+    pub fn remaining(&self, now: Instant) -> Duration {
+        if self.is_admitted(now) {
+            Duration::from_secs(0)
+        } else {
+            self.admission - now
+        }
+    }
+
+    /// Retrieve the position within the queue, the first waiting logon is at position 1.
+    ///
+    /// Zero is returned when the logon is admitted.
+    pub fn position(&self, now: Instant) -> u32 {
+        if self.is_admitted(now) {
+            return 0;
+        }
+        let remaining = nanos(self.remaining(now));
+        let slot = nanos(self.slot_duration).max(1);
+        // Each waiting logon in front of this one occupies a slot, a partially elapsed
+        // slot still counts.
+        let position = remaining / slot;
+        if position * slot < remaining {
+            position as u32 + 1
+        } else {
+            position as u32
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_mul(1_000_000_000)
+        .saturating_add(u64::from(duration.subsec_nanos()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn admits_at_configured_rate() {
+        let config = LogonQueueConfig::builder()
+            .admissions_per_second(4u32)
+            .build();
+        let queue = LogonQueue::new(config);
+        let now = Instant::now();
+
+        let first = queue.enqueue_at(now);
+        assert!(first.is_admitted(now));
+        assert_eq!(0, first.position(now));
+
+        let second = queue.enqueue_at(now);
+        let third = queue.enqueue_at(now);
+        assert_eq!(1, second.position(now));
+        assert_eq!(2, third.position(now));
+        assert_eq!(Duration::from_millis(500), third.remaining(now));
+
+        let later = now + Duration::from_millis(250);
+        assert!(second.is_admitted(later));
+        assert_eq!(1, third.position(later));
+
+        // The queue is empty again after all slots passed.
+        let much_later = now + Duration::from_secs(10);
+        assert!(queue.enqueue_at(much_later).is_admitted(much_later));
+    }
+}
@@ -2,4 +2,10 @@
 //!
 //! The one you'll probably need is [`LobbyServer`].
 
+pub mod accounts;
+pub mod connections;
 pub mod lobby;
+pub mod logon_queue;
+pub mod registry;
+pub mod storage;
+pub mod tokens;
@@ -0,0 +1,359 @@
+//! Registry of all live client sessions of a server.
+//!
+//! Each session is registered after completing its handshake and removed when it stops.
+//! Sessions can be looked up by peer address and client [`ProcessId`], and after logon
+//! by the [`EntityId`] of the account or game account.
+//! The returned [`SessionHandle`]s allow any service to push notifications towards
+//! the session of another player.
+
+use firestarter_generated::proto::bnet::protocol::{EntityId, ProcessId};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+use protocol::bnet::session::SessionHandle;
+
+pub use self::error::*;
+
+// ProcessId and EntityId don't implement Hash, so the components are used as keys.
+type ProcessKey = (u32, u32);
+type EntityKey = (u64, u64);
+
+fn process_key(id: &ProcessId) -> ProcessKey {
+    (id.label, id.epoch)
+}
+
+fn entity_key(id: &EntityId) -> EntityKey {
+    (id.high, id.low)
+}
+
+fn entity_id(key: EntityKey) -> EntityId {
+    EntityId {
+        high: key.0,
+        low: key.1,
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    handle: SessionHandle,
+    account: Option<EntityKey>,
+    game_account: Option<EntityKey>,
+}
+
+#[derive(Debug, Clone)]
+/// Information about a registered session at a specific moment.
+pub struct SessionInfo {
+    client_id: ProcessId,
+    address: SocketAddr,
+    account: Option<EntityId>,
+    game_account: Option<EntityId>,
+}
+
+impl SessionInfo {
+    /// Retrieve the process ID which was allocated for the client.
+    pub fn client_id(&self) -> &ProcessId {
+        &self.client_id
+    }
+
+    /// Retrieve the address endpoint of the client.
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Retrieve the account the client logged on with, if any.
+    pub fn account(&self) -> Option<&EntityId> {
+        self.account.as_ref()
+    }
+
+    /// Retrieve the game account the client logged on with, if any.
+    pub fn game_account(&self) -> Option<&EntityId> {
+        self.game_account.as_ref()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    // Set when the server shuts down, no new sessions are accepted afterwards.
+    closed: bool,
+    sessions: HashMap<ProcessKey, Entry>,
+    by_address: HashMap<SocketAddr, ProcessKey>,
+    by_account: HashMap<EntityKey, ProcessKey>,
+    by_game_account: HashMap<EntityKey, ProcessKey>,
+}
+
+impl Registry {
+    fn lookup(&self, key: Option<&ProcessKey>) -> Option<SessionHandle> {
+        key.and_then(|key| self.sessions.get(key))
+            .map(|entry| entry.handle.clone())
+    }
+
+    fn remove(&mut self, key: &ProcessKey) -> Option<SessionHandle> {
+        let entry = self.sessions.remove(key)?;
+        // Indices could already point towards a newer session.
+        let address = *entry.handle.address();
+        if self.by_address.get(&address) == Some(key) {
+            self.by_address.remove(&address);
+        }
+        if let Some(account) = entry.account {
+            if self.by_account.get(&account) == Some(key) {
+                self.by_account.remove(&account);
+            }
+        }
+        if let Some(game_account) = entry.game_account {
+            if self.by_game_account.get(&game_account) == Some(key) {
+                self.by_game_account.remove(&game_account);
+            }
+        }
+        Some(entry.handle)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Concurrent registry of live sessions.
+///
+/// The registry is cheap to clone, all clones operate on the same data.
+pub struct SessionRegistry {
+    inner: Arc<RwLock<Registry>>,
+}
+
+impl SessionRegistry {
+    /// Creates a new and empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the amount of registered sessions.
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().sessions.len()
+    }
+
+    /// Returns true if no sessions are registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Register the session behind the provided handle.
+    ///
+    /// A session which was registered with the same client ID is replaced.
+    /// Registration fails after the registry is closed.
+    pub fn register(&self, handle: SessionHandle) -> Result<(), RegistryError> {
+        let key = process_key(handle.client_id());
+        let mut registry = self.inner.write().unwrap();
+        if registry.closed {
+            Err(RegistryError::Closed)?;
+        }
+        registry.remove(&key);
+        registry.by_address.insert(*handle.address(), key);
+        registry.sessions.insert(
+            key,
+            Entry {
+                handle,
+                account: None,
+                game_account: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Refuse all future registrations.
+    ///
+    /// Already registered sessions are unaffected.
+    pub fn close(&self) {
+        self.inner.write().unwrap().closed = true;
+    }
+
+    /// Returns true if the registry refuses new registrations.
+    pub fn is_closed(&self) -> bool {
+        self.inner.read().unwrap().closed
+    }
+
+    /// Remove the session of the client with the provided ID.
+    ///
+    /// The handle of the removed session is returned, if any.
+    pub fn unregister(&self, client_id: &ProcessId) -> Option<SessionHandle> {
+        self.inner.write().unwrap().remove(&process_key(client_id))
+    }
+
+    /// Link the account with the session of the provided client.
+    ///
+    /// The session which was previously linked with the same account is returned,
+    /// if any.
+    pub fn set_account(
+        &self,
+        client_id: &ProcessId,
+        account: &EntityId,
+    ) -> Result<Option<SessionHandle>, RegistryError> {
+        let key = process_key(client_id);
+        let account = entity_key(account);
+        let mut registry = self.inner.write().unwrap();
+        {
+            let entry = registry
+                .sessions
+                .get_mut(&key)
+                .ok_or(RegistryError::UnknownSession {
+                    label: client_id.label,
+                })?;
+            entry.account = Some(account);
+        }
+        let previous = registry.by_account.insert(account, key);
+        Ok(registry.lookup(previous.filter(|previous| *previous != key).as_ref()))
+    }
+
+    /// Link the game account with the session of the provided client.
+    ///
+    /// The session which was previously linked with the same game account is returned,
+    /// if any.
+    pub fn set_game_account(
+        &self,
+        client_id: &ProcessId,
+        game_account: &EntityId,
+    ) -> Result<Option<SessionHandle>, RegistryError> {
+        let key = process_key(client_id);
+        let game_account = entity_key(game_account);
+        let mut registry = self.inner.write().unwrap();
+        {
+            let entry = registry
+                .sessions
+                .get_mut(&key)
+                .ok_or(RegistryError::UnknownSession {
+                    label: client_id.label,
+                })?;
+            entry.game_account = Some(game_account);
+        }
+        let previous = registry.by_game_account.insert(game_account, key);
+        Ok(registry.lookup(previous.filter(|previous| *previous != key).as_ref()))
+    }
+
+    /// Find the session of the client with the provided ID.
+    pub fn by_client_id(&self, client_id: &ProcessId) -> Option<SessionHandle> {
+        let registry = self.inner.read().unwrap();
+        registry.lookup(Some(&process_key(client_id)))
+    }
+
+    /// Find the session connected from the provided address.
+    pub fn by_address(&self, address: &SocketAddr) -> Option<SessionHandle> {
+        let registry = self.inner.read().unwrap();
+        registry.lookup(registry.by_address.get(address))
+    }
+
+    /// Find the session which is logged on with the provided account.
+    pub fn by_account(&self, account: &EntityId) -> Option<SessionHandle> {
+        let registry = self.inner.read().unwrap();
+        registry.lookup(registry.by_account.get(&entity_key(account)))
+    }
+
+    /// Find the session which is logged on with the provided game account.
+    pub fn by_game_account(&self, game_account: &EntityId) -> Option<SessionHandle> {
+        let registry = self.inner.read().unwrap();
+        registry.lookup(registry.by_game_account.get(&entity_key(game_account)))
+    }
+
+    /// Retrieve the handles of all registered sessions.
+    ///
+    /// This is a snapshot, sessions could stop or be registered right after.
+    pub fn handles(&self) -> Vec<SessionHandle> {
+        let registry = self.inner.read().unwrap();
+        registry
+            .sessions
+            .values()
+            .map(|entry| entry.handle.clone())
+            .collect()
+    }
+
+    /// Retrieve information about all registered sessions.
+    pub fn snapshot(&self) -> Vec<SessionInfo> {
+        let registry = self.inner.read().unwrap();
+        registry
+            .sessions
+            .values()
+            .map(|entry| SessionInfo {
+                client_id: entry.handle.client_id().clone(),
+                address: *entry.handle.address(),
+                account: entry.account.map(entity_id),
+                game_account: entry.game_account.map(entity_id),
+            })
+            .collect()
+    }
+}
+
+mod error {
+    #[derive(Debug, Fail)]
+    /// Error type related to manipulating the session registry.
+    pub enum RegistryError {
+        #[fail(display = "No session is registered for client {}", label)]
+        /// Failure to find the session of the provided client.
+        UnknownSession {
+            /// The label of the client process ID.
+            label: u32,
+        },
+
+        #[fail(display = "The registry doesn't accept new sessions")]
+        /// Failure to register a session after the registry was closed.
+        Closed,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn handle(label: u32, port: u16) -> SessionHandle {
+        let address = SocketAddr::from(([127, 0, 0, 1], port));
+        SessionHandle::detached(address, ProcessId { label, epoch: 1 })
+    }
+
+    #[test]
+    fn indexes_sessions() {
+        let registry = SessionRegistry::new();
+        let first = handle(1, 1000);
+        let account = EntityId { high: 1, low: 2 };
+        registry.register(first.clone()).unwrap();
+        registry.register(handle(2, 1001)).unwrap();
+        registry.set_account(first.client_id(), &account).unwrap();
+
+        let found = registry.by_account(&account).unwrap();
+        assert_eq!(1, found.client_id().label);
+        let found = registry.by_address(&SocketAddr::from(([127, 0, 0, 1], 1001)));
+        assert_eq!(2, found.unwrap().client_id().label);
+        assert_eq!(2, registry.handles().len());
+        let snapshot = registry.snapshot();
+        let info = snapshot.iter().find(|info| info.client_id().label == 1);
+        assert_eq!(Some(&account), info.unwrap().account());
+
+        registry.unregister(first.client_id()).unwrap();
+        assert!(registry.by_account(&account).is_none());
+        assert!(registry.by_client_id(first.client_id()).is_none());
+        assert_eq!(1, registry.len());
+    }
+
+    #[test]
+    fn reports_previous_account_session() {
+        let registry = SessionRegistry::new();
+        let account = EntityId { high: 1, low: 2 };
+        let (first, second) = (handle(1, 1000), handle(2, 1001));
+        registry.register(first.clone()).unwrap();
+        registry.register(second.clone()).unwrap();
+
+        let previous = registry.set_account(first.client_id(), &account).unwrap();
+        assert!(previous.is_none());
+        let previous = registry.set_account(second.client_id(), &account).unwrap();
+        assert_eq!(1, previous.unwrap().client_id().label);
+
+        // Removing the older session keeps the newer one indexed.
+        registry.unregister(first.client_id());
+        let found = registry.by_account(&account).unwrap();
+        assert_eq!(2, found.client_id().label);
+
+        let unknown = ProcessId { label: 9, epoch: 1 };
+        assert!(registry.set_account(&unknown, &account).is_err());
+    }
+
+    #[test]
+    fn refuses_registration_after_close() {
+        let registry = SessionRegistry::new();
+        registry.close();
+        assert!(registry.register(handle(1, 1000)).is_err());
+        assert!(registry.is_empty());
+    }
+}
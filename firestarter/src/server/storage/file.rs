@@ -0,0 +1,344 @@
+//! Storage backend which keeps the state of each player within a file.
+//!
+//! The storage occupies one directory with the following layout:
+//! - `storage.meta` holds the schema version of the storage and the next identifier
+//!   to allocate for game accounts.
+//! - `players/<account>.player` holds the state of the player with that account
+//!   identifier, together with the schema version of the file.
+//!
+//! Files are replaced as a whole when the state changes, which happens by writing a
+//! temporary file first and renaming it afterwards. A crash never leaves a partially
+//! written file behind.
+//!
+//! # Migrations
+//! Each change to the layout of the files, which older files can't follow, bumps
+//! [`SCHEMA_VERSION`] and appends a migration. When the storage is opened, all
+//! migrations from the schema version of each player file up to the current one are
+//! applied to that file. Because each file tracks its own schema version, an interrupted
+//! migration is resumed without applying any migration twice.
+//! Storage written by a newer version of the server is refused.
+
+use prost::{DecodeError, Message};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::schema::{Meta, Player};
+use super::{PlayerData, Storage, StorageError, FIRST_CREATED_GAME_ACCOUNT_ID};
+
+/// The schema version of files written by this server.
+///
+/// This is one more than the amount of migrations.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const META_FILE: &str = "storage.meta";
+const PLAYERS_DIRECTORY: &str = "players";
+const PLAYER_EXTENSION: &str = "player";
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+// Upgrades a player file from the previous schema version to the next.
+type Migration = fn(&mut Player);
+
+// Migrations between consecutive schema versions, the first upgrades from version 1.
+const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Debug)]
+/// Storage which keeps the state of each player within a file.
+///
+/// See the module documentation for more information.
+pub struct FileStorage {
+    directory: PathBuf,
+    // Serializes all access to the files.
+    meta: Mutex<Meta>,
+}
+
+impl FileStorage {
+    /// Open the storage within the provided directory.
+    ///
+    /// The directory is created when it doesn't exist yet. Files written by older
+    /// versions of the server are migrated to the current schema version.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Self, StorageError> {
+        Self::open_with(directory.as_ref(), MIGRATIONS)
+    }
+
+    fn open_with(directory: &Path, migrations: &[Migration]) -> Result<Self, StorageError> {
+        // Each migration upgrades to the next schema version.
+        let supported = 1 + migrations.len() as u32;
+        let players = directory.join(PLAYERS_DIRECTORY);
+        fs::create_dir_all(&players).map_err(|error| io_error(&players, error))?;
+
+        let meta_path = directory.join(META_FILE);
+        let mut meta = match read_message::<Meta>(&meta_path)? {
+            Some(meta) => meta,
+            None => {
+                let meta = Meta {
+                    schema_version: supported,
+                    next_game_account_id: FIRST_CREATED_GAME_ACCOUNT_ID,
+                };
+                write_message(&meta_path, &meta)?;
+                meta
+            }
+        };
+
+        if meta.schema_version == 0 {
+            // Empty or truncated files decode into defaults.
+            return Err(StorageError::Corrupt {
+                path: meta_path,
+                error: DecodeError::new("missing schema version"),
+            });
+        }
+        if meta.schema_version > supported {
+            return Err(StorageError::UnsupportedVersion {
+                found: meta.schema_version,
+                supported,
+            });
+        }
+        if meta.schema_version < supported {
+            migrate_players(&players, migrations)?;
+            meta.schema_version = supported;
+            // Only bumped after all players are migrated, so an interrupted migration
+            // is resumed. Files which were already migrated are skipped.
+            write_message(&meta_path, &meta)?;
+        }
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            meta: Mutex::new(meta),
+        })
+    }
+
+    /// Retrieve the directory holding the files of the storage.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn player_path(&self, account: u64) -> PathBuf {
+        self.directory
+            .join(PLAYERS_DIRECTORY)
+            .join(format!("{}.{}", account, PLAYER_EXTENSION))
+    }
+}
+
+impl Storage for FileStorage {
+    fn load_player(&self, account: u64) -> Result<Option<PlayerData>, StorageError> {
+        let _guard = self.meta.lock().unwrap();
+        let player = read_message::<Player>(&self.player_path(account))?;
+        Ok(player.map(PlayerData::from))
+    }
+
+    fn update_player(
+        &self,
+        account: u64,
+        update: &mut dyn FnMut(&mut PlayerData),
+    ) -> Result<(), StorageError> {
+        let meta = self.meta.lock().unwrap();
+        let path = self.player_path(account);
+        let mut data = read_message::<Player>(&path)?
+            .map(PlayerData::from)
+            .unwrap_or_default();
+        update(&mut data);
+        let mut player = Player::from(&data);
+        player.schema_version = meta.schema_version;
+        write_message(&path, &player)
+    }
+
+    fn allocate_game_account_id(&self) -> Result<u64, StorageError> {
+        let mut meta = self.meta.lock().unwrap();
+        if meta.next_game_account_id > u64::from(u32::MAX) {
+            return Err(StorageError::Exhausted);
+        }
+        let id = meta.next_game_account_id;
+        let mut next = meta.clone();
+        next.next_game_account_id += 1;
+        // Persisted before handing out the identifier, so it's never handed out twice.
+        write_message(&self.directory.join(META_FILE), &next)?;
+        *meta = next;
+        Ok(id)
+    }
+}
+
+fn migrate_players(players: &Path, migrations: &[Migration]) -> Result<(), StorageError> {
+    let entries = fs::read_dir(players).map_err(|error| io_error(players, error))?;
+    for entry in entries {
+        let path = entry.map_err(|error| io_error(players, error))?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(PLAYER_EXTENSION) {
+            continue;
+        }
+        if let Some(mut player) = read_message::<Player>(&path)? {
+            let supported = 1 + migrations.len() as u32;
+            let version = player.schema_version.max(1);
+            if version > supported {
+                return Err(StorageError::UnsupportedVersion {
+                    found: version,
+                    supported,
+                });
+            }
+            if version == supported {
+                continue;
+            }
+            for migration in &migrations[version as usize - 1..] {
+                migration(&mut player);
+            }
+            player.schema_version = supported;
+            write_message(&path, &player)?;
+        }
+    }
+    Ok(())
+}
+
+fn io_error(path: &Path, error: io::Error) -> StorageError {
+    StorageError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+/// Read and decode the message within the file at the provided path.
+///
+/// `None` is returned when the file doesn't exist.
+fn read_message<M: Message + Default>(path: &Path) -> Result<Option<M>, StorageError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(io_error(path, error)),
+    };
+    M::decode(bytes)
+        .map(Some)
+        .map_err(|error| StorageError::Corrupt {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+/// Encode the message and replace the file at the provided path with it.
+fn write_message<M: Message>(path: &Path, message: &M) -> Result<(), StorageError> {
+    let mut bytes = Vec::with_capacity(message.encoded_len());
+    message
+        .encode(&mut bytes)
+        .expect("Vectors grow to fit the message");
+    let temporary = path.with_extension(TEMPORARY_EXTENSION);
+    fs::write(&temporary, &bytes).map_err(|error| io_error(&temporary, error))?;
+    fs::rename(&temporary, path).map_err(|error| io_error(path, error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use server::storage::{AccountData, Currencies, Deck};
+    use std::env;
+    use std::ops::Deref;
+    use std::process;
+    use std::slice;
+
+    // Directory which is removed when dropped, so failing tests don't leave it behind.
+    struct TemporaryDirectory(PathBuf);
+
+    impl TemporaryDirectory {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("firestarter-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TemporaryDirectory(path)
+        }
+    }
+
+    impl Deref for TemporaryDirectory {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TemporaryDirectory {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TemporaryDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn stores_players_in_files() {
+        let directory = TemporaryDirectory::new("file-storage");
+        let mut data = AccountData::initial(7, Some("Alice#1234"));
+        data.add_license(12);
+        let deck = Deck::builder()
+            .id(1i64)
+            .name("Mage".to_string())
+            .hero(637)
+            .build();
+
+        let id = {
+            let storage = FileStorage::open(&directory).unwrap();
+            storage.save_account(7, &data).unwrap();
+            storage.save_decks(7, slice::from_ref(&deck)).unwrap();
+            storage
+                .save_currencies(7, &Currencies::new(100, 40))
+                .unwrap();
+            storage.allocate_game_account_id().unwrap()
+        };
+
+        let storage = FileStorage::open(&directory).unwrap();
+        assert_eq!(Some(data), storage.load_account(7).unwrap());
+        assert_eq!(vec![deck], storage.load_decks(7).unwrap());
+        assert_eq!(40, storage.load_currencies(7).unwrap().dust());
+        assert!(storage.load_player(8).unwrap().is_none());
+        assert_eq!(id + 1, storage.allocate_game_account_id().unwrap());
+    }
+
+    #[test]
+    fn migrates_older_schema() {
+        fn grant_gold(player: &mut Player) {
+            player.gold += 50;
+        }
+
+        let directory = TemporaryDirectory::new("file-storage-migration");
+        FileStorage::open_with(&directory, &[])
+            .unwrap()
+            .save_currencies(7, &Currencies::new(100, 0))
+            .unwrap();
+
+        let storage = FileStorage::open_with(&directory, &[grant_gold]).unwrap();
+        assert_eq!(150, storage.load_currencies(7).unwrap().gold());
+        drop(storage);
+        // Migrations are only applied once, even when the storage wasn't marked as
+        // migrated because the migration was interrupted.
+        let meta = Meta {
+            schema_version: 1,
+            next_game_account_id: FIRST_CREATED_GAME_ACCOUNT_ID,
+        };
+        write_message(&directory.join(META_FILE), &meta).unwrap();
+        let storage = FileStorage::open_with(&directory, &[grant_gold]).unwrap();
+        assert_eq!(150, storage.load_currencies(7).unwrap().gold());
+        storage.save_currencies(8, &Currencies::new(10, 0)).unwrap();
+        drop(storage);
+        let storage = FileStorage::open_with(&directory, &[grant_gold]).unwrap();
+        assert_eq!(150, storage.load_currencies(7).unwrap().gold());
+        assert_eq!(10, storage.load_currencies(8).unwrap().gold());
+        drop(storage);
+
+        match FileStorage::open_with(&directory, &[]) {
+            Err(StorageError::UnsupportedVersion {
+                found: 2,
+                supported: 1,
+            }) => {}
+            other => panic!("Expected unsupported version, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refuses_empty_meta() {
+        let directory = TemporaryDirectory::new("file-storage-empty-meta");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(META_FILE), b"").unwrap();
+        match FileStorage::open(&directory) {
+            Err(StorageError::Corrupt { .. }) => {}
+            other => panic!("Expected corrupt storage, got {:?}", other),
+        }
+    }
+}
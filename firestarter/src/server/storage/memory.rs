@@ -0,0 +1,97 @@
+//! Storage backend which keeps all state in memory.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{PlayerData, Storage, StorageError, FIRST_CREATED_GAME_ACCOUNT_ID};
+
+#[derive(Debug)]
+struct MemoryState {
+    players: HashMap<u64, PlayerData>,
+    next_game_account_id: u64,
+}
+
+#[derive(Debug)]
+/// Storage which keeps all state in memory.
+///
+/// All state is lost when the storage is dropped, so this is mostly useful for tests.
+pub struct MemoryStorage {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryStorage {
+    /// Creates a new and empty storage.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MemoryState {
+                players: HashMap::new(),
+                next_game_account_id: FIRST_CREATED_GAME_ACCOUNT_ID,
+            }),
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_player(&self, account: u64) -> Result<Option<PlayerData>, StorageError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.players.get(&account).cloned())
+    }
+
+    fn update_player(
+        &self,
+        account: u64,
+        update: &mut dyn FnMut(&mut PlayerData),
+    ) -> Result<(), StorageError> {
+        let mut state = self.state.lock().unwrap();
+        update(state.players.entry(account).or_default());
+        Ok(())
+    }
+
+    fn allocate_game_account_id(&self) -> Result<u64, StorageError> {
+        let mut state = self.state.lock().unwrap();
+        if state.next_game_account_id > u64::from(u32::MAX) {
+            return Err(StorageError::Exhausted);
+        }
+        let id = state.next_game_account_id;
+        state.next_game_account_id += 1;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use server::accounts::{Account, Credential, HEARTHSTONE_PROGRAM};
+    use server::storage::{AccountData, GameAccountData};
+
+    #[test]
+    fn stores_account_state() {
+        let storage = MemoryStorage::new();
+        assert!(storage.load_account(7).unwrap().is_none());
+
+        let account = Account::new(7, "alice@example.com".into(), Credential::Token(vec![1]));
+        let mut data = AccountData::initial(account.id(), account.battle_tag());
+        assert_eq!(
+            Some(account.game_account_id()),
+            data.game_accounts().first().map(GameAccountData::entity_id)
+        );
+
+        let id = storage.allocate_game_account_id().unwrap();
+        assert_ne!(id, storage.allocate_game_account_id().unwrap());
+        data.add_game_account(GameAccountData::new(
+            id,
+            HEARTHSTONE_PROGRAM,
+            2,
+            "EU".into(),
+        ));
+        data.add_license(12);
+        storage.save_account(7, &data).unwrap();
+        assert_eq!(Some(data), storage.load_account(7).unwrap());
+    }
+}
@@ -0,0 +1,657 @@
+//! Persistent state of players, which outlives their sessions.
+//!
+//! Services read and update the state of players through the [`Storage`] trait, so the
+//! backend can be replaced. Two backends are provided:
+//! - [`MemoryStorage`](self::memory::MemoryStorage) keeps all state in memory, which is
+//!   lost when the server stops. This is mostly useful for tests.
+//! - [`FileStorage`](self::file::FileStorage) keeps the state of each player within a
+//!   file. The layout of these files is versioned, files written by older versions of the
+//!   server are migrated when the storage is opened.
+//!
+//! All state of one player is kept together within [`PlayerData`]. The trait provides
+//! accessors for each part of this state, backends only implement loading and updating
+//! the state as a whole.
+//!
+//! The credentials of accounts are not part of this state, see
+//! [`accounts`](::server::accounts) for those.
+
+use firestarter_generated::proto::bnet::protocol::account::privacy_info::GameInfoPrivacy;
+use firestarter_generated::proto::bnet::protocol::account::GameAccountHandle;
+use firestarter_generated::proto::bnet::protocol::EntityId;
+use std::fmt;
+use std::sync::Arc;
+
+use server::accounts::{DEFAULT_REGION, HEARTHSTONE_PROGRAM};
+
+pub use self::error::*;
+
+pub mod file;
+pub mod memory;
+mod schema;
+
+/// Identifiers of game accounts created by clients are allocated starting from this
+/// value, so they don't collide with the game accounts which come with each account.
+///
+/// Game account handles only hold 32 bits of the identifier, which is why allocated
+/// identifiers remain within that range.
+pub const FIRST_CREATED_GAME_ACCOUNT_ID: u64 = 0x8000_0000;
+
+/// Storage which is shared between all sessions.
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Backend holding the state of players.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// Load all state of the player with the provided account identifier.
+    ///
+    /// `None` is returned when no state was stored for the player yet.
+    fn load_player(&self, account: u64) -> Result<Option<PlayerData>, StorageError>;
+
+    /// Update the state of the player with the provided account identifier.
+    ///
+    /// `update` receives the stored state, or empty state if nothing was stored yet.
+    /// Updates of the same player don't interleave, so concurrent updates of different
+    /// parts of the state are not lost.
+    fn update_player(
+        &self,
+        account: u64,
+        update: &mut dyn FnMut(&mut PlayerData),
+    ) -> Result<(), StorageError>;
+
+    /// Allocate a unique identifier for a new game account.
+    fn allocate_game_account_id(&self) -> Result<u64, StorageError>;
+
+    /// Load the state of the account with the provided identifier.
+    ///
+    /// `None` is returned when no state was stored for the account yet.
+    fn load_account(&self, account: u64) -> Result<Option<AccountData>, StorageError> {
+        Ok(self.load_player(account)?.and_then(|player| player.account))
+    }
+
+    /// Store the state of the account with the provided identifier, replacing the
+    /// previously stored state.
+    fn save_account(&self, account: u64, data: &AccountData) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| player.account = Some(data.clone()))
+    }
+
+    /// Load the cards owned by the player.
+    fn load_collection(&self, account: u64) -> Result<Vec<CardStack>, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().collection)
+    }
+
+    /// Replace the cards owned by the player.
+    fn save_collection(&self, account: u64, collection: &[CardStack]) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| {
+            player.collection = collection.to_vec()
+        })
+    }
+
+    /// Load the decks of the player.
+    fn load_decks(&self, account: u64) -> Result<Vec<Deck>, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().decks)
+    }
+
+    /// Replace the decks of the player.
+    fn save_decks(&self, account: u64, decks: &[Deck]) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| player.decks = decks.to_vec())
+    }
+
+    /// Load the currency balances of the player.
+    fn load_currencies(&self, account: u64) -> Result<Currencies, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().currencies)
+    }
+
+    /// Replace the currency balances of the player.
+    fn save_currencies(&self, account: u64, currencies: &Currencies) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| player.currencies = *currencies)
+    }
+
+    /// Load the account identifiers of the friends of the player.
+    fn load_friends(&self, account: u64) -> Result<Vec<u64>, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().friends)
+    }
+
+    /// Replace the friends of the player.
+    fn save_friends(&self, account: u64, friends: &[u64]) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| player.friends = friends.to_vec())
+    }
+
+    /// Load the account identifiers of the players blocked by the player.
+    fn load_blocked(&self, account: u64) -> Result<Vec<u64>, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().blocked)
+    }
+
+    /// Replace the players blocked by the player.
+    fn save_blocked(&self, account: u64, blocked: &[u64]) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| player.blocked = blocked.to_vec())
+    }
+
+    /// Load the achievement progress of the player.
+    fn load_achievements(&self, account: u64) -> Result<Vec<Achievement>, StorageError> {
+        Ok(self.load_player(account)?.unwrap_or_default().achievements)
+    }
+
+    /// Replace the achievement progress of the player.
+    fn save_achievements(
+        &self,
+        account: u64,
+        achievements: &[Achievement],
+    ) -> Result<(), StorageError> {
+        self.update_player(account, &mut |player| {
+            player.achievements = achievements.to_vec()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TypedBuilder)]
+/// Privacy settings of an account.
+pub struct PrivacySettings {
+    #[default = "false"]
+    /// The real name of the player is shown to friends instead of the battle tag.
+    real_id: bool,
+
+    #[default = "false"]
+    /// The real name of the player is shown to friends of friends.
+    real_id_visible_for_friends: bool,
+
+    #[default = "false"]
+    /// The account can't be found through the friend finder.
+    hidden_from_friend_finder: bool,
+
+    #[default = "GameInfoPrivacy::PrivacyFriends"]
+    /// Players which can see what the player is doing in game.
+    game_info: GameInfoPrivacy,
+}
+
+impl PrivacySettings {
+    /// Returns true if the real name of the player is shown to friends.
+    pub fn real_id(&self) -> bool {
+        self.real_id
+    }
+
+    /// Returns true if the real name of the player is shown to friends of friends.
+    pub fn real_id_visible_for_friends(&self) -> bool {
+        self.real_id_visible_for_friends
+    }
+
+    /// Returns true if the account can't be found through the friend finder.
+    pub fn hidden_from_friend_finder(&self) -> bool {
+        self.hidden_from_friend_finder
+    }
+
+    /// Retrieve which players can see what the player is doing in game.
+    pub fn game_info(&self) -> GameInfoPrivacy {
+        self.game_info
+    }
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Game account owned by an account.
+pub struct GameAccountData {
+    id: u64,
+    program: u32,
+    region: u32,
+    name: String,
+    licenses: Vec<u32>,
+}
+
+impl GameAccountData {
+    /// Creates a new game account without licenses.
+    pub fn new(id: u64, program: u32, region: u32, name: String) -> Self {
+        Self {
+            id,
+            program,
+            region,
+            name,
+            licenses: Vec::new(),
+        }
+    }
+
+    /// Retrieve the numeric identifier of the game account.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retrieve the program (game) this game account belongs to.
+    pub fn program(&self) -> u32 {
+        self.program
+    }
+
+    /// Retrieve the region this game account belongs to.
+    pub fn region(&self) -> u32 {
+        self.region
+    }
+
+    /// Retrieve the display name of the game account.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the licenses granted to this game account.
+    pub fn licenses(&self) -> &[u32] {
+        &self.licenses
+    }
+
+    /// Grant the license to this game account.
+    pub fn add_license(&mut self, license: u32) {
+        if !self.licenses.contains(&license) {
+            self.licenses.push(license);
+        }
+    }
+
+    /// Retrieve the entity ID of this game account.
+    pub fn entity_id(&self) -> EntityId {
+        EntityId {
+            // Game account type combined with the program.
+            high: 0x0200_0000_0000_0000 | u64::from(self.program),
+            low: self.id,
+        }
+    }
+
+    /// Retrieve the handle of this game account.
+    pub fn handle(&self) -> GameAccountHandle {
+        GameAccountHandle {
+            id: self.id as u32,
+            program: self.program,
+            region: self.region,
+        }
+    }
+
+    /// Returns true if the provided handle refers to this game account.
+    pub fn matches(&self, handle: &GameAccountHandle) -> bool {
+        self.handle() == *handle
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// State of one account.
+pub struct AccountData {
+    licenses: Vec<u32>,
+    privacy: PrivacySettings,
+    game_accounts: Vec<GameAccountData>,
+}
+
+impl AccountData {
+    /// Creates a new state without licenses and game accounts.
+    pub fn new() -> Self {
+        Self {
+            licenses: Vec::new(),
+            privacy: PrivacySettings::default(),
+            game_accounts: Vec::new(),
+        }
+    }
+
+    /// Creates the initial state of the account with the provided identifier.
+    ///
+    /// The state holds the Hearthstone game account which comes with each account, named
+    /// after the battle tag. See
+    /// [`Account::game_account_id`](::server::accounts::Account::game_account_id).
+    pub fn initial(account: u64, battle_tag: Option<&str>) -> Self {
+        let name = battle_tag.unwrap_or_default().to_string();
+        // The game account shares its identifier with the account.
+        let game_account = GameAccountData::new(account, HEARTHSTONE_PROGRAM, DEFAULT_REGION, name);
+        let mut data = Self::new();
+        data.add_game_account(game_account);
+        data
+    }
+
+    /// Retrieve the licenses granted to the account.
+    pub fn licenses(&self) -> &[u32] {
+        &self.licenses
+    }
+
+    /// Grant the license to the account.
+    pub fn add_license(&mut self, license: u32) {
+        if !self.licenses.contains(&license) {
+            self.licenses.push(license);
+        }
+    }
+
+    /// Retrieve the privacy settings of the account.
+    pub fn privacy(&self) -> &PrivacySettings {
+        &self.privacy
+    }
+
+    /// Replace the privacy settings of the account.
+    pub fn set_privacy(&mut self, privacy: PrivacySettings) {
+        self.privacy = privacy;
+    }
+
+    /// Retrieve the game accounts owned by the account.
+    pub fn game_accounts(&self) -> &[GameAccountData] {
+        &self.game_accounts
+    }
+
+    /// Find the game account with the provided entity ID.
+    pub fn game_account(&self, entity_id: &EntityId) -> Option<&GameAccountData> {
+        self.game_accounts
+            .iter()
+            .find(|game_account| game_account.entity_id() == *entity_id)
+    }
+
+    /// Find the game account behind the provided handle.
+    pub fn game_account_by_handle(&self, handle: &GameAccountHandle) -> Option<&GameAccountData> {
+        self.game_accounts
+            .iter()
+            .find(|game_account| game_account.matches(handle))
+    }
+
+    /// Add a game account to the account.
+    pub fn add_game_account(&mut self, game_account: GameAccountData) {
+        self.game_accounts.push(game_account);
+    }
+}
+
+impl Default for AccountData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Amount of copies of one card.
+pub struct CardStack {
+    asset: i32,
+    premium: i32,
+    count: u32,
+}
+
+impl CardStack {
+    /// Creates a new stack of `count` copies of the card with the provided asset ID.
+    ///
+    /// `premium` is zero for normal cards and non-zero for golden cards.
+    pub fn new(asset: i32, premium: i32, count: u32) -> Self {
+        Self {
+            asset,
+            premium,
+            count,
+        }
+    }
+
+    /// Retrieve the asset ID of the card.
+    pub fn asset(&self) -> i32 {
+        self.asset
+    }
+
+    /// Retrieve the premium type of the card, zero for normal cards.
+    pub fn premium(&self) -> i32 {
+        self.premium
+    }
+
+    /// Retrieve the amount of copies.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, TypedBuilder)]
+/// Deck built by a player.
+pub struct Deck {
+    /// Identifier of the deck, unique for the player.
+    id: i64,
+
+    /// Display name of the deck.
+    name: String,
+
+    /// Asset ID of the hero card.
+    hero: i32,
+
+    #[default = "0"]
+    /// Premium type of the hero card, zero for the normal hero.
+    hero_premium: i32,
+
+    #[default = "0"]
+    /// Identifier of the card back, zero for the default card back.
+    card_back: i32,
+
+    #[default = "0"]
+    /// Type of the deck, see `PegasusUtil.DeckInfo.DeckType`.
+    deck_type: i32,
+
+    #[default = "Vec::new()"]
+    /// Cards within the deck.
+    cards: Vec<CardStack>,
+}
+
+impl Deck {
+    /// Retrieve the identifier of the deck.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Retrieve the display name of the deck.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the asset ID of the hero card.
+    pub fn hero(&self) -> i32 {
+        self.hero
+    }
+
+    /// Retrieve the premium type of the hero card.
+    pub fn hero_premium(&self) -> i32 {
+        self.hero_premium
+    }
+
+    /// Retrieve the identifier of the card back.
+    pub fn card_back(&self) -> i32 {
+        self.card_back
+    }
+
+    /// Retrieve the type of the deck.
+    pub fn deck_type(&self) -> i32 {
+        self.deck_type
+    }
+
+    /// Retrieve the cards within the deck.
+    pub fn cards(&self) -> &[CardStack] {
+        &self.cards
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Currency balances of a player.
+pub struct Currencies {
+    gold: i64,
+    dust: i64,
+}
+
+impl Currencies {
+    /// Creates new balances.
+    pub fn new(gold: i64, dust: i64) -> Self {
+        Self { gold, dust }
+    }
+
+    /// Retrieve the amount of gold.
+    pub fn gold(&self) -> i64 {
+        self.gold
+    }
+
+    /// Retrieve the amount of arcane dust.
+    pub fn dust(&self) -> i64 {
+        self.dust
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TypedBuilder)]
+/// Progress of a player towards one achievement.
+pub struct Achievement {
+    /// Identifier of the achievement.
+    id: i32,
+
+    #[default = "0"]
+    /// Current progress.
+    progress: i32,
+
+    #[default = "0"]
+    /// Progress which was acknowledged by the client.
+    ack_progress: i32,
+
+    #[default = "0"]
+    /// Amount of times the achievement was completed.
+    completion_count: i32,
+
+    #[default = "true"]
+    /// The achievement can still be progressed.
+    active: bool,
+}
+
+impl Achievement {
+    /// Retrieve the identifier of the achievement.
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Retrieve the current progress.
+    pub fn progress(&self) -> i32 {
+        self.progress
+    }
+
+    /// Retrieve the progress which was acknowledged by the client.
+    pub fn ack_progress(&self) -> i32 {
+        self.ack_progress
+    }
+
+    /// Retrieve the amount of times the achievement was completed.
+    pub fn completion_count(&self) -> i32 {
+        self.completion_count
+    }
+
+    /// Returns true if the achievement can still be progressed.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// All state of one player.
+pub struct PlayerData {
+    account: Option<AccountData>,
+    collection: Vec<CardStack>,
+    decks: Vec<Deck>,
+    currencies: Currencies,
+    friends: Vec<u64>,
+    blocked: Vec<u64>,
+    achievements: Vec<Achievement>,
+}
+
+impl PlayerData {
+    /// Retrieve the state of the account, if any was stored.
+    pub fn account(&self) -> Option<&AccountData> {
+        self.account.as_ref()
+    }
+
+    /// Retrieve the cards owned by the player.
+    pub fn collection(&self) -> &[CardStack] {
+        &self.collection
+    }
+
+    /// Retrieve the decks of the player.
+    pub fn decks(&self) -> &[Deck] {
+        &self.decks
+    }
+
+    /// Retrieve the currency balances of the player.
+    pub fn currencies(&self) -> &Currencies {
+        &self.currencies
+    }
+
+    /// Retrieve the account identifiers of the friends of the player.
+    pub fn friends(&self) -> &[u64] {
+        &self.friends
+    }
+
+    /// Retrieve the account identifiers of the players blocked by the player.
+    pub fn blocked(&self) -> &[u64] {
+        &self.blocked
+    }
+
+    /// Retrieve the achievement progress of the player.
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+}
+
+mod error {
+    use prost::DecodeError;
+    use std::io;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Fail)]
+    /// Error type related to storing the state of players.
+    pub enum StorageError {
+        #[fail(display = "No identifiers are left to allocate")]
+        /// All identifiers were allocated.
+        Exhausted,
+
+        #[fail(display = "Failed to access {:?}: {}", path, error)]
+        /// Failure to read or write a file of the storage.
+        Io {
+            /// The file which was accessed.
+            path: PathBuf,
+            /// The cause of the failure.
+            #[cause]
+            error: io::Error,
+        },
+
+        #[fail(display = "The contents of {:?} are corrupt: {}", path, error)]
+        /// A file of the storage can't be decoded.
+        Corrupt {
+            /// The corrupt file.
+            path: PathBuf,
+            /// The cause of the failure.
+            #[cause]
+            error: DecodeError,
+        },
+
+        #[fail(
+            display = "The storage has schema version {}, this server supports up to {}",
+            found, supported
+        )]
+        /// The storage was written by a newer version of the server.
+        UnsupportedVersion {
+            /// The schema version of the storage.
+            found: u32,
+            /// The most recent schema version known to this server.
+            supported: u32,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::memory::MemoryStorage;
+    use super::*;
+    use std::slice;
+
+    #[test]
+    fn updates_parts_of_player() {
+        let storage = MemoryStorage::new();
+        assert!(storage.load_player(7).unwrap().is_none());
+
+        let mut data = AccountData::initial(7, Some("Alice#1234"));
+        data.add_license(12);
+        storage.save_account(7, &data).unwrap();
+        let deck = Deck::builder()
+            .id(1i64)
+            .name("Mage".to_string())
+            .hero(637)
+            .build();
+        storage.save_decks(7, slice::from_ref(&deck)).unwrap();
+        storage
+            .save_currencies(7, &Currencies::new(100, 40))
+            .unwrap();
+
+        assert_eq!(Some(data), storage.load_account(7).unwrap());
+        assert_eq!(vec![deck], storage.load_decks(7).unwrap());
+        assert_eq!(100, storage.load_currencies(7).unwrap().gold());
+        assert!(storage.load_friends(7).unwrap().is_empty());
+        assert!(storage.load_account(8).unwrap().is_none());
+    }
+}
@@ -0,0 +1,285 @@
+//! Layout of the files written by [`FileStorage`](super::file::FileStorage).
+//!
+//! Files are protobuf encoded, so fields can be added without breaking older files.
+//! Tags of fields must never be reused! Changes which older files can't follow require
+//! a new schema version and a migration, see [`file`](super::file).
+
+use firestarter_generated::proto::bnet::protocol::account::privacy_info::GameInfoPrivacy;
+
+use super::{
+    AccountData, Achievement as AchievementData, CardStack as CardStackData, Currencies,
+    Deck as DeckData, GameAccountData, PlayerData, PrivacySettings,
+};
+
+#[derive(Clone, PartialEq, Message)]
+/// Bookkeeping of the entire storage.
+pub struct Meta {
+    #[prost(uint32, tag = "1")]
+    pub schema_version: u32,
+    #[prost(uint64, tag = "2")]
+    pub next_game_account_id: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+/// All state of one player.
+pub struct Player {
+    #[prost(message, optional, tag = "1")]
+    pub account: Option<Account>,
+    #[prost(message, repeated, tag = "2")]
+    pub collection: Vec<CardStack>,
+    #[prost(message, repeated, tag = "3")]
+    pub decks: Vec<Deck>,
+    #[prost(int64, tag = "4")]
+    pub gold: i64,
+    #[prost(int64, tag = "5")]
+    pub dust: i64,
+    #[prost(uint64, repeated, tag = "6")]
+    pub friends: Vec<u64>,
+    #[prost(uint64, repeated, tag = "7")]
+    pub blocked: Vec<u64>,
+    #[prost(message, repeated, tag = "8")]
+    pub achievements: Vec<Achievement>,
+    /// Schema version this file was written with, files without one are of version 1.
+    #[prost(uint32, tag = "9")]
+    pub schema_version: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost(uint32, repeated, tag = "1")]
+    pub licenses: Vec<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub privacy: Option<Privacy>,
+    #[prost(message, repeated, tag = "3")]
+    pub game_accounts: Vec<GameAccount>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Privacy {
+    #[prost(bool, tag = "1")]
+    pub real_id: bool,
+    #[prost(bool, tag = "2")]
+    pub real_id_visible_for_friends: bool,
+    #[prost(bool, tag = "3")]
+    pub hidden_from_friend_finder: bool,
+    #[prost(int32, tag = "4")]
+    pub game_info: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GameAccount {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(uint32, tag = "2")]
+    pub program: u32,
+    #[prost(uint32, tag = "3")]
+    pub region: u32,
+    #[prost(string, tag = "4")]
+    pub name: String,
+    #[prost(uint32, repeated, tag = "5")]
+    pub licenses: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CardStack {
+    #[prost(int32, tag = "1")]
+    pub asset: i32,
+    #[prost(int32, tag = "2")]
+    pub premium: i32,
+    #[prost(uint32, tag = "3")]
+    pub count: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Deck {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(int32, tag = "3")]
+    pub hero: i32,
+    #[prost(int32, tag = "4")]
+    pub hero_premium: i32,
+    #[prost(int32, tag = "5")]
+    pub card_back: i32,
+    #[prost(int32, tag = "6")]
+    pub deck_type: i32,
+    #[prost(message, repeated, tag = "7")]
+    pub cards: Vec<CardStack>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Achievement {
+    #[prost(int32, tag = "1")]
+    pub id: i32,
+    #[prost(int32, tag = "2")]
+    pub progress: i32,
+    #[prost(int32, tag = "3")]
+    pub ack_progress: i32,
+    #[prost(int32, tag = "4")]
+    pub completion_count: i32,
+    #[prost(bool, tag = "5")]
+    pub active: bool,
+}
+
+impl<'a> From<&'a PlayerData> for Player {
+    fn from(data: &'a PlayerData) -> Self {
+        Self {
+            account: data.account.as_ref().map(Account::from),
+            collection: data.collection.iter().map(CardStack::from).collect(),
+            decks: data.decks.iter().map(Deck::from).collect(),
+            gold: data.currencies.gold,
+            dust: data.currencies.dust,
+            friends: data.friends.clone(),
+            blocked: data.blocked.clone(),
+            achievements: data.achievements.iter().map(Achievement::from).collect(),
+            schema_version: 0,
+        }
+    }
+}
+
+impl From<Player> for PlayerData {
+    fn from(player: Player) -> Self {
+        Self {
+            account: player.account.map(AccountData::from),
+            collection: player
+                .collection
+                .into_iter()
+                .map(CardStackData::from)
+                .collect(),
+            decks: player.decks.into_iter().map(DeckData::from).collect(),
+            currencies: Currencies::new(player.gold, player.dust),
+            friends: player.friends,
+            blocked: player.blocked,
+            achievements: player
+                .achievements
+                .into_iter()
+                .map(AchievementData::from)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a AccountData> for Account {
+    fn from(data: &'a AccountData) -> Self {
+        let privacy = &data.privacy;
+        Self {
+            licenses: data.licenses.clone(),
+            privacy: Some(Privacy {
+                real_id: privacy.real_id,
+                real_id_visible_for_friends: privacy.real_id_visible_for_friends,
+                hidden_from_friend_finder: privacy.hidden_from_friend_finder,
+                game_info: privacy.game_info as i32,
+            }),
+            game_accounts: data
+                .game_accounts
+                .iter()
+                .map(|game_account| GameAccount {
+                    id: game_account.id,
+                    program: game_account.program,
+                    region: game_account.region,
+                    name: game_account.name.clone(),
+                    licenses: game_account.licenses.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Account> for AccountData {
+    fn from(account: Account) -> Self {
+        let privacy = account.privacy.map(|privacy| {
+            let game_info = GameInfoPrivacy::from_i32(privacy.game_info)
+                .unwrap_or(GameInfoPrivacy::PrivacyFriends);
+            PrivacySettings::builder()
+                .real_id(privacy.real_id)
+                .real_id_visible_for_friends(privacy.real_id_visible_for_friends)
+                .hidden_from_friend_finder(privacy.hidden_from_friend_finder)
+                .game_info(game_info)
+                .build()
+        });
+        Self {
+            licenses: account.licenses,
+            privacy: privacy.unwrap_or_default(),
+            game_accounts: account
+                .game_accounts
+                .into_iter()
+                .map(|game_account| GameAccountData {
+                    id: game_account.id,
+                    program: game_account.program,
+                    region: game_account.region,
+                    name: game_account.name,
+                    licenses: game_account.licenses,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a CardStackData> for CardStack {
+    fn from(data: &'a CardStackData) -> Self {
+        Self {
+            asset: data.asset,
+            premium: data.premium,
+            count: data.count,
+        }
+    }
+}
+
+impl From<CardStack> for CardStackData {
+    fn from(stack: CardStack) -> Self {
+        Self::new(stack.asset, stack.premium, stack.count)
+    }
+}
+
+impl<'a> From<&'a DeckData> for Deck {
+    fn from(data: &'a DeckData) -> Self {
+        Self {
+            id: data.id,
+            name: data.name.clone(),
+            hero: data.hero,
+            hero_premium: data.hero_premium,
+            card_back: data.card_back,
+            deck_type: data.deck_type,
+            cards: data.cards.iter().map(CardStack::from).collect(),
+        }
+    }
+}
+
+impl From<Deck> for DeckData {
+    fn from(deck: Deck) -> Self {
+        Self {
+            id: deck.id,
+            name: deck.name,
+            hero: deck.hero,
+            hero_premium: deck.hero_premium,
+            card_back: deck.card_back,
+            deck_type: deck.deck_type,
+            cards: deck.cards.into_iter().map(CardStackData::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a AchievementData> for Achievement {
+    fn from(data: &'a AchievementData) -> Self {
+        Self {
+            id: data.id,
+            progress: data.progress,
+            ack_progress: data.ack_progress,
+            completion_count: data.completion_count,
+            active: data.active,
+        }
+    }
+}
+
+impl From<Achievement> for AchievementData {
+    fn from(achievement: Achievement) -> Self {
+        Self {
+            id: achievement.id,
+            progress: achievement.progress,
+            ack_progress: achievement.ack_progress,
+            completion_count: achievement.completion_count,
+            active: achievement.active,
+        }
+    }
+}
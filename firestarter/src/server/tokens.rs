@@ -0,0 +1,200 @@
+//! Single sign-on tokens, which let players log on again without credentials.
+//!
+//! A logged on client requests a token through `AuthenticationServer.GenerateSSOToken`.
+//! The token is presented when logging on again, eg when reconnecting to the lobby or
+//! when connecting to a game server.
+//!
+//! Tokens are bound to an account and expire after a configured lifetime. They're signed
+//! with HMAC-SHA256, so any server knowing the key can verify them without keeping track
+//! of issued tokens. Tokens remain valid across restarts as long as the key is unchanged.
+//!
+//! # Format
+//! ```text
+//! version (1 byte) | account ID (8 bytes) | expiry (8 bytes) | nonce (8 bytes) | signature (32 bytes)
+//! ```
+//! Integers are big-endian, the expiry is expressed in seconds since the UNIX epoch.
+
+use hmac::{Hmac, Mac};
+use rand::{self, RngCore};
+use sha2::Sha256;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use self::error::*;
+
+/// Recommended length of signing keys, in bytes.
+pub const TOKEN_KEY_LENGTH: usize = 32;
+
+/// Default amount of time a token remains valid after it was issued.
+pub const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+const TOKEN_VERSION: u8 = 1;
+const PAYLOAD_LENGTH: usize = 1 + 8 + 8 + 8;
+const SIGNATURE_LENGTH: usize = 32;
+
+/// Length of each token, in bytes.
+pub const TOKEN_LENGTH: usize = PAYLOAD_LENGTH + SIGNATURE_LENGTH;
+
+#[derive(Clone)]
+/// Issues and verifies single sign-on tokens.
+///
+/// See the module documentation for more information.
+pub struct TokenSigner {
+    key: Vec<u8>,
+    lifetime: Duration,
+}
+
+impl TokenSigner {
+    /// Creates a new signer with the provided key.
+    ///
+    /// Tokens issued by this signer are valid for `lifetime`.
+    pub fn new(key: Vec<u8>, lifetime: Duration) -> Self {
+        Self { key, lifetime }
+    }
+
+    /// Creates a new signer with a random key.
+    ///
+    /// Tokens issued by this signer are not accepted by other processes.
+    pub fn random(lifetime: Duration) -> Self {
+        Self::new(Self::generate_key(), lifetime)
+    }
+
+    /// Generate a random key of [`TOKEN_KEY_LENGTH`] bytes.
+    pub fn generate_key() -> Vec<u8> {
+        let mut key = vec![0; TOKEN_KEY_LENGTH];
+        rand::thread_rng().fill_bytes(&mut key);
+        key
+    }
+
+    /// Retrieve the amount of time issued tokens remain valid.
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    /// Issue a new token for the account with the provided identifier.
+    pub fn issue(&self, account: u64) -> Vec<u8> {
+        self.issue_at(account, SystemTime::now())
+    }
+
+    /// Issue a new token for the account as if the current time is `now`.
+    pub fn issue_at(&self, account: u64, now: SystemTime) -> Vec<u8> {
+        let expiry = unix_seconds(now) + self.lifetime.as_secs();
+        let mut nonce = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let mut token = Vec::with_capacity(TOKEN_LENGTH);
+        token.push(TOKEN_VERSION);
+        token.extend_from_slice(&account.to_be_bytes());
+        token.extend_from_slice(&expiry.to_be_bytes());
+        token.extend_from_slice(&nonce);
+        let signature = self.mac(&token).result().code();
+        token.extend_from_slice(&signature);
+        token
+    }
+
+    /// Verify the provided token.
+    ///
+    /// Returns the identifier of the account the token was issued for.
+    pub fn verify(&self, token: &[u8]) -> Result<u64, TokenError> {
+        self.verify_at(token, SystemTime::now())
+    }
+
+    /// Verify the provided token as if the current time is `now`.
+    pub fn verify_at(&self, token: &[u8], now: SystemTime) -> Result<u64, TokenError> {
+        if token.len() != TOKEN_LENGTH || token[0] != TOKEN_VERSION {
+            return Err(TokenError::Malformed);
+        }
+        let (payload, signature) = token.split_at(PAYLOAD_LENGTH);
+        // The comparison time doesn't depend on the position of the first mismatch.
+        self.mac(payload)
+            .verify(signature)
+            .map_err(|_| TokenError::InvalidSignature)?;
+
+        let account = read_u64(&payload[1..9]);
+        let expiry = read_u64(&payload[9..17]);
+        if unix_seconds(now) >= expiry {
+            return Err(TokenError::Expired);
+        }
+        Ok(account)
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        // HMAC accepts keys of any length.
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.key).unwrap();
+        mac.input(payload);
+        mac
+    }
+}
+
+impl fmt::Debug for TokenSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The key must never end up in logs.
+        f.debug_struct("TokenSigner")
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(bytes);
+    u64::from_be_bytes(value)
+}
+
+mod error {
+    #[derive(Debug, Fail, PartialEq, Eq)]
+    /// Error type related to verifying tokens.
+    pub enum TokenError {
+        #[fail(display = "The token is malformed")]
+        /// The token doesn't have the expected format.
+        Malformed,
+
+        #[fail(display = "The signature of the token is invalid")]
+        /// The token was not issued with the same key, or it was altered.
+        InvalidSignature,
+
+        #[fail(display = "The token has expired")]
+        /// The lifetime of the token has passed.
+        Expired,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verifies_issued_tokens() {
+        let signer = TokenSigner::new(vec![7; TOKEN_KEY_LENGTH], Duration::from_secs(60));
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let token = signer.issue_at(42, now);
+
+        assert_eq!(Ok(42), signer.verify_at(&token, now));
+        // Another signer with the same key accepts the token, eg after restarting.
+        let restarted = TokenSigner::new(vec![7; TOKEN_KEY_LENGTH], Duration::from_secs(60));
+        assert_eq!(Ok(42), restarted.verify_at(&token, now));
+        assert_eq!(
+            Err(TokenError::Expired),
+            signer.verify_at(&token, now + Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn rejects_forged_tokens() {
+        let signer = TokenSigner::new(vec![7; TOKEN_KEY_LENGTH], Duration::from_secs(60));
+        let mut token = signer.issue(42);
+
+        let other = TokenSigner::random(Duration::from_secs(60));
+        assert_eq!(Err(TokenError::InvalidSignature), other.verify(&token));
+        // Account ID 42 is stored within the last byte of the account field.
+        token[8] = 43;
+        assert_eq!(Err(TokenError::InvalidSignature), signer.verify(&token));
+        assert_eq!(Err(TokenError::Malformed), signer.verify(&token[1..]));
+    }
+}
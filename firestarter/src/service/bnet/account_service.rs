@@ -0,0 +1,741 @@
+//! Service exposing the state of accounts and game accounts to players.
+//!
+//! The state is kept within the [`Storage`] of the server. Accounts without stored state
+//! receive their initial state on first access, see [`AccountData::initial`].
+//!
+//! Clients subscribe to their account and game accounts through `Subscribe`. The current
+//! state is pushed through the `AccountNotify` service of the client right after
+//! subscribing, changes are pushed for as long as the subscription lasts.
+//!
+//! Clients can only access the account they're logged on with, and the game accounts
+//! owned by that account.
+
+use firestarter_generated::proto::bnet::protocol::account::{
+    self, AccountBlob, AccountFieldOptions, AccountId, AccountLevelInfo, AccountLicense,
+    AccountNotifyStub, AccountReference, AccountServiceMethod, AccountState,
+    AccountStateNotification, CreateGameAccountRequest, GameAccountBlob, GameAccountFieldOptions,
+    GameAccountHandle, GameAccountLink, GameAccountList, GameAccountNotification,
+    GameAccountSessionNotification, GameAccountState, GameAccountStateNotification, GameLevelInfo,
+    GameSessionUpdateInfo, GameStatus, GameTimeInfo, GetAccountRequest, GetAccountResponse,
+    GetAccountStateRequest, GetAccountStateResponse, GetGameAccountStateRequest,
+    GetGameAccountStateResponse, GetLicensesRequest, GetLicensesResponse, ParentalControlInfo,
+    PrivacyInfo, SubscriptionUpdateRequest, SubscriptionUpdateResponse,
+};
+use firestarter_generated::proto::bnet::protocol::{EntityId, NoData};
+use firestarter_generated::rpc::RpcFuture;
+use futures::future;
+use std::collections::HashMap;
+
+use protocol::bnet::session::{Identity, SessionHandle};
+use rpc::status::StatusCode;
+use rpc::system::RPCError;
+use server::accounts::{SharedAccountStore, ACCOUNT_ID_HIGH, DEFAULT_REGION, HEARTHSTONE_PROGRAM};
+use server::storage::{AccountData, GameAccountData, SharedStorage};
+
+type EntityKey = (u64, u64);
+
+// Realm permissions of each game account, which allows playing on the default realm.
+const DEFAULT_REALM_PERMISSIONS: u32 = 1;
+
+#[derive(Debug)]
+// Notifications which are pushed towards the client.
+enum Notification {
+    Account(AccountStateNotification),
+    GameAccount(GameAccountStateNotification),
+    GameAccounts(GameAccountNotification),
+    GameSession(GameAccountSessionNotification),
+}
+
+#[derive(Debug)]
+/// Service exposing the state of the account of one client.
+///
+/// See the module documentation for more information.
+pub struct AccountService {
+    session: SessionHandle,
+    accounts: SharedAccountStore,
+    storage: SharedStorage,
+    // Subscriber IDs chosen by the client, by subscribed entity.
+    subscriptions: HashMap<EntityKey, u64>,
+}
+
+impl AccountService {
+    const SERVICE_NAME: &'static str = AccountServiceMethod::SERVICE_NAME;
+
+    /// Creates a new service for the session behind the provided handle.
+    ///
+    /// The email address of the account is looked up within the provided accounts, all
+    /// other state is kept within the provided storage.
+    pub fn new(
+        session: SessionHandle,
+        accounts: SharedAccountStore,
+        storage: SharedStorage,
+    ) -> Self {
+        Self {
+            session,
+            accounts,
+            storage,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    fn invalid_request(method: AccountServiceMethod) -> RPCError {
+        RPCError::InvalidRequest {
+            service_name: Self::SERVICE_NAME,
+            method_id: method.id(),
+        }
+    }
+
+    /// Retrieve the identity of the client, which must be logged on.
+    fn identity(&self, method: AccountServiceMethod) -> Result<Identity, RPCError> {
+        self.session.identity().ok_or_else(|| {
+            warn!(self.session.logger(), "Account request before logon";
+                "method" => method.name(),
+            );
+            Self::invalid_request(method)
+        })
+    }
+
+    /// Load the state of the account the client is logged on with.
+    ///
+    /// The initial state is stored when nothing was stored yet.
+    fn load(&self, identity: &Identity) -> Result<AccountData, RPCError> {
+        let account = identity.account().low;
+        let stored = self.storage.load_account(account).map_err(|error| {
+            warn!(self.session.logger(), "Failed to load account"; "error" => %error);
+            StatusCode::Internal
+        })?;
+        match stored {
+            Some(data) => Ok(data),
+            None => {
+                let data = AccountData::initial(account, identity.battle_tag());
+                self.save(identity, &data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    fn save(&self, identity: &Identity, data: &AccountData) -> Result<(), RPCError> {
+        self.storage
+            .save_account(identity.account().low, data)
+            .map_err(|error| {
+                warn!(self.session.logger(), "Failed to save account"; "error" => %error);
+                StatusCode::Internal.into()
+            })
+    }
+
+    /// Returns true if the reference points to the account of the client.
+    ///
+    /// A missing reference points to the account of the client.
+    fn is_own_account(
+        &self,
+        identity: &Identity,
+        data: &AccountData,
+        reference: &AccountReference,
+    ) -> bool {
+        let account = identity.account().low;
+        let email_matches = |email: &str| {
+            self.accounts
+                .find_by_email(email)
+                .map(|found| found.id() == account)
+                .unwrap_or(false)
+        };
+
+        reference
+            .id
+            .map(|id| u64::from(id) == account & 0xFFFF_FFFF)
+            .unwrap_or(true)
+            && reference
+                .email
+                .as_ref()
+                .map(|email| email_matches(email))
+                .unwrap_or(true)
+            && reference
+                .battle_tag
+                .as_ref()
+                .map(|tag| identity.battle_tag() == Some(tag.as_str()))
+                .unwrap_or(true)
+            && reference
+                .handle
+                .as_ref()
+                .map(|handle| data.game_account_by_handle(handle).is_some())
+                .unwrap_or(true)
+    }
+
+    fn is_own_entity(identity: &Identity, entity_id: &EntityId) -> bool {
+        *entity_id == *identity.account()
+    }
+
+    fn send_notifications(session: &SessionHandle, notifications: Vec<Notification>) {
+        let mut stub = AccountNotifyStub::new(session.client());
+        let results = notifications
+            .into_iter()
+            .map(|notification| match notification {
+                Notification::Account(ref request) => stub.notify_account_state_updated(request),
+                Notification::GameAccount(ref request) => {
+                    stub.notify_game_account_state_updated(request)
+                }
+                Notification::GameAccounts(ref request) => {
+                    stub.notify_game_accounts_updated(request)
+                }
+                Notification::GameSession(ref request) => stub.notify_game_session_updated(request),
+            });
+        session.check_notifications(results);
+    }
+
+    /// Deliver the notifications after the response was produced, so the client receives
+    /// the response first.
+    fn respond_then_notify<T>(
+        &self,
+        response: T,
+        notifications: Vec<Notification>,
+    ) -> RpcFuture<T, RPCError>
+    where
+        T: Send + 'static,
+    {
+        let session = self.session.clone();
+        Box::new(future::lazy(move || {
+            Self::send_notifications(&session, notifications);
+            Ok(response)
+        }))
+    }
+}
+
+fn licenses(ids: &[u32]) -> Vec<AccountLicense> {
+    ids.iter()
+        .map(|&id| AccountLicense { id, expires: None })
+        .collect()
+}
+
+fn account_state(data: &AccountData, options: &AccountFieldOptions) -> AccountState {
+    let all = options.all_fields.unwrap_or(false);
+    let wanted = |field: Option<bool>| all || field.unwrap_or(false);
+    let game_accounts = data.game_accounts();
+    let mut state = AccountState::default();
+
+    if wanted(options.field_account_level_info) {
+        state.account_level_info = Some(AccountLevelInfo {
+            licenses: licenses(data.licenses()),
+            preferred_region: Some(DEFAULT_REGION),
+            ..Default::default()
+        });
+    }
+    if wanted(options.field_privacy_info) {
+        let privacy = data.privacy();
+        state.privacy_info = Some(PrivacyInfo {
+            is_using_rid: Some(privacy.real_id()),
+            is_real_id_visible_for_view_friends: Some(privacy.real_id_visible_for_friends()),
+            is_hidden_from_friend_finder: Some(privacy.hidden_from_friend_finder()),
+            game_info_privacy: Some(privacy.game_info() as i32),
+        });
+    }
+    if wanted(options.field_parental_control_info) {
+        state.parental_control_info = Some(ParentalControlInfo::default());
+    }
+    if wanted(options.field_game_level_info) {
+        state.game_level_info = game_accounts.iter().map(game_level_info).collect();
+    }
+    if wanted(options.field_game_status) {
+        state.game_status = game_accounts.iter().map(game_status).collect();
+    }
+    if wanted(options.field_game_accounts) {
+        state.game_accounts = game_account_lists(game_accounts);
+    }
+    state
+}
+
+// Groups the handles of the game accounts by region.
+fn game_account_lists(game_accounts: &[GameAccountData]) -> Vec<GameAccountList> {
+    let mut lists: Vec<GameAccountList> = vec![];
+    for game_account in game_accounts {
+        let region = Some(game_account.region());
+        match lists.iter().position(|list| list.region == region) {
+            Some(index) => lists[index].handle.push(game_account.handle()),
+            None => lists.push(GameAccountList {
+                region,
+                handle: vec![game_account.handle()],
+            }),
+        }
+    }
+    lists
+}
+
+fn game_level_info(game_account: &GameAccountData) -> GameLevelInfo {
+    GameLevelInfo {
+        name: Some(game_account.name().to_string()),
+        program: Some(game_account.program()),
+        licenses: licenses(game_account.licenses()),
+        realm_permissions: Some(DEFAULT_REALM_PERMISSIONS),
+        ..Default::default()
+    }
+}
+
+fn game_status(game_account: &GameAccountData) -> GameStatus {
+    GameStatus {
+        is_suspended: Some(false),
+        is_banned: Some(false),
+        program: Some(game_account.program()),
+        ..Default::default()
+    }
+}
+
+fn game_account_state(
+    game_account: &GameAccountData,
+    options: &GameAccountFieldOptions,
+) -> GameAccountState {
+    let all = options.all_fields.unwrap_or(false);
+    let wanted = |field: Option<bool>| all || field.unwrap_or(false);
+    GameAccountState {
+        game_level_info: Some(game_level_info(game_account))
+            .filter(|_| wanted(options.field_game_level_info)),
+        game_time_info: Some(GameTimeInfo {
+            is_unlimited_play_time: Some(true),
+            ..Default::default()
+        })
+        .filter(|_| wanted(options.field_game_time_info)),
+        game_status: Some(game_status(game_account)).filter(|_| wanted(options.field_game_status)),
+    }
+}
+
+fn all_account_fields() -> AccountFieldOptions {
+    AccountFieldOptions {
+        all_fields: Some(true),
+        ..Default::default()
+    }
+}
+
+fn all_game_account_fields() -> GameAccountFieldOptions {
+    GameAccountFieldOptions {
+        all_fields: Some(true),
+        ..Default::default()
+    }
+}
+
+impl account::AccountService for AccountService {
+    type Error = RPCError;
+
+    fn get_account(
+        &mut self,
+        request: GetAccountRequest,
+    ) -> RpcFuture<GetAccountResponse, Self::Error> {
+        let result = self
+            .identity(AccountServiceMethod::GetAccount)
+            .and_then(|identity| Ok((self.load(&identity)?, identity)));
+        let (data, identity) = match result {
+            Ok(loaded) => loaded,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let reference = request.ref_.clone().unwrap_or_default();
+        if !self.is_own_account(&identity, &data, &reference) {
+            debug!(self.session.logger(), "Refused access to foreign account";
+                "reference" => ?reference,
+            );
+            return Box::new(future::err(StatusCode::Denied.into()));
+        }
+
+        let all = request.fetch_all.unwrap_or(false);
+        let wanted = |field: Option<bool>| all || field.unwrap_or(false);
+        let account = identity.account().low;
+        let id = AccountId { id: account as u32 };
+        let email: Vec<_> = self
+            .accounts
+            .find_by_id(account)
+            .map(|found| found.email().to_string())
+            .into_iter()
+            .collect();
+        let battle_tag = identity.battle_tag().map(String::from);
+        let full_name = battle_tag
+            .as_ref()
+            .map(|tag| tag.split('#').next().unwrap_or_default().to_string());
+        let links: Vec<_> = data
+            .game_accounts()
+            .iter()
+            .map(|game_account| GameAccountLink {
+                game_account: game_account.handle(),
+                name: game_account.name().to_string(),
+            })
+            .collect();
+
+        let blob = AccountBlob {
+            id: id.id,
+            region: DEFAULT_REGION,
+            email: email.clone(),
+            full_name: full_name.clone().unwrap_or_default(),
+            licenses: licenses(data.licenses()),
+            account_links: links.clone(),
+            battle_tag: battle_tag.clone(),
+            ..Default::default()
+        };
+        let response = GetAccountResponse {
+            blob: Some(blob).filter(|_| wanted(request.fetch_blob)),
+            id: Some(id).filter(|_| wanted(request.fetch_id)),
+            email: email
+                .into_iter()
+                .filter(|_| wanted(request.fetch_email))
+                .collect(),
+            battle_tag: battle_tag.filter(|_| wanted(request.fetch_battle_tag)),
+            full_name: full_name.filter(|_| wanted(request.fetch_full_name)),
+            links: links
+                .into_iter()
+                .filter(|_| wanted(request.fetch_links))
+                .collect(),
+            parental_control_info: Some(ParentalControlInfo::default())
+                .filter(|_| wanted(request.fetch_parental_controls)),
+        };
+        Box::new(future::ok(response))
+    }
+
+    fn get_game_account(
+        &mut self,
+        request: GameAccountHandle,
+    ) -> RpcFuture<GameAccountBlob, Self::Error> {
+        let data = match self
+            .identity(AccountServiceMethod::GetGameAccount)
+            .and_then(|identity| self.load(&identity))
+        {
+            Ok(data) => data,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let game_account = match data.game_account_by_handle(&request) {
+            Some(game_account) => game_account,
+            None => return Box::new(future::err(StatusCode::NotExists.into())),
+        };
+
+        let blob = GameAccountBlob {
+            game_account: game_account.handle(),
+            name: Some(game_account.name().to_string()),
+            realm_permissions: Some(DEFAULT_REALM_PERMISSIONS),
+            status: 0,
+            flags: Some(0),
+            billing_flags: Some(0),
+            cache_expiration: 0,
+            licenses: licenses(game_account.licenses()),
+            ..Default::default()
+        };
+        Box::new(future::ok(blob))
+    }
+
+    fn create_game_account(
+        &mut self,
+        request: CreateGameAccountRequest,
+    ) -> RpcFuture<GameAccountHandle, Self::Error> {
+        let identity = match self.identity(AccountServiceMethod::CreateGameAccount) {
+            Ok(identity) => identity,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let account = identity.account().low;
+        if let Some(ref requested) = request.account {
+            if u64::from(requested.id) != account & 0xFFFF_FFFF {
+                return Box::new(future::err(StatusCode::Denied.into()));
+            }
+        }
+
+        let result = self.load(&identity).and_then(|mut data| {
+            let id = self.storage.allocate_game_account_id().map_err(|error| {
+                warn!(self.session.logger(), "Failed to allocate game account"; "error" => %error);
+                RPCError::from(StatusCode::Internal)
+            })?;
+            let name = identity.battle_tag().unwrap_or_default().to_string();
+            let game_account = GameAccountData::new(
+                id,
+                request.program.unwrap_or(HEARTHSTONE_PROGRAM),
+                request.region.unwrap_or(DEFAULT_REGION),
+                name,
+            );
+            data.add_game_account(game_account.clone());
+            self.save(&identity, &data)?;
+            Ok(game_account)
+        });
+        let game_account = match result {
+            Ok(game_account) => game_account,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        info!(self.session.logger(), "Game account created";
+            "game_account" => game_account.id(),
+            "program" => game_account.program(),
+            "region" => game_account.region(),
+        );
+
+        let mut notifications = vec![];
+        let account_key = (ACCOUNT_ID_HIGH, account);
+        if let Some(&subscriber_id) = self.subscriptions.get(&account_key) {
+            notifications.push(Notification::GameAccounts(GameAccountNotification {
+                region_delta: game_account_lists(::std::slice::from_ref(&game_account)),
+                subscriber_id: Some(subscriber_id),
+                account_tags: None,
+            }));
+        }
+        self.respond_then_notify(game_account.handle(), notifications)
+    }
+
+    fn subscribe(
+        &mut self,
+        request: SubscriptionUpdateRequest,
+    ) -> RpcFuture<SubscriptionUpdateResponse, Self::Error> {
+        let result = self
+            .identity(AccountServiceMethod::Subscribe)
+            .and_then(|identity| Ok((self.load(&identity)?, identity)));
+        let (data, identity) = match result {
+            Ok(loaded) => loaded,
+            Err(error) => return Box::new(future::err(error)),
+        };
+
+        let mut notifications = vec![];
+        for reference in &request.ref_ {
+            let entity_id = match reference.entity_id {
+                Some(ref entity_id) => entity_id,
+                None => return Box::new(future::err(StatusCode::InvalidSubscriber.into())),
+            };
+            let subscriber_id = reference.object_id.unwrap_or(0);
+
+            if Self::is_own_entity(&identity, entity_id) {
+                notifications.push(Notification::Account(AccountStateNotification {
+                    state: Some(account_state(&data, &all_account_fields())),
+                    subscriber_id: Some(subscriber_id),
+                    account_tags: None,
+                    subscription_completed: Some(true),
+                }));
+            } else if let Some(game_account) = data.game_account(entity_id) {
+                let state = game_account_state(game_account, &all_game_account_fields());
+                notifications.push(Notification::GameAccount(GameAccountStateNotification {
+                    state: Some(state),
+                    subscriber_id: Some(subscriber_id),
+                    game_account_tags: None,
+                    subscription_completed: Some(true),
+                }));
+                notifications.push(Notification::GameSession(GameAccountSessionNotification {
+                    game_account: Some(game_account.handle()),
+                    session_info: Some(GameSessionUpdateInfo::default()),
+                }));
+            } else {
+                debug!(self.session.logger(), "Refused subscription to foreign entity";
+                    "entity_id" => ?entity_id,
+                );
+                return Box::new(future::err(StatusCode::Denied.into()));
+            }
+        }
+
+        for reference in &request.ref_ {
+            if let Some(ref entity_id) = reference.entity_id {
+                let key = (entity_id.high, entity_id.low);
+                self.subscriptions
+                    .insert(key, reference.object_id.unwrap_or(0));
+            }
+        }
+        let response = SubscriptionUpdateResponse { ref_: request.ref_ };
+        self.respond_then_notify(response, notifications)
+    }
+
+    fn unsubscribe(
+        &mut self,
+        request: SubscriptionUpdateRequest,
+    ) -> RpcFuture<NoData, Self::Error> {
+        for reference in request.ref_ {
+            if let Some(entity_id) = reference.entity_id {
+                self.subscriptions.remove(&(entity_id.high, entity_id.low));
+            }
+        }
+        Box::new(future::ok(NoData {}))
+    }
+
+    fn get_account_state(
+        &mut self,
+        request: GetAccountStateRequest,
+    ) -> RpcFuture<GetAccountStateResponse, Self::Error> {
+        let result = self
+            .identity(AccountServiceMethod::GetAccountState)
+            .and_then(|identity| Ok((self.load(&identity)?, identity)));
+        let (data, identity) = match result {
+            Ok(loaded) => loaded,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        match request.entity_id {
+            Some(ref entity_id) if Self::is_own_entity(&identity, entity_id) => {}
+            _ => return Box::new(future::err(StatusCode::Denied.into())),
+        }
+
+        let options = request.options.unwrap_or_else(all_account_fields);
+        let response = GetAccountStateResponse {
+            state: Some(account_state(&data, &options)),
+            tags: None,
+        };
+        Box::new(future::ok(response))
+    }
+
+    fn get_game_account_state(
+        &mut self,
+        request: GetGameAccountStateRequest,
+    ) -> RpcFuture<GetGameAccountStateResponse, Self::Error> {
+        let data = match self
+            .identity(AccountServiceMethod::GetGameAccountState)
+            .and_then(|identity| self.load(&identity))
+        {
+            Ok(data) => data,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let game_account = match request
+            .game_account_id
+            .as_ref()
+            .and_then(|entity_id| data.game_account(entity_id))
+        {
+            Some(game_account) => game_account,
+            None => return Box::new(future::err(StatusCode::Denied.into())),
+        };
+
+        let options = request.options.unwrap_or_else(all_game_account_fields);
+        let response = GetGameAccountStateResponse {
+            state: Some(game_account_state(game_account, &options)),
+            tags: None,
+        };
+        Box::new(future::ok(response))
+    }
+
+    fn get_licenses(
+        &mut self,
+        request: GetLicensesRequest,
+    ) -> RpcFuture<GetLicensesResponse, Self::Error> {
+        let result = self
+            .identity(AccountServiceMethod::GetLicenses)
+            .and_then(|identity| Ok((self.load(&identity)?, identity)));
+        let (data, identity) = match result {
+            Ok(loaded) => loaded,
+            Err(error) => return Box::new(future::err(error)),
+        };
+        let target = request
+            .target_id
+            .clone()
+            .unwrap_or_else(|| identity.account().clone());
+
+        let mut response = GetLicensesResponse::default();
+        if Self::is_own_entity(&identity, &target) {
+            if request.get_account_licenses.unwrap_or(false) {
+                response.licenses.extend(licenses(data.licenses()));
+            }
+            if request.get_game_account_licenses.unwrap_or(false) {
+                let game_accounts = data.game_accounts().iter().filter(|game_account| {
+                    request
+                        .program_id
+                        .map(|program| program == game_account.program())
+                        .unwrap_or(true)
+                });
+                for game_account in game_accounts {
+                    response.licenses.extend(licenses(game_account.licenses()));
+                }
+            }
+        } else if let Some(game_account) = data.game_account(&target) {
+            response.licenses.extend(licenses(game_account.licenses()));
+        } else {
+            return Box::new(future::err(StatusCode::Denied.into()));
+        }
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter_generated::proto::bnet::protocol::account::{
+        AccountNotifyMethod, AccountService as AccountServer, SubscriberReference,
+    };
+    use futures::prelude::*;
+    use futures::sync::mpsc;
+    use protocol::bnet::session::{sent_method_ids, SessionCommand};
+    use server::accounts::{Account, AccountList, Credential};
+    use server::storage::memory::MemoryStorage;
+    use service::bnet::service_info::ImportedServiceID;
+    use std::sync::Arc;
+
+    fn build_service() -> (AccountService, mpsc::UnboundedReceiver<SessionCommand>) {
+        let (handle, receiver) = SessionHandle::detached_importing(&[(
+            AccountNotifyMethod::SERVICE_HASH,
+            ImportedServiceID::AccountNotify as u32,
+        )]);
+
+        let mut accounts = AccountList::new();
+        let account = Account::new(7, "alice@example.com".into(), Credential::Token(vec![1]))
+            .with_battle_tag("Alice#1234".into());
+        handle.set_identity(Some(Identity::new(
+            account.entity_id(),
+            Some("Alice#1234".into()),
+        )));
+        accounts.insert(account).unwrap();
+
+        let service =
+            AccountService::new(handle, Arc::new(accounts), Arc::new(MemoryStorage::new()));
+        (service, receiver)
+    }
+
+    #[test]
+    fn creates_game_accounts() {
+        let (mut service, receiver) = build_service();
+        let account = service.session.identity().unwrap().account().clone();
+        let request = SubscriptionUpdateRequest {
+            ref_: vec![SubscriberReference {
+                object_id: Some(3),
+                entity_id: Some(account.clone()),
+                ..Default::default()
+            }],
+        };
+        service.subscribe(request).wait().unwrap();
+
+        let request = GetAccountRequest {
+            fetch_all: Some(true),
+            ..Default::default()
+        };
+        let response = service.get_account(request).wait().unwrap();
+        assert_eq!(vec!["alice@example.com".to_string()], response.email);
+        assert_eq!(1, response.links.len());
+
+        let request = CreateGameAccountRequest {
+            region: Some(2),
+            ..Default::default()
+        };
+        let handle = service.create_game_account(request).wait().unwrap();
+        let blob = service.get_game_account(handle.clone()).wait().unwrap();
+        assert_eq!(handle, blob.game_account);
+
+        let request = GetAccountStateRequest {
+            entity_id: Some(account),
+            ..Default::default()
+        };
+        let state = service
+            .get_account_state(request)
+            .wait()
+            .unwrap()
+            .state
+            .unwrap();
+        assert_eq!(2, state.game_accounts.len());
+
+        assert_eq!(
+            vec![
+                AccountNotifyMethod::NotifyAccountStateUpdated.id(),
+                AccountNotifyMethod::NotifyGameAccountsUpdated.id(),
+            ],
+            sent_method_ids(receiver)
+        );
+    }
+
+    #[test]
+    fn refuses_foreign_accounts() {
+        let (mut service, _receiver) = build_service();
+        let request = GetAccountRequest {
+            ref_: Some(AccountReference {
+                email: Some("bob@example.com".into()),
+                ..Default::default()
+            }),
+            fetch_all: Some(true),
+            ..Default::default()
+        };
+        assert!(service.get_account(request).wait().is_err());
+
+        let request = SubscriptionUpdateRequest {
+            ref_: vec![SubscriberReference {
+                entity_id: Some(EntityId {
+                    high: ACCOUNT_ID_HIGH,
+                    low: 8,
+                }),
+                ..Default::default()
+            }],
+        };
+        assert!(service.subscribe(request).wait().is_err());
+    }
+}
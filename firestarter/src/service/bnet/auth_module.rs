@@ -0,0 +1,245 @@
+//! Pluggable modules which challenge the client during logon.
+//!
+//! The server instructs the client to load a module through `AuthenticationClient.ModuleLoad`.
+//! The client module answers through `AuthenticationServer.ModuleMessage`, after which the
+//! server replies with `AuthenticationClient.ModuleMessage`. This exchange repeats until
+//! the module on the server side accepts or denies the client.
+//!
+//! Modules are chained, each module must accept the client before the next one starts.
+//! The account accepted by a module is provided to the next module, the account accepted
+//! by the last module is logged on.
+//!
+//! Each session works on its own copy of the registered modules, see
+//! [`AuthModule::boxed_clone`].
+
+use firestarter_generated::proto::bnet::protocol::ContentHandle;
+use std::fmt;
+
+use rpc::status::StatusCode;
+use rpc::util::hash_service_name;
+use server::accounts::{Account, Credential};
+
+/// Module which can be shared between threads.
+pub type BoxedAuthModule = Box<dyn AuthModule>;
+
+#[derive(Debug, Clone, Copy)]
+/// Information about the logon attempt, provided to modules.
+pub struct ModuleContext<'a> {
+    email: &'a str,
+    account: Option<&'a Account>,
+}
+
+impl<'a> ModuleContext<'a> {
+    /// Creates a new context for the client which logs on with the provided email address.
+    pub fn new(email: &'a str, account: Option<&'a Account>) -> Self {
+        Self { email, account }
+    }
+
+    /// Retrieve the email address the client logs on with.
+    pub fn email(&self) -> &'a str {
+        self.email
+    }
+
+    /// Retrieve the account the client claims to own.
+    ///
+    /// This is the account accepted by the previous module, or the account matching
+    /// the email address for the first module. `None` is returned when no account
+    /// is known.
+    pub fn account(&self) -> Option<&'a Account> {
+        self.account
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Outcome of one step of a module.
+pub enum ModuleStep {
+    /// Deliver the message to the client module and wait for its answer.
+    Continue(Vec<u8>),
+    /// The client proved it owns the account.
+    Accept(Account),
+    /// The client failed the challenge.
+    Deny(StatusCode),
+}
+
+/// Challenge which the client must pass before logging on.
+pub trait AuthModule: fmt::Debug + Send + Sync {
+    /// Name of the module, used for logging.
+    fn name(&self) -> &'static str;
+
+    /// Handle of the module the client must load.
+    ///
+    /// This is only used when [`AuthModule::start`] continues.
+    fn content_handle(&self) -> ContentHandle;
+
+    /// Start a new exchange with the client, any previous exchange is forgotten.
+    ///
+    /// The message of [`ModuleStep::Continue`] is delivered when loading the module.
+    fn start(&mut self, context: &ModuleContext) -> ModuleStep;
+
+    /// Handle a message from the client module.
+    fn message(&mut self, context: &ModuleContext, message: &[u8]) -> ModuleStep;
+
+    /// Creates a copy of this module in its initial state.
+    fn boxed_clone(&self) -> BoxedAuthModule;
+}
+
+impl Clone for BoxedAuthModule {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Module accepting every client without challenge, for development only!
+///
+/// Clients logging on with an unknown email address receive an account which is
+/// derived from their email address. These accounts aren't checked for collisions
+/// with the account store.
+pub struct AcceptAnyModule;
+
+impl AcceptAnyModule {
+    /// Creates a new module.
+    pub fn new() -> Self {
+        AcceptAnyModule
+    }
+
+    fn derive_account(email: &str) -> Account {
+        // The high bit keeps these accounts apart from the low identifiers within
+        // account files.
+        let id = (1 << 63) | u64::from(hash_service_name(email.to_lowercase()));
+        // Nobody can log on with this account through credentials.
+        Account::new(id, email.to_string(), Credential::Token(vec![0; 32]))
+    }
+}
+
+impl AuthModule for AcceptAnyModule {
+    fn name(&self) -> &'static str {
+        "accept-any"
+    }
+
+    fn content_handle(&self) -> ContentHandle {
+        // Never loaded by the client.
+        ContentHandle::default()
+    }
+
+    fn start(&mut self, context: &ModuleContext) -> ModuleStep {
+        let account = context
+            .account()
+            .cloned()
+            .unwrap_or_else(|| Self::derive_account(context.email()));
+        ModuleStep::Accept(account)
+    }
+
+    fn message(&mut self, _context: &ModuleContext, _message: &[u8]) -> ModuleStep {
+        ModuleStep::Deny(StatusCode::InvalidArgs)
+    }
+
+    fn boxed_clone(&self) -> BoxedAuthModule {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenState {
+    Idle,
+    AwaitingToken,
+}
+
+#[derive(Debug, Clone)]
+/// Module which expects the client module to answer with the token of the account.
+///
+/// Only accounts with a [`Credential::Token`] can pass this module.
+pub struct TokenModule {
+    handle: ContentHandle,
+    state: TokenState,
+}
+
+impl TokenModule {
+    /// Creates a new module which lets the client load the module behind the handle.
+    pub fn new(handle: ContentHandle) -> Self {
+        Self {
+            handle,
+            state: TokenState::Idle,
+        }
+    }
+}
+
+impl AuthModule for TokenModule {
+    fn name(&self) -> &'static str {
+        "token"
+    }
+
+    fn content_handle(&self) -> ContentHandle {
+        self.handle.clone()
+    }
+
+    fn start(&mut self, _context: &ModuleContext) -> ModuleStep {
+        self.state = TokenState::AwaitingToken;
+        ModuleStep::Continue(Vec::new())
+    }
+
+    fn message(&mut self, context: &ModuleContext, message: &[u8]) -> ModuleStep {
+        if self.state != TokenState::AwaitingToken {
+            return ModuleStep::Deny(StatusCode::InvalidArgs);
+        }
+        self.state = TokenState::Idle;
+
+        match context.account() {
+            Some(account) => match *account.credential() {
+                Credential::Token(_) if account.credential().verify(message) => {
+                    ModuleStep::Accept(account.clone())
+                }
+                _ => ModuleStep::Deny(StatusCode::LogonInvalidAuthToken),
+            },
+            None => ModuleStep::Deny(StatusCode::Denied),
+        }
+    }
+
+    fn boxed_clone(&self) -> BoxedAuthModule {
+        Box::new(Self::new(self.handle.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_module_verifies_token() {
+        let account = Account::new(1, "a@example.com".into(), Credential::Token(vec![1, 2]));
+        let context = ModuleContext::new("a@example.com", Some(&account));
+        let mut module = TokenModule::new(ContentHandle::default());
+
+        // Messages are refused before the module is loaded.
+        assert_eq!(
+            ModuleStep::Deny(StatusCode::InvalidArgs),
+            module.message(&context, &[1, 2])
+        );
+        assert_eq!(ModuleStep::Continue(vec![]), module.start(&context));
+        assert_eq!(
+            ModuleStep::Deny(StatusCode::LogonInvalidAuthToken),
+            module.message(&context, &[1, 3])
+        );
+
+        let mut module = module.boxed_clone();
+        module.start(&context);
+        assert_eq!(
+            ModuleStep::Accept(account.clone()),
+            module.message(&context, &[1, 2])
+        );
+    }
+
+    #[test]
+    fn accept_any_module_derives_account() {
+        let context = ModuleContext::new("Bob@example.com", None);
+        let account = match AcceptAnyModule::new().start(&context) {
+            ModuleStep::Accept(account) => account,
+            step => panic!("Unexpected step {:?}", step),
+        };
+        let context = ModuleContext::new("bob@example.com", None);
+        match AcceptAnyModule::new().start(&context) {
+            ModuleStep::Accept(other) => assert_eq!(account.id(), other.id()),
+            step => panic!("Unexpected step {:?}", step),
+        }
+    }
+}
@@ -0,0 +1,962 @@
+//! Service handling the logon of players.
+//!
+//! The client starts with a `Logon` request, which is answered right away. The result
+//! of the logon is delivered afterwards through the `AuthenticationClient` service of
+//! the client: `LogonUpdate`, `LogonComplete` and, on success, `AccountSettings`.
+//!
+//! Clients provide their credentials within the logon request, or through a
+//! `VerifyWebCredentials` request when web verification was announced during logon.
+//! The credentials are checked against an [`AccountStore`].
+//!
+//! Logged on clients can request a single sign-on token through `GenerateSSOToken`.
+//! This token is accepted instead of credentials during later logons, see
+//! [`tokens`](::server::tokens) for more information.
+//!
+//! When authentication modules are registered, the client must pass these modules instead
+//! and the credentials within the logon request are ignored. Single sign-on tokens are
+//! still accepted.
+//! See [`auth_module`](::service::bnet::auth_module) for more information.
+//!
+//! When a [`LogonQueue`] is configured, logons wait for admission before they're processed.
+//! Waiting clients receive their position through `LogonQueueUpdate` and are notified
+//! with `LogonQueueEnd` when admitted.
+
+use firestarter_generated::proto::bnet::protocol::authentication::{
+    self, AccountSettingsNotification, AuthenticationClientStub, AuthenticationServerMethod,
+    GameAccountSelectedRequest, GenerateSsoTokenRequest, GenerateSsoTokenResponse,
+    LogonQueueUpdateRequest, LogonRequest, LogonResult, LogonUpdateRequest, ModuleLoadRequest,
+    ModuleMessageRequest, SelectGameAccountRequest, VerifyWebCredentialsRequest,
+};
+use firestarter_generated::proto::bnet::protocol::{ContentHandle, EntityId, NoData};
+use firestarter_generated::rpc::RpcFuture;
+use futures::future;
+use futures::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_executor as executor;
+use tokio_timer::{Delay, Error as TimerError, Interval};
+
+use protocol::bnet::session::{Identity, SessionHandle};
+use rpc::status::StatusCode;
+use rpc::system::RPCError;
+use server::accounts::{Account, SharedAccountStore};
+use server::logon_queue::{LogonQueue, QueueTicket, DEFAULT_QUEUE_UPDATE_INTERVAL};
+use server::registry::SessionRegistry;
+use server::tokens::TokenSigner;
+use service::bnet::auth_module::{BoxedAuthModule, ModuleContext, ModuleStep};
+
+#[derive(Debug)]
+enum LogonState {
+    // No logon was attempted, or the last attempt failed.
+    Idle,
+    // Logon is waiting for admission by the logon queue.
+    Queued,
+    // Logon is waiting for the client to verify its web credentials.
+    AwaitingCredentials {
+        email: String,
+    },
+    // Logon is waiting for the client to answer the module at the provided index.
+    Authenticating {
+        email: String,
+        module: usize,
+        account: Option<Account>,
+    },
+    LoggedOn {
+        account: Account,
+    },
+}
+
+// Reasons for waiting within the logon queue to end early.
+enum QueueWaitError {
+    Timer(TimerError),
+    // The client can't be notified anymore.
+    Notify(RPCError),
+}
+
+#[derive(Debug)]
+/// Service handling the logon of one client.
+///
+/// See the module documentation for more information.
+pub struct AuthenticationService {
+    session: SessionHandle,
+    accounts: SharedAccountStore,
+    sessions: SessionRegistry,
+    tokens: TokenSigner,
+    queue: Option<LogonQueue>,
+    // Shared with logons which continue after waiting in the queue.
+    modules: Arc<Mutex<Vec<BoxedAuthModule>>>,
+    state: Arc<Mutex<LogonState>>,
+}
+
+impl AuthenticationService {
+    const SERVICE_NAME: &'static str = AuthenticationServerMethod::SERVICE_NAME;
+
+    /// Creates a new service for the session behind the provided handle.
+    ///
+    /// Credentials are checked against the provided accounts. The session is linked
+    /// with its account within the registry after logon.
+    /// Single sign-on tokens are issued and verified with the provided signer.
+    pub fn new(
+        session: SessionHandle,
+        accounts: SharedAccountStore,
+        sessions: SessionRegistry,
+        tokens: TokenSigner,
+    ) -> Self {
+        Self {
+            session,
+            accounts,
+            sessions,
+            tokens,
+            queue: None,
+            modules: Arc::new(Mutex::new(Vec::new())),
+            state: Arc::new(Mutex::new(LogonState::Idle)),
+        }
+    }
+
+    /// Add a module which the client must pass during logon.
+    ///
+    /// Modules run in order of registration.
+    pub fn register_module(&mut self, module: BoxedAuthModule) {
+        self.modules.lock().unwrap().push(module);
+    }
+
+    /// Let each logon wait within the provided queue before it's processed.
+    pub fn set_logon_queue(&mut self, queue: LogonQueue) {
+        self.queue = Some(queue);
+    }
+
+    // Creates a second service which shares the state of this service.
+    fn share(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            accounts: self.accounts.clone(),
+            sessions: self.sessions.clone(),
+            tokens: self.tokens.clone(),
+            queue: self.queue.clone(),
+            modules: self.modules.clone(),
+            state: self.state.clone(),
+        }
+    }
+
+    fn set_state(&self, state: LogonState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    fn take_state(&self) -> LogonState {
+        ::std::mem::replace(&mut *self.state.lock().unwrap(), LogonState::Idle)
+    }
+
+    fn logged_on_account(&self) -> Option<Account> {
+        match *self.state.lock().unwrap() {
+            LogonState::LoggedOn { ref account } => Some(account.clone()),
+            _ => None,
+        }
+    }
+
+    fn authenticate(&self, email: &str, secret: &[u8]) -> Result<Account, StatusCode> {
+        // Tokens can't be mistaken for credentials, because of their signature.
+        if let Ok(id) = self.tokens.verify(secret) {
+            return match self.accounts.find_by_id(id) {
+                Some(ref account) if account.email().eq_ignore_ascii_case(email) => {
+                    Ok(account.clone())
+                }
+                _ => {
+                    info!(self.session.logger(), "Logon denied, token of another account";
+                        "email" => email,
+                    );
+                    Err(StatusCode::LogonInvalidAuthToken)
+                }
+            };
+        }
+
+        // Unknown accounts and wrong credentials are indistinguishable for the client.
+        match self.accounts.find_by_email(email) {
+            Some(ref account) if account.credential().verify(secret) => Ok(account.clone()),
+            Some(_) => {
+                info!(self.session.logger(), "Logon denied, invalid credentials"; "email" => email);
+                Err(StatusCode::Denied)
+            }
+            None => {
+                info!(self.session.logger(), "Logon denied, unknown account"; "email" => email);
+                Err(StatusCode::Denied)
+            }
+        }
+    }
+
+    /// Wait until the logon queue admits the client.
+    ///
+    /// The client is notified about its position while waiting, and when it's admitted.
+    /// Waiting stops with an error when the client can't be notified, because its session
+    /// is closed.
+    fn wait_in_queue(&self, ticket: QueueTicket) -> impl Future<Item = (), Error = RPCError> {
+        let interval = self
+            .queue
+            .as_ref()
+            .map(|queue| queue.config().update_interval())
+            .unwrap_or(DEFAULT_QUEUE_UPDATE_INTERVAL);
+        let session = self.session.clone();
+        let update_session = self.session.clone();
+        let updates = Interval::new(Instant::now(), interval)
+            .map_err(QueueWaitError::Timer)
+            .for_each(move |now| {
+                let request = LogonQueueUpdateRequest {
+                    position: ticket.position(now),
+                    estimated_time: ticket.remaining(now).as_secs(),
+                    eta_deviation_in_sec: 0,
+                };
+                let mut stub = AuthenticationClientStub::new(update_session.client());
+                stub.logon_queue_update(&request)
+                    .map_err(QueueWaitError::Notify)
+            });
+
+        Delay::new(ticket.admission())
+            .map_err(QueueWaitError::Timer)
+            .select(updates)
+            .then(move |result| -> RpcFuture<(), RPCError> {
+                match result {
+                    Ok(_) => {}
+                    Err((QueueWaitError::Notify(error), _)) => return Box::new(future::err(error)),
+                    // The client is admitted anyway when the timer fails.
+                    Err((QueueWaitError::Timer(error), _)) => {
+                        warn!(session.logger(), "Logon queue timer failed"; "error" => %error);
+                    }
+                }
+                let mut stub = AuthenticationClientStub::new(session.client());
+                // Fails as well when the session closed after the last update.
+                stub.logon_queue_end(&NoData {})
+            })
+    }
+
+    /// Process the logon request of the client, after it was admitted.
+    fn proceed_logon(&self, email: String, request: LogonRequest) -> RpcFuture<NoData, RPCError> {
+        if !self.modules.lock().unwrap().is_empty() {
+            // Clients holding a single sign-on token already passed the modules.
+            return match request.sso_id {
+                Some(ref token) if self.tokens.verify(token).is_ok() => {
+                    self.complete_logon(&email, token)
+                }
+                _ => self.start_modules(email),
+            };
+        }
+
+        let secret = request.cached_web_credentials.or(request.sso_id);
+        match secret {
+            Some(secret) => self.complete_logon(&email, &secret),
+            None if request.web_client_verification == Some(true) => {
+                // The credentials arrive through a VerifyWebCredentials request.
+                self.set_state(LogonState::AwaitingCredentials { email });
+                Box::new(future::ok(NoData {}))
+            }
+            None => {
+                info!(self.session.logger(), "Logon denied, no credentials"; "email" => &email);
+                self.finish_logon(Err(StatusCode::Denied))
+            }
+        }
+    }
+
+    /// Finish the logon with the provided credentials.
+    ///
+    /// The result is delivered to the client after the returned future produces the
+    /// response, so the client receives the response first.
+    fn complete_logon(&self, email: &str, secret: &[u8]) -> RpcFuture<NoData, RPCError> {
+        let result = self.authenticate(email, secret);
+        self.finish_logon(result)
+    }
+
+    fn finish_logon(&self, result: Result<Account, StatusCode>) -> RpcFuture<NoData, RPCError> {
+        let result = result.and_then(|account| self.attach_identity(account));
+        if result.is_err() {
+            self.set_state(LogonState::Idle);
+        }
+
+        let session = self.session.clone();
+        let response = future::lazy(move || {
+            match result {
+                Ok((account, previous)) => {
+                    Self::notify_logon_success(&session, &account);
+                    if let Some(previous) = previous {
+                        Self::disconnect_previous(&previous);
+                    }
+                }
+                Err(status) => Self::notify_logon_failure(&session, status),
+            }
+            Ok(NoData {})
+        });
+        Box::new(response)
+    }
+
+    fn start_modules(&self, email: String) -> RpcFuture<NoData, RPCError> {
+        let account = self.accounts.find_by_email(&email);
+        let step =
+            self.modules.lock().unwrap()[0].start(&ModuleContext::new(&email, account.as_ref()));
+        self.set_state(LogonState::Authenticating {
+            email,
+            module: 0,
+            account,
+        });
+        self.drive_modules(step, true)
+    }
+
+    /// Act upon the step of the current module.
+    ///
+    /// `loading` indicates the step was produced by starting the module, which means the
+    /// client must load the module first.
+    fn drive_modules(
+        &self,
+        mut step: ModuleStep,
+        mut loading: bool,
+    ) -> RpcFuture<NoData, RPCError> {
+        let mut modules = self.modules.lock().unwrap();
+        loop {
+            let (email, module, account) = match self.take_state() {
+                LogonState::Authenticating {
+                    email,
+                    module,
+                    account,
+                } => (email, module, account),
+                other => {
+                    // The logon was abandoned meanwhile, eg because the session closed
+                    // while the logon was queued.
+                    self.set_state(other);
+                    debug!(self.session.logger(), "Logon is no longer authenticating");
+                    return Box::new(future::err(RPCError::Status {
+                        status: StatusCode::NotStarted,
+                    }));
+                }
+            };
+            trace!(self.session.logger(), "Authentication module step";
+                "module" => modules[module].name(),
+                "step" => ?step,
+            );
+
+            match step {
+                ModuleStep::Continue(message) => {
+                    let handle = modules[module].content_handle();
+                    self.set_state(LogonState::Authenticating {
+                        email,
+                        module,
+                        account,
+                    });
+                    return self.send_module_message(module, handle, message, loading);
+                }
+                ModuleStep::Accept(account) => {
+                    let next = module + 1;
+                    if next == modules.len() {
+                        return self.finish_logon(Ok(account));
+                    }
+                    step = modules[next].start(&ModuleContext::new(&email, Some(&account)));
+                    loading = true;
+                    self.set_state(LogonState::Authenticating {
+                        email,
+                        module: next,
+                        account: Some(account),
+                    });
+                }
+                ModuleStep::Deny(status) => {
+                    info!(self.session.logger(), "Logon denied by module";
+                        "module" => modules[module].name(),
+                        "email" => &email,
+                        "status" => ?status,
+                    );
+                    return self.finish_logon(Err(status));
+                }
+            }
+        }
+    }
+
+    fn send_module_message(
+        &self,
+        module: usize,
+        handle: ContentHandle,
+        message: Vec<u8>,
+        loading: bool,
+    ) -> RpcFuture<NoData, RPCError> {
+        let session = self.session.clone();
+        let response = future::lazy(move || {
+            let mut stub = AuthenticationClientStub::new(session.client());
+            if loading {
+                let request = ModuleLoadRequest {
+                    module_handle: handle,
+                    message: Some(message),
+                };
+                session.check_notifications(vec![stub.module_load(&request)]);
+            } else {
+                let request = ModuleMessageRequest {
+                    module_id: module as i32,
+                    message: Some(message),
+                };
+                // The client answers through a new request towards the server, so the
+                // response of the client is only awaited to release the pending call.
+                let logger = session.logger().clone();
+                let response = stub.module_message(&request).map(|_| ()).map_err(
+                    move |error| warn!(logger, "Module message failed"; "error" => %error),
+                );
+                executor::spawn(response);
+            }
+            Ok(NoData {})
+        });
+        Box::new(response)
+    }
+
+    /// Link the account with the session.
+    ///
+    /// Returns the session which was previously logged on with the same account, if any.
+    fn attach_identity(
+        &self,
+        account: Account,
+    ) -> Result<(Account, Option<SessionHandle>), StatusCode> {
+        let previous = self
+            .sessions
+            .set_account(self.session.client_id(), &account.entity_id())
+            .map_err(|error| {
+                warn!(self.session.logger(), "Failed to register account"; "error" => %error);
+                StatusCode::Internal
+            })?;
+
+        let battle_tag = account.battle_tag().map(String::from);
+        self.session
+            .set_identity(Some(Identity::new(account.entity_id(), battle_tag)));
+        self.set_state(LogonState::LoggedOn {
+            account: account.clone(),
+        });
+        info!(self.session.logger(), "Client logged on";
+            "account" => account.id(),
+            "email" => account.email(),
+        );
+        Ok((account, previous))
+    }
+
+    fn notify_logon_success(session: &SessionHandle, account: &Account) {
+        let mut stub = AuthenticationClientStub::new(session.client());
+        let logon_result = LogonResult {
+            error_code: StatusCode::Ok.code(),
+            account: Some(account.entity_id()),
+            game_account: vec![account.game_account_id()],
+            email: Some(account.email().to_string()),
+            battle_tag: account.battle_tag().map(String::from),
+            ..Default::default()
+        };
+        let results = vec![
+            stub.logon_update(&LogonUpdateRequest {
+                error_code: StatusCode::Ok.code(),
+            }),
+            stub.logon_complete(&logon_result),
+            stub.account_settings(&AccountSettingsNotification::default()),
+        ];
+        session.check_notifications(results);
+    }
+
+    fn notify_logon_failure(session: &SessionHandle, status: StatusCode) {
+        let mut stub = AuthenticationClientStub::new(session.client());
+        let logon_result = LogonResult {
+            error_code: status.code(),
+            ..Default::default()
+        };
+        let results = vec![
+            stub.logon_update(&LogonUpdateRequest {
+                error_code: status.code(),
+            }),
+            stub.logon_complete(&logon_result),
+        ];
+        session.check_notifications(results);
+    }
+
+    /// Disconnect the session which was previously logged on with the same account.
+    ///
+    /// The disconnect runs on its own, so the logon doesn't wait for the other session.
+    fn disconnect_previous(previous: &SessionHandle) {
+        info!(previous.logger(), "Account logged on from another session");
+        let logger = previous.logger().clone();
+        let disconnect = previous
+            .force_disconnect(
+                StatusCode::RpcDisconnect,
+                Some("Logged on elsewhere".into()),
+            )
+            .map_err(move |error| warn!(logger, "Failed to disconnect"; "error" => %error));
+        executor::spawn(disconnect);
+    }
+
+    fn select(&mut self, game_account: EntityId, method_id: u32) -> RpcFuture<NoData, RPCError> {
+        let account = match self.logged_on_account() {
+            Some(account) => account,
+            None => {
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id,
+                }))
+            }
+        };
+
+        let result = if game_account == account.game_account_id() {
+            self.attach_game_account(game_account.clone())
+        } else {
+            warn!(self.session.logger(), "Client selected a foreign game account";
+                "game_account" => ?game_account,
+            );
+            Err(StatusCode::Denied)
+        };
+
+        let session = self.session.clone();
+        let response = future::lazy(move || {
+            let request = GameAccountSelectedRequest {
+                result: result.err().unwrap_or(StatusCode::Ok).code(),
+                game_account: Some(game_account),
+            };
+            let mut stub = AuthenticationClientStub::new(session.client());
+            session.check_notifications(vec![stub.game_account_selected(&request)]);
+            Ok(NoData {})
+        });
+        Box::new(response)
+    }
+
+    fn attach_game_account(&mut self, game_account: EntityId) -> Result<(), StatusCode> {
+        self.sessions
+            .set_game_account(self.session.client_id(), &game_account)
+            .map_err(|error| {
+                warn!(self.session.logger(), "Failed to register game account"; "error" => %error);
+                StatusCode::Internal
+            })?;
+
+        let mut identity = self.session.identity().ok_or(StatusCode::Internal)?;
+        identity.set_game_account(game_account);
+        self.session.set_identity(Some(identity));
+        Ok(())
+    }
+}
+
+impl authentication::AuthenticationServer for AuthenticationService {
+    type Error = RPCError;
+
+    fn logon(&mut self, request: LogonRequest) -> RpcFuture<NoData, Self::Error> {
+        let method_id = AuthenticationServerMethod::Logon.id();
+        match *self.state.lock().unwrap() {
+            LogonState::Idle => {}
+            _ => {
+                warn!(self.session.logger(), "Repeated logon request");
+                return Box::new(future::err(StatusCode::InProgress.into()));
+            }
+        }
+
+        debug!(self.session.logger(), "Logon request";
+            "program" => ?request.program,
+            "platform" => ?request.platform,
+            "locale" => ?request.locale,
+            "version" => ?request.version,
+        );
+        let email = match request.email.clone() {
+            Some(email) => email,
+            None => {
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id,
+                }))
+            }
+        };
+
+        let ticket = match self.queue {
+            Some(ref queue) => queue.enqueue(),
+            None => return self.proceed_logon(email, request),
+        };
+        if ticket.is_admitted(Instant::now()) {
+            return self.proceed_logon(email, request);
+        }
+
+        debug!(self.session.logger(), "Logon queued";
+            "position" => ticket.position(Instant::now()),
+        );
+        self.set_state(LogonState::Queued);
+        let service = self.share();
+        let logger = self.session.logger().clone();
+        let queued_logon = self
+            .wait_in_queue(ticket)
+            .and_then(move |_| service.proceed_logon(email, request))
+            .map(|_| ())
+            .map_err(move |error| warn!(logger, "Queued logon failed"; "error" => %error));
+        // The response is sent right away, the logon continues on its own afterwards.
+        executor::spawn(queued_logon);
+        Box::new(future::ok(NoData {}))
+    }
+
+    fn module_message(&mut self, request: ModuleMessageRequest) -> RpcFuture<NoData, Self::Error> {
+        let message = request.message.unwrap_or_default();
+        let step = match *self.state.lock().unwrap() {
+            LogonState::Authenticating {
+                ref email,
+                module,
+                ref account,
+            } if request.module_id == module as i32 => {
+                let context = ModuleContext::new(email, account.as_ref());
+                self.modules.lock().unwrap()[module].message(&context, &message)
+            }
+            _ => {
+                warn!(self.session.logger(), "Unexpected module message";
+                    "module_id" => request.module_id,
+                );
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id: AuthenticationServerMethod::ModuleMessage.id(),
+                }));
+            }
+        };
+        self.drive_modules(step, false)
+    }
+
+    fn select_game_account_deprecated(
+        &mut self,
+        request: EntityId,
+    ) -> RpcFuture<NoData, Self::Error> {
+        let method_id = AuthenticationServerMethod::SelectGameAccountDeprecated.id();
+        self.select(request, method_id)
+    }
+
+    fn select_game_account(
+        &mut self,
+        request: SelectGameAccountRequest,
+    ) -> RpcFuture<NoData, Self::Error> {
+        let method_id = AuthenticationServerMethod::SelectGameAccount.id();
+        self.select(request.game_account, method_id)
+    }
+
+    fn generate_sso_token(
+        &mut self,
+        request: GenerateSsoTokenRequest,
+    ) -> RpcFuture<GenerateSsoTokenResponse, Self::Error> {
+        let account = match self.logged_on_account() {
+            Some(account) => account,
+            None => {
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id: AuthenticationServerMethod::GenerateSsoToken.id(),
+                }))
+            }
+        };
+
+        debug!(self.session.logger(), "Issuing single sign-on token";
+            "account" => account.id(),
+            "program" => ?request.program,
+        );
+        let response = GenerateSsoTokenResponse {
+            sso_id: Some(self.tokens.issue(account.id())),
+            sso_secret: None,
+        };
+        Box::new(future::ok(response))
+    }
+
+    fn verify_web_credentials(
+        &mut self,
+        request: VerifyWebCredentialsRequest,
+    ) -> RpcFuture<NoData, Self::Error> {
+        let email = match self.take_state() {
+            LogonState::AwaitingCredentials { email } => email,
+            state => {
+                self.set_state(state);
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id: AuthenticationServerMethod::VerifyWebCredentials.id(),
+                }));
+            }
+        };
+
+        let secret = request.web_credentials.unwrap_or_default();
+        self.complete_logon(&email, &secret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter_generated::proto::bnet::protocol::authentication::{
+        AuthenticationClientMethod, AuthenticationServer,
+    };
+    use firestarter_generated::proto::bnet::protocol::connection::ConnectionServiceMethod;
+    use firestarter_generated::proto::bnet::protocol::ContentHandle;
+    use futures::sync::mpsc;
+    use protocol::bnet::session::{sent_method_ids, SessionCommand};
+    use server::accounts::{AccountList, Credential};
+    use server::logon_queue::LogonQueueConfig;
+    use service::bnet::auth_module::{AcceptAnyModule, TokenModule};
+    use service::bnet::service_info::ImportedServiceID;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::runtime::current_thread::Runtime;
+
+    fn build_service() -> (
+        AuthenticationService,
+        SessionRegistry,
+        mpsc::UnboundedReceiver<SessionCommand>,
+    ) {
+        let (handle, receiver) = SessionHandle::detached_importing(&[(
+            AuthenticationClientMethod::SERVICE_HASH,
+            ImportedServiceID::AuthenticationClient as u32,
+        )]);
+        let registry = SessionRegistry::new();
+        registry.register(handle.clone()).unwrap();
+
+        let mut accounts = AccountList::new();
+        let account = Account::new(
+            7,
+            "alice@example.com".into(),
+            Credential::Password("hunter2".into()),
+        );
+        accounts.insert(account).unwrap();
+        let account = Account::new(8, "bob@example.com".into(), Credential::Token(vec![1, 2]));
+        accounts.insert(account).unwrap();
+
+        let tokens = TokenSigner::random(Duration::from_secs(60));
+        let service =
+            AuthenticationService::new(handle, Arc::new(accounts), registry.clone(), tokens);
+        (service, registry, receiver)
+    }
+
+    #[test]
+    fn logs_on_with_password() {
+        let (mut service, registry, receiver) = build_service();
+        let request = LogonRequest {
+            email: Some("Alice@example.com".into()),
+            cached_web_credentials: Some(b"hunter2".to_vec()),
+            ..Default::default()
+        };
+        service.logon(request).wait().unwrap();
+
+        let account = EntityId {
+            high: ::server::accounts::ACCOUNT_ID_HIGH,
+            low: 7,
+        };
+        assert_eq!(1, registry.by_account(&account).unwrap().client_id().label);
+        let identity = service.session.identity().unwrap();
+        assert_eq!(&account, identity.account());
+
+        let game_account = EntityId {
+            high: ::server::accounts::GAME_ACCOUNT_ID_HIGH,
+            low: 7,
+        };
+        let request = SelectGameAccountRequest {
+            game_account: game_account.clone(),
+        };
+        service.select_game_account(request).wait().unwrap();
+        assert!(registry.by_game_account(&game_account).is_some());
+
+        assert_eq!(
+            vec![
+                AuthenticationClientMethod::LogonUpdate.id(),
+                AuthenticationClientMethod::LogonComplete.id(),
+                AuthenticationClientMethod::AccountSettings.id(),
+                AuthenticationClientMethod::GameAccountSelected.id(),
+            ],
+            sent_method_ids(receiver)
+        );
+    }
+
+    #[test]
+    fn disconnects_previous_session() {
+        let (previous, receiver) = SessionHandle::detached_importing(&[]);
+        let mut runtime = Runtime::new().unwrap();
+        runtime
+            .block_on(future::lazy(|| {
+                AuthenticationService::disconnect_previous(&previous);
+                Ok::<_, ()>(())
+            }))
+            .unwrap();
+        runtime.run().unwrap();
+        assert_eq!(
+            vec![ConnectionServiceMethod::ForceDisconnect.id()],
+            sent_method_ids(receiver)
+        );
+    }
+
+    #[test]
+    fn denies_invalid_credentials() {
+        let (mut service, registry, receiver) = build_service();
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            web_client_verification: Some(true),
+            ..Default::default()
+        };
+        service.logon(request).wait().unwrap();
+        let request = VerifyWebCredentialsRequest {
+            web_credentials: Some(b"wrong".to_vec()),
+        };
+        service.verify_web_credentials(request).wait().unwrap();
+
+        assert!(service.session.identity().is_none());
+        assert!(registry.snapshot()[0].account().is_none());
+        assert_eq!(
+            vec![
+                AuthenticationClientMethod::LogonUpdate.id(),
+                AuthenticationClientMethod::LogonComplete.id(),
+            ],
+            sent_method_ids(receiver)
+        );
+
+        // A new attempt is allowed after failure.
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            sso_id: Some(b"hunter2".to_vec()),
+            ..Default::default()
+        };
+        service.logon(request).wait().unwrap();
+        assert!(service.session.identity().is_some());
+    }
+
+    #[test]
+    fn logs_on_through_modules() {
+        let (mut service, _registry, receiver) = build_service();
+        service.register_module(Box::new(AcceptAnyModule::new()));
+        service.register_module(Box::new(TokenModule::new(ContentHandle::default())));
+
+        let request = LogonRequest {
+            email: Some("bob@example.com".into()),
+            // Credentials are ignored when modules are registered.
+            cached_web_credentials: Some(vec![1, 2]),
+            ..Default::default()
+        };
+        service.logon(request).wait().unwrap();
+        assert!(service.session.identity().is_none());
+
+        // The token module is the second module.
+        let request = ModuleMessageRequest {
+            module_id: 0,
+            message: Some(vec![1, 2]),
+        };
+        assert!(service.module_message(request).wait().is_err());
+        let request = ModuleMessageRequest {
+            module_id: 1,
+            message: Some(vec![1, 2]),
+        };
+        service.module_message(request).wait().unwrap();
+        assert_eq!(8, service.session.identity().unwrap().account().low);
+
+        assert_eq!(
+            vec![
+                AuthenticationClientMethod::ModuleLoad.id(),
+                AuthenticationClientMethod::LogonUpdate.id(),
+                AuthenticationClientMethod::LogonComplete.id(),
+                AuthenticationClientMethod::AccountSettings.id(),
+            ],
+            sent_method_ids(receiver)
+        );
+    }
+
+    #[test]
+    fn refuses_module_step_of_abandoned_logon() {
+        let (mut service, _registry, receiver) = build_service();
+        service.register_module(Box::new(AcceptAnyModule::new()));
+        // Eg the session closed while the logon was queued.
+        service.set_state(LogonState::Idle);
+        let step = ModuleStep::Continue(vec![1]);
+        match service.drive_modules(step, true).wait() {
+            Err(RPCError::Status { status }) => assert_eq!(StatusCode::NotStarted, status),
+            _ => panic!("Expected the module step to be refused"),
+        }
+        assert!(service.session.identity().is_none());
+        assert!(sent_method_ids(receiver).is_empty());
+    }
+
+    #[test]
+    fn logs_on_with_sso_token() {
+        let (mut service, _registry, _receiver) = build_service();
+        let request = GenerateSsoTokenRequest::default();
+        assert!(service.generate_sso_token(request).wait().is_err());
+
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            cached_web_credentials: Some(b"hunter2".to_vec()),
+            ..Default::default()
+        };
+        service.logon(request).wait().unwrap();
+        let request = GenerateSsoTokenRequest::default();
+        let token = service.generate_sso_token(request).wait().unwrap().sso_id;
+
+        // The token is only valid for the account it was issued for.
+        let (mut other, _registry, _receiver) = build_service();
+        other.tokens = service.tokens.clone();
+        let request = LogonRequest {
+            email: Some("bob@example.com".into()),
+            sso_id: token.clone(),
+            ..Default::default()
+        };
+        other.logon(request).wait().unwrap();
+        assert!(other.session.identity().is_none());
+
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            sso_id: token,
+            ..Default::default()
+        };
+        other.logon(request).wait().unwrap();
+        assert_eq!(7, other.session.identity().unwrap().account().low);
+    }
+
+    #[test]
+    fn waits_in_logon_queue() {
+        let (mut service, _registry, receiver) = build_service();
+        let config = LogonQueueConfig::builder()
+            .admissions_per_second(10u32)
+            .update_interval(Duration::from_millis(5))
+            .build();
+        let queue = LogonQueue::new(config);
+        // Another client occupies the first slot.
+        queue.enqueue();
+        service.set_logon_queue(queue);
+
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            cached_web_credentials: Some(b"hunter2".to_vec()),
+            ..Default::default()
+        };
+        let mut runtime = Runtime::new().unwrap();
+        // Queued logons are spawned onto the runtime.
+        runtime
+            .block_on(future::lazy(|| service.logon(request)))
+            .unwrap();
+        assert!(service.session.identity().is_none());
+        // Wait for the queued logon.
+        runtime.run().unwrap();
+        assert_eq!(7, service.session.identity().unwrap().account().low);
+
+        let methods = sent_method_ids(receiver);
+        assert_eq!(
+            AuthenticationClientMethod::LogonQueueUpdate.id(),
+            methods[0]
+        );
+        let end = methods.len() - 4;
+        assert_eq!(
+            &[
+                AuthenticationClientMethod::LogonQueueEnd.id(),
+                AuthenticationClientMethod::LogonUpdate.id(),
+                AuthenticationClientMethod::LogonComplete.id(),
+                AuthenticationClientMethod::AccountSettings.id(),
+            ],
+            &methods[end..]
+        );
+    }
+
+    #[test]
+    fn leaves_logon_queue_of_closed_session() {
+        let (mut service, _registry, receiver) = build_service();
+        let config = LogonQueueConfig::builder()
+            .admissions_per_second(10u32)
+            .update_interval(Duration::from_millis(5))
+            .build();
+        let queue = LogonQueue::new(config);
+        queue.enqueue();
+        service.set_logon_queue(queue);
+
+        let request = LogonRequest {
+            email: Some("alice@example.com".into()),
+            cached_web_credentials: Some(b"hunter2".to_vec()),
+            ..Default::default()
+        };
+        let mut runtime = Runtime::new().unwrap();
+        // The session closes right after the logon is queued.
+        runtime
+            .block_on(future::lazy(|| {
+                let response = service.logon(request);
+                drop(receiver);
+                response
+            }))
+            .unwrap();
+        runtime.run().unwrap();
+        assert!(service.session.identity().is_none());
+    }
+}
@@ -2,54 +2,80 @@
 //! client and server.
 
 use bytes::BytesMut;
-use futures::future::lazy;
+use firestarter_generated::proto::bnet::protocol::connection::{
+    self, BindRequest, BindResponse, ConnectionServiceMethod, DisconnectNotification,
+    DisconnectRequest, EchoRequest, EchoResponse,
+};
+use firestarter_generated::proto::bnet::protocol::NoData;
+use firestarter_generated::rpc::RpcFuture;
+use futures::future::{self, lazy};
 use futures::prelude::*;
 use prost::Message;
+use slog;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::Delay;
 
 use protocol::bnet::frame::BNetPacket;
-use protocol::bnet::session::LightWeightSession;
+use protocol::bnet::packet_extension::RESPONSE_SERVICE_ID;
+use protocol::bnet::session::{LightWeightSession, SessionConfig, SessionHandle};
+use rpc::status::StatusCode;
 use rpc::system::RPCError;
 use rpc::transport::{Request, Response};
+use service::bnet::router::ServiceBindings;
 
-#[derive(Debug, Default)]
+/// IDs for services which are unknown to the server are allocated starting from this
+/// value, which lies beyond all known services.
+const FIRST_UNSUPPORTED_SERVICE_ID: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Defines how services, which are unknown to the server, are handled while binding.
+pub enum BindMode {
+    /// Reject the entire bind request.
+    Strict,
+    /// Accept the bind request and record the unknown services as unsupported.
+    ///
+    /// Requests towards unsupported services are rejected with `RpcNotImplemented`, which
+    /// allows newer clients to connect as long as they don't depend on these services.
+    Lenient,
+}
+
+#[derive(Debug)]
 /// Service handling RPC requests/responses that manipulate the connection between
 /// client and server.
 ///
 /// See the module documentation for more information.
-pub struct ConnectionService {}
-
-#[allow(missing_docs)]
-#[repr(u32)]
-#[derive(Debug, Copy, Clone)]
-/// Addressable methods for this service.
-pub enum Methods {
-    Connect = 1,
-    Bind = 2,
-    Echo = 3,
-    ForceDisconnect = 4,
-    KeepAlive = 5,
-    Encrypt = 6,
-    RequestDisconnect = 7,
+pub struct ConnectionService {
+    session: SessionHandle,
+    bindings: Arc<Mutex<ServiceBindings>>,
+    echo_delay: Option<Duration>,
+    bind_mode: BindMode,
 }
 
 impl ConnectionService {
-    // TODO: Replace with general Service-like approach.
-    const SERVICE_NAME: &'static str = "ConnectionService";
-
-    /// See [`Methods::Connect`]
-    pub const METHOD_CONNECT: Methods = Methods::Connect;
-    /// See [`Methods::Bind`]
-    pub const METHOD_BIND: Methods = Methods::Bind;
-    /// See [`Methods::Echo`]
-    pub const METHOD_ECHO: Methods = Methods::Echo;
-    /// See [`Methods::ForceDisconnect`]
-    pub const METHOD_FORCE_DISCONNECT: Methods = Methods::ForceDisconnect;
-    /// See [`Methods::KeepAlive`]
-    pub const METHOD_KEEP_ALIVE: Methods = Methods::KeepAlive;
-    /// See [`Methods::Encrypt`]
-    pub const METHOD_ENCRYPT: Methods = Methods::Encrypt;
-    /// See [`Methods::RequestDisconnect`]
-    pub const METHOD_REQUEST_DISCONNECT: Methods = Methods::RequestDisconnect;
+    const SERVICE_NAME: &'static str = ConnectionServiceMethod::SERVICE_NAME;
+
+    /// Creates a new service operating on the session behind the provided handle.
+    ///
+    /// Bind requests extend the provided bindings table. The behaviour of this service
+    /// is further tuned by the session configuration.
+    pub fn new(
+        session: SessionHandle,
+        bindings: Arc<Mutex<ServiceBindings>>,
+        config: &SessionConfig,
+    ) -> Self {
+        Self {
+            session,
+            bindings,
+            echo_delay: config.echo_delay(),
+            bind_mode: config.bind_mode(),
+        }
+    }
+
+    fn close_session(&self) {
+        // The session could already be stopped, which is what the client asked for anyway.
+        let _ = self.session.close();
+    }
 }
 
 impl ConnectionService {
@@ -59,34 +85,142 @@ impl ConnectionService {
         let method = header.method_id.ok_or(RPCError::UnknownRequest {
             service_name: Self::SERVICE_NAME,
         })?;
-        if method == (Methods::Connect as u32) {
+        if method == ConnectionServiceMethod::Connect.id() {
             return Ok(());
         }
 
         Err(RPCError::InvalidRequest {
             service_name: Self::SERVICE_NAME,
-            method_id: Methods::Connect as u32,
+            method_id: ConnectionServiceMethod::Connect.id(),
+        })
+    }
+
+    /// Processes the service bindings requested by the client.
+    ///
+    /// The negotiated IDs are stored within the provided table and returned as
+    /// response for the client. The table is untouched when negotiation fails.
+    ///
+    /// See [`BindMode`] for how unknown services are handled.
+    fn negotiate_bindings(
+        bind_request: BindRequest,
+        bindings: &mut ServiceBindings,
+        method: ConnectionServiceMethod,
+        mode: BindMode,
+        logger: &slog::Logger,
+    ) -> Result<BindResponse, RPCError> {
+        use service::bnet::service_info::{SERVICES_EXPORTED_BINDING, SERVICES_IMPORTED_BINDING};
+
+        // This destructuring is probably difficult to understand.
+        // We're receiving the BindRequest from the client perspective;
+        // this means that any service that is "imported" is EXPORTED by us.
+        // Analogue for "exported", which is IMPORTED by us.
+        //
+        // The comments below will explain building a response in the
+        // perspective of the client.
+        let BindRequest {
+            imported_service_hash: exported_services,
+            exported_service: imported_services,
+        } = bind_request;
+
+        // Match all imported service IDs with our info.
+        let mut unknown_imported = vec![];
+        let mut mismatched_imported = vec![];
+        for service in &imported_services {
+            // Find the service for the provided hash.
+            match SERVICES_IMPORTED_BINDING
+                .get(&service.hash)
+                .map(|m| (*m) as u32)
+            {
+                Some(id) if id == service.id => {}
+                Some(_) => mismatched_imported.push(service.hash),
+                None => unknown_imported.push(service.hash),
+            }
+        }
+        let unknown_exported: Vec<u32> = exported_services
+            .iter()
+            .filter(|hash| !SERVICES_EXPORTED_BINDING.contains_key(hash))
+            .cloned()
+            .collect();
+
+        let all_known = unknown_imported.is_empty()
+            && mismatched_imported.is_empty()
+            && unknown_exported.is_empty();
+        if !all_known {
+            warn!(logger, "Client bound unknown services";
+                "mode" => ?mode,
+                "unknown_exported" => ?unknown_exported,
+                "unknown_imported" => ?unknown_imported,
+                "mismatched_imported" => ?mismatched_imported,
+            );
+
+            if mode == BindMode::Strict {
+                Err(RPCError::InvalidRequest {
+                    service_name: Self::SERVICE_NAME,
+                    method_id: method.id(),
+                })?;
+            }
+        }
+
+        // Bind our known exported services according to the service info.
+        for hash in &exported_services {
+            if let Some(id) = SERVICES_EXPORTED_BINDING.get(hash) {
+                bindings.bind_exported(*id as u32, *hash);
+            }
+        }
+        // Unknown services receive an ID beyond all known services, requests towards
+        // these are rejected by the router.
+        let mut next_unsupported_id = FIRST_UNSUPPORTED_SERVICE_ID;
+        for hash in &unknown_exported {
+            while bindings.exported_hash(next_unsupported_id).is_some()
+                || next_unsupported_id == RESPONSE_SERVICE_ID
+            {
+                next_unsupported_id += 1;
+            }
+            bindings.bind_exported(next_unsupported_id, *hash);
+            bindings.mark_unsupported(*hash);
+        }
+        let service_bindings: Vec<u32> = exported_services
+            .iter()
+            .map(|hash| {
+                SERVICES_EXPORTED_BINDING
+                    .get(hash)
+                    .map(|m| (*m) as u32)
+                    .or_else(|| bindings.exported_id(*hash))
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        // The client decides on the IDs of its own services.
+        for service in &imported_services {
+            bindings.bind_imported(service.hash, service.id);
+        }
+        for hash in &unknown_imported {
+            bindings.mark_unsupported(*hash);
+        }
+
+        Ok(BindResponse {
+            imported_service_id: service_bindings,
         })
     }
 
     /// Handles a direct connect request without going through routing and service handling.
     ///
-    /// This method can be used to directly handshake with a client, without side-effects.
+    /// This method can be used to directly handshake with a client. The negotiated bindings,
+    /// including services marked as unsupported, are stored within the returned session.
     pub fn connect_direct(
         session: LightWeightSession,
         request: Request<BNetPacket>,
     ) -> impl Future<Item = (LightWeightSession, Response<BNetPacket>), Error = RPCError> {
         use chrono::Local;
         use firestarter_generated::proto::bnet::protocol::connection::{
-            BindRequest, BindResponse, ConnectRequest, ConnectResponse,
+            ConnectRequest, ConnectResponse,
         };
-        use firestarter_generated::proto::bnet::protocol::ProcessId;
-        use service::bnet::service_info::{SERVICES_EXPORTED_BINDING, SERVICES_IMPORTED_BINDING};
 
         lazy(move || {
-            Self::is_connect_request(&request);
+            Self::is_connect_request(&request)?;
             Ok(request)
         }).and_then(move |request| {
+            let mut session = session;
             let body = request.as_ref().into_inner().body().clone();
             let message = ConnectRequest::decode(body)?;
             trace!(session.logger(), "Handshake request"; "message" => ?message);
@@ -95,66 +229,44 @@ impl ConnectionService {
             if bind_request.is_none() {
                 Err(RPCError::InvalidRequest {
                     service_name: Self::SERVICE_NAME,
-                    method_id: Self::METHOD_CONNECT as u32,
+                    method_id: ConnectionServiceMethod::Connect.id(),
                 })?;
             }
 
-            // This destructuring is probably difficult to understand.
-            // We're receiving the BindRequest from the client perspective;
-            // this means that any service that is "imported" is EXPORTED by us.
-            // Analogue for "exported", which is IMPORTED by us.
-            //
-            // The comments below will explain building a response in the
-            // perspective of the client.
-            let BindRequest {
-                imported_service_hash: exported_services,
-                exported_service: imported_services,
-            } = bind_request.unwrap();
-            // Match all imported service IDs with our info.
-            let match_imported_services = imported_services.into_iter().all(|s| {
-                // Find the service for the provided hash.
-                let known_import_opt = SERVICES_IMPORTED_BINDING.get(&s.hash).map(|m| (*m) as u32);
-                if let Some(id) = known_import_opt {
-                    if id == s.id {
-                        return true;
-                    }
-                }
-                false
-            });
-            if !match_imported_services {
-                Err(RPCError::InvalidRequest {
-                    service_name: Self::SERVICE_NAME,
-                    method_id: Self::METHOD_CONNECT as u32,
-                })?;
-            }
+            // Store the negotiated bindings, so requests can be routed later on.
+            let mut session_bindings = ServiceBindings::new();
+            let bind_response = Self::negotiate_bindings(
+                bind_request.unwrap(),
+                &mut session_bindings,
+                ConnectionServiceMethod::Connect,
+                session.config().bind_mode(),
+                session.logger(),
+            )?;
 
-            // Build a mapping for our exported services according to the service info.
-            let service_bindings: Vec<u32> = exported_services
-                .into_iter()
-                .map(|hash| {
-                    SERVICES_EXPORTED_BINDING
-                        .get(&hash)
-                        .map(|m| (*m) as u32)
-                        .unwrap_or(0)
-                })
+            // The client is informed when it doesn't provide the services we depend on.
+            let missing_services: Vec<u32> = session
+                .config()
+                .required_services()
+                .iter()
+                .filter(|hash| session_bindings.imported_id(**hash).is_none())
+                .cloned()
                 .collect();
-            let bind_response = BindResponse {
-                imported_service_id: service_bindings,
+            let bind_result = if missing_services.is_empty() {
+                StatusCode::Ok
+            } else {
+                warn!(session.logger(), "Client is missing required services";
+                    "missing" => ?missing_services,
+                );
+                StatusCode::RpcServiceNotBound
             };
+            session.set_bindings(session_bindings);
 
             // Start collecting all data into a response.
-            let time = Local::now().timestamp();
             let precise_time = Local::now().timestamp_nanos();
             let response_message = ConnectResponse {
-                server_id: ProcessId {
-                    label: 3868510373,
-                    epoch: time as u32,
-                },
-                client_id: Some(ProcessId {
-                    label: 1255760,
-                    epoch: time as u32,
-                }),
-                bind_result: Some(0),
+                server_id: session.server_id().clone(),
+                client_id: Some(session.client_id().clone()),
+                bind_result: Some(bind_result.code()),
                 bind_response: Some(bind_response),
                 server_time: Some(precise_time as u64),
                 ..Default::default()
@@ -173,3 +285,331 @@ impl ConnectionService {
             })
     }
 }
+
+impl connection::ConnectionService for ConnectionService {
+    type Error = RPCError;
+
+    fn connect(
+        &mut self,
+        _request: connection::ConnectRequest,
+    ) -> RpcFuture<connection::ConnectResponse, Self::Error> {
+        // Connecting happens once during the handshake, see [`ConnectionService::connect_direct`].
+        Box::new(future::err(RPCError::InvalidRequest {
+            service_name: Self::SERVICE_NAME,
+            method_id: ConnectionServiceMethod::Connect.id(),
+        }))
+    }
+
+    fn bind(&mut self, request: BindRequest) -> RpcFuture<BindResponse, Self::Error> {
+        trace!(self.session.logger(), "Bind request"; "message" => ?request);
+        let mut bindings = self.bindings.lock().unwrap();
+        let result = Self::negotiate_bindings(
+            request,
+            &mut bindings,
+            ConnectionServiceMethod::Bind,
+            self.bind_mode,
+            self.session.logger(),
+        );
+        Box::new(future::result(result))
+    }
+
+    fn echo(&mut self, request: EchoRequest) -> RpcFuture<EchoResponse, Self::Error> {
+        let response = EchoResponse {
+            time: request.time,
+            payload: request.payload,
+        };
+
+        match self.echo_delay {
+            Some(delay) => {
+                let response = Delay::new(Instant::now() + delay)
+                    .map_err(|_| RPCError::from(StatusCode::Internal))
+                    .map(move |_| response);
+                Box::new(response)
+            }
+            None => Box::new(future::ok(response)),
+        }
+    }
+
+    fn force_disconnect(&mut self, request: DisconnectNotification) -> RpcFuture<(), Self::Error> {
+        info!(self.session.logger(), "Client forced disconnect";
+            "error_code" => request.error_code,
+            "reason" => ?request.reason,
+        );
+        self.close_session();
+        Box::new(future::ok(()))
+    }
+
+    fn keep_alive(&mut self, _request: NoData) -> RpcFuture<(), Self::Error> {
+        // The session registers activity for each received packet, so there is nothing
+        // left to do.
+        trace!(self.session.logger(), "Keep alive");
+        Box::new(future::ok(()))
+    }
+
+    fn request_disconnect(&mut self, request: DisconnectRequest) -> RpcFuture<(), Self::Error> {
+        info!(self.session.logger(), "Client requested disconnect";
+            "error_code" => request.error_code,
+        );
+        self.close_session();
+        Box::new(future::ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter_generated::proto::bnet::protocol::connection::{
+        BoundService, ConnectionService as Handler,
+    };
+    use futures::sync::mpsc;
+    use protocol::bnet::session::SessionCommand;
+    use service::bnet::service_info::{ExportedServiceID, ImportedServiceID};
+    use tokio::runtime::current_thread::Runtime;
+
+    const AUTHENTICATION_SERVER_HASH: u32 = 233634817;
+    const AUTHENTICATION_CLIENT_HASH: u32 = 1898188341;
+    const UNKNOWN_HASH: u32 = 0xDEAD_BEEF;
+
+    fn negotiate(
+        request: BindRequest,
+        bindings: &mut ServiceBindings,
+        mode: BindMode,
+    ) -> Result<BindResponse, RPCError> {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        ConnectionService::negotiate_bindings(
+            request,
+            bindings,
+            ConnectionServiceMethod::Bind,
+            mode,
+            &logger,
+        )
+    }
+
+    fn build_service(
+        config: &SessionConfig,
+    ) -> (
+        ConnectionService,
+        Arc<Mutex<ServiceBindings>>,
+        mpsc::UnboundedReceiver<SessionCommand>,
+    ) {
+        let (session, receiver) = SessionHandle::detached_importing(&[]);
+        let bindings = Arc::new(Mutex::new(ServiceBindings::new()));
+        let service = ConnectionService::new(session, bindings.clone(), config);
+        (service, bindings, receiver)
+    }
+
+    // Returns all commands queued by a detached handle.
+    fn sent_commands(mut receiver: mpsc::UnboundedReceiver<SessionCommand>) -> Vec<SessionCommand> {
+        let mut commands = vec![];
+        // The handle holds a sender, so the receiver never finishes.
+        future::poll_fn(|| {
+            while let Async::Ready(Some(command)) = receiver.poll()? {
+                commands.push(command);
+            }
+            Ok::<_, ()>(Async::Ready(()))
+        })
+        .wait()
+        .unwrap();
+        commands
+    }
+
+    fn assert_close(command: &SessionCommand) {
+        match *command {
+            SessionCommand::Close => {}
+            SessionCommand::Send(_) => panic!("Expected the session to close"),
+        }
+    }
+
+    #[test]
+    fn echoes_request() {
+        let (mut service, _bindings, receiver) = build_service(&SessionConfig::default());
+        let request = EchoRequest {
+            time: Some(42),
+            network_only: None,
+            payload: Some(b"ping".to_vec()),
+        };
+        let response = Handler::echo(&mut service, request).wait().unwrap();
+
+        assert_eq!(Some(42), response.time);
+        assert_eq!(Some(b"ping".to_vec()), response.payload);
+        assert!(sent_commands(receiver).is_empty());
+    }
+
+    #[test]
+    fn delays_echo() {
+        let delay = Duration::from_millis(50);
+        let config = SessionConfig::builder().echo_delay(Some(delay)).build();
+        let (mut service, _bindings, _receiver) = build_service(&config);
+        let request = EchoRequest {
+            time: Some(42),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let response = Runtime::new()
+            .unwrap()
+            .block_on(Handler::echo(&mut service, request))
+            .unwrap();
+
+        assert!(start.elapsed() >= delay);
+        assert_eq!(Some(42), response.time);
+    }
+
+    #[test]
+    fn accepts_keep_alive() {
+        let (mut service, _bindings, receiver) = build_service(&SessionConfig::default());
+        Handler::keep_alive(&mut service, NoData {}).wait().unwrap();
+
+        assert!(sent_commands(receiver).is_empty());
+    }
+
+    #[test]
+    fn closes_session_on_request_disconnect() {
+        let (mut service, _bindings, receiver) = build_service(&SessionConfig::default());
+        let request = DisconnectRequest {
+            error_code: StatusCode::Ok.code(),
+        };
+        Handler::request_disconnect(&mut service, request)
+            .wait()
+            .unwrap();
+
+        let commands = sent_commands(receiver);
+        assert_eq!(1, commands.len());
+        assert_close(&commands[0]);
+    }
+
+    #[test]
+    fn closes_session_on_client_force_disconnect() {
+        let (mut service, _bindings, receiver) = build_service(&SessionConfig::default());
+        let request = DisconnectNotification {
+            error_code: StatusCode::RpcShutdown.code(),
+            reason: None,
+        };
+        Handler::force_disconnect(&mut service, request)
+            .wait()
+            .unwrap();
+
+        let commands = sent_commands(receiver);
+        assert_eq!(1, commands.len());
+        assert_close(&commands[0]);
+    }
+
+    #[test]
+    fn binds_within_session() {
+        let (mut service, bindings, _receiver) = build_service(&SessionConfig::default());
+        let request = BindRequest {
+            imported_service_hash: vec![AUTHENTICATION_SERVER_HASH],
+            exported_service: vec![BoundService {
+                hash: AUTHENTICATION_CLIENT_HASH,
+                id: ImportedServiceID::AuthenticationClient as u32,
+            }],
+        };
+        let response = Handler::bind(&mut service, request).wait().unwrap();
+
+        let server_id = ExportedServiceID::AuthenticationServer as u32;
+        assert_eq!(vec![server_id], response.imported_service_id);
+        let bindings = bindings.lock().unwrap();
+        assert_eq!(
+            Some(AUTHENTICATION_SERVER_HASH),
+            bindings.exported_hash(server_id)
+        );
+        assert_eq!(
+            Some(ImportedServiceID::AuthenticationClient as u32),
+            bindings.imported_id(AUTHENTICATION_CLIENT_HASH)
+        );
+    }
+
+    #[test]
+    fn notifies_server_initiated_disconnect() {
+        let (session, receiver) = SessionHandle::detached_importing(&[]);
+        session
+            .force_disconnect(StatusCode::RpcShutdown, Some("Maintenance".into()))
+            .wait()
+            .unwrap();
+
+        let commands = sent_commands(receiver);
+        assert_eq!(2, commands.len());
+        match commands[0] {
+            SessionCommand::Send(ref packet) => {
+                let header = packet.header();
+                assert_eq!(0, header.service_id);
+                assert_eq!(
+                    Some(ConnectionServiceMethod::ForceDisconnect.id()),
+                    header.method_id
+                );
+                let notification = DisconnectNotification::decode(packet.body().clone()).unwrap();
+                assert_eq!(StatusCode::RpcShutdown.code(), notification.error_code);
+                assert_eq!(Some("Maintenance".to_string()), notification.reason);
+            }
+            SessionCommand::Close => panic!("Expected the notification before closing"),
+        }
+        assert_close(&commands[1]);
+    }
+
+    #[test]
+    fn negotiates_bindings() {
+        let request = BindRequest {
+            imported_service_hash: vec![AUTHENTICATION_SERVER_HASH],
+            exported_service: vec![BoundService {
+                hash: AUTHENTICATION_CLIENT_HASH,
+                id: ImportedServiceID::AuthenticationClient as u32,
+            }],
+        };
+        let mut bindings = ServiceBindings::new();
+        let response = negotiate(request, &mut bindings, BindMode::Strict).unwrap();
+
+        let server_id = ExportedServiceID::AuthenticationServer as u32;
+        assert_eq!(vec![server_id], response.imported_service_id);
+        assert_eq!(
+            Some(AUTHENTICATION_SERVER_HASH),
+            bindings.exported_hash(server_id)
+        );
+        assert_eq!(
+            Some(ImportedServiceID::AuthenticationClient as u32),
+            bindings.imported_id(AUTHENTICATION_CLIENT_HASH)
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_import() {
+        let request = BindRequest {
+            imported_service_hash: vec![AUTHENTICATION_SERVER_HASH],
+            exported_service: vec![BoundService {
+                hash: AUTHENTICATION_CLIENT_HASH,
+                id: 100,
+            }],
+        };
+        let mut bindings = ServiceBindings::new();
+        let result = negotiate(request, &mut bindings, BindMode::Strict);
+
+        assert!(result.is_err());
+        assert_eq!(None, bindings.imported_id(AUTHENTICATION_CLIENT_HASH));
+    }
+
+    #[test]
+    fn accepts_unknown_services() {
+        let request = BindRequest {
+            imported_service_hash: vec![UNKNOWN_HASH, AUTHENTICATION_SERVER_HASH],
+            exported_service: vec![BoundService {
+                hash: UNKNOWN_HASH,
+                id: 40,
+            }],
+        };
+        let mut bindings = ServiceBindings::new();
+        let strict_result = negotiate(request.clone(), &mut bindings, BindMode::Strict);
+        assert!(strict_result.is_err());
+
+        let response = negotiate(request, &mut bindings, BindMode::Lenient).unwrap();
+        let server_id = ExportedServiceID::AuthenticationServer as u32;
+        assert_eq!(
+            vec![FIRST_UNSUPPORTED_SERVICE_ID, server_id],
+            response.imported_service_id
+        );
+        assert_eq!(
+            Some(UNKNOWN_HASH),
+            bindings.exported_hash(FIRST_UNSUPPORTED_SERVICE_ID)
+        );
+        assert_eq!(Some(40), bindings.imported_id(UNKNOWN_HASH));
+        assert!(bindings.is_unsupported(UNKNOWN_HASH));
+        assert!(!bindings.is_unsupported(AUTHENTICATION_SERVER_HASH));
+    }
+}
@@ -0,0 +1,380 @@
+//! Service carrying the Pegasus util packets of Hearthstone.
+//!
+//! Most lobby traffic of Hearthstone, eg retrieving the collection or decks, doesn't use
+//! dedicated BNet services. The client wraps the messages of `pegasusutil.proto` within
+//! the attributes of `GameUtilities.ProcessClientRequest` instead.
+//!
+//! The request holds the attribute [`PACKET_ATTRIBUTE`], which is a blob starting with the
+//! packet ID of the message as 16-bit little endian integer, followed by the encoded
+//! message.
+//! The response holds the packet ID of the reply as integer attribute, followed by the
+//! encoded reply as blob attribute. The client reads these attributes by position.
+//!
+//! Packets are dispatched to the [`UtilHandler`] registered for their packet ID, see
+//! [`GameUtilitiesService::register_handler`]. Each message carries its packet ID, eg
+//! `GetAccountInfo::PACKET_ID`, and `PegasusUtilPacket` decodes any util message by
+//! packet ID. The handlers of the packets sent during logon are found within
+//! [`util_handlers`](super::util_handlers).
+
+use bytes::IntoBuf;
+use firestarter_generated::proto::bnet::protocol::attribute::{Attribute, Variant};
+use firestarter_generated::proto::bnet::protocol::game_utilities::{
+    ClientRequest, ClientResponse, GameUtilities, GameUtilitiesMethod,
+};
+use firestarter_generated::proto::pegasusutil::PegasusUtilPacket;
+use firestarter_generated::rpc::RpcFuture;
+use futures::future;
+use prost::Message;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use protocol::bnet::session::{Identity, SessionHandle};
+use rpc::status::StatusCode;
+use rpc::system::RPCError;
+use server::storage::SharedStorage;
+
+/// Name of the request attribute holding the packet.
+pub const PACKET_ATTRIBUTE: &str = "p";
+/// Name of the response attribute holding the packet ID of the reply.
+pub const REPLY_ID_ATTRIBUTE: &str = "method_id";
+/// Name of the response attribute holding the encoded reply.
+pub const REPLY_BODY_ATTRIBUTE: &str = "message_blob";
+
+/// Handler which can be shared between sessions.
+pub type SharedUtilHandler = Arc<dyn UtilHandler>;
+
+/// Handlers indexed by the packet ID they handle.
+pub type UtilHandlerMap = HashMap<i32, SharedUtilHandler>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Pegasus util message, together with its packet ID.
+pub struct UtilPacket {
+    id: i32,
+    body: Vec<u8>,
+}
+
+impl UtilPacket {
+    /// Creates a new packet from an encoded message.
+    pub fn new(id: i32, body: Vec<u8>) -> Self {
+        Self { id, body }
+    }
+
+    /// Creates a new packet by encoding the message.
+    pub fn from_message<M: Message>(id: i32, message: &M) -> Self {
+        let mut body = Vec::with_capacity(message.encoded_len());
+        message
+            .encode(&mut body)
+            .expect("Vectors grow to fit the message");
+        Self::new(id, body)
+    }
+
+    /// Retrieve the packet ID.
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Retrieve the encoded message.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Retrieve the name of the util message with the packet ID of this packet.
+    pub fn message_name(&self) -> Option<&'static str> {
+        PegasusUtilPacket::message_name(self.id)
+    }
+
+    /// Decode the message within this packet.
+    ///
+    /// Messages which can't be decoded result in [`StatusCode::InvalidArgs`].
+    pub fn decode<M: Message + Default>(&self) -> Result<M, StatusCode> {
+        M::decode(self.body.as_slice().into_buf()).map_err(|_| StatusCode::InvalidArgs)
+    }
+
+    /// Unwrap the packet from the attributes of the request.
+    ///
+    /// `None` is returned if the request doesn't hold a packet.
+    fn from_request(request: &ClientRequest) -> Option<Self> {
+        let blob = request
+            .attribute
+            .iter()
+            .find(|attribute| attribute.name == PACKET_ATTRIBUTE)?
+            .value
+            .blob_value
+            .as_ref()?;
+        if blob.len() < 2 {
+            return None;
+        }
+        let id = i32::from(blob[0]) | (i32::from(blob[1]) << 8);
+        Some(Self::new(id, blob[2..].to_vec()))
+    }
+
+    /// Wrap the packet into the attributes of a response.
+    fn into_response(self) -> ClientResponse {
+        let id = Attribute {
+            name: REPLY_ID_ATTRIBUTE.to_string(),
+            value: Variant {
+                int_value: Some(i64::from(self.id)),
+                ..Default::default()
+            },
+        };
+        let body = Attribute {
+            name: REPLY_BODY_ATTRIBUTE.to_string(),
+            value: Variant {
+                blob_value: Some(self.body),
+                ..Default::default()
+            },
+        };
+        ClientResponse {
+            attribute: vec![id, body],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Information about the client sending the packet, provided to handlers.
+pub struct UtilContext<'a> {
+    session: &'a SessionHandle,
+    identity: &'a Identity,
+    storage: &'a SharedStorage,
+}
+
+impl<'a> UtilContext<'a> {
+    pub(crate) fn new(
+        session: &'a SessionHandle,
+        identity: &'a Identity,
+        storage: &'a SharedStorage,
+    ) -> Self {
+        Self {
+            session,
+            identity,
+            storage,
+        }
+    }
+
+    /// Retrieve the session of the client.
+    pub fn session(&self) -> &'a SessionHandle {
+        self.session
+    }
+
+    /// Retrieve the identity the client is logged on with.
+    pub fn identity(&self) -> &'a Identity {
+        self.identity
+    }
+
+    /// Retrieve the storage holding the state of players.
+    pub fn storage(&self) -> &'a SharedStorage {
+        self.storage
+    }
+}
+
+/// Handler of one or more kinds of Pegasus util packets.
+pub trait UtilHandler: fmt::Debug + Send + Sync {
+    /// Handle the packet sent by the client.
+    ///
+    /// The returned packet is the reply towards the client, `None` results in an empty
+    /// response.
+    fn handle(
+        &self,
+        context: &UtilContext,
+        packet: &UtilPacket,
+    ) -> Result<Option<UtilPacket>, StatusCode>;
+}
+
+#[derive(Debug)]
+/// Service dispatching the Pegasus util packets of one client.
+///
+/// See the module documentation for more information.
+pub struct GameUtilitiesService {
+    session: SessionHandle,
+    storage: SharedStorage,
+    handlers: UtilHandlerMap,
+}
+
+impl GameUtilitiesService {
+    const SERVICE_NAME: &'static str = GameUtilitiesMethod::SERVICE_NAME;
+
+    /// Creates a new service for the session behind the provided handle.
+    ///
+    /// Handlers can access the state of players within the provided storage.
+    pub fn new(session: SessionHandle, storage: SharedStorage) -> Self {
+        Self {
+            session,
+            storage,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Dispatch packets with the provided ID to the handler.
+    ///
+    /// The handler which was previously registered for the same ID is replaced.
+    pub fn register_handler(&mut self, packet_id: i32, handler: SharedUtilHandler) {
+        self.handlers.insert(packet_id, handler);
+    }
+
+    fn invalid_request(method: GameUtilitiesMethod) -> RPCError {
+        RPCError::InvalidRequest {
+            service_name: Self::SERVICE_NAME,
+            method_id: method.id(),
+        }
+    }
+
+    fn dispatch(&self, request: &ClientRequest) -> Result<ClientResponse, RPCError> {
+        let method = GameUtilitiesMethod::ProcessClientRequest;
+        let identity = self.session.identity().ok_or_else(|| {
+            warn!(self.session.logger(), "Util packet before logon");
+            Self::invalid_request(method)
+        })?;
+        let packet = UtilPacket::from_request(request).ok_or_else(|| {
+            warn!(self.session.logger(), "Client request without util packet";
+                "attributes" => ?request.attribute.iter().map(|a| &a.name).collect::<Vec<_>>(),
+            );
+            Self::invalid_request(method)
+        })?;
+
+        let handler = match self.handlers.get(&packet.id()) {
+            Some(handler) => handler,
+            None => {
+                warn!(self.session.logger(), "Unhandled util packet";
+                    "packet_id" => packet.id(),
+                    "message" => ?packet.message_name(),
+                );
+                return Err(StatusCode::NotImplemented.into());
+            }
+        };
+        trace!(self.session.logger(), "Util packet";
+            "packet_id" => packet.id(),
+            "message" => ?packet.message_name(),
+            "handler" => ?handler,
+        );
+        let context = UtilContext::new(&self.session, &identity, &self.storage);
+        let reply = handler.handle(&context, &packet)?;
+        Ok(reply.map(UtilPacket::into_response).unwrap_or_default())
+    }
+}
+
+impl GameUtilities for GameUtilitiesService {
+    type Error = RPCError;
+
+    fn process_client_request(
+        &mut self,
+        request: ClientRequest,
+    ) -> RpcFuture<ClientResponse, Self::Error> {
+        Box::new(future::result(self.dispatch(&request)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter_generated::proto::bnet::protocol::ProcessId;
+    use firestarter_generated::proto::pegasusutil::{get_account_info, GetAccountInfo};
+    use futures::prelude::*;
+    use server::storage::memory::MemoryStorage;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+
+    #[derive(Debug)]
+    struct EchoHandler;
+
+    impl UtilHandler for EchoHandler {
+        fn handle(
+            &self,
+            _context: &UtilContext,
+            packet: &UtilPacket,
+        ) -> Result<Option<UtilPacket>, StatusCode> {
+            let request = packet.decode::<GetAccountInfo>()?;
+            Ok(Some(UtilPacket::from_message(packet.id() + 1, &request)))
+        }
+    }
+
+    fn client_request(packet_id: u16, body: &[u8]) -> ClientRequest {
+        let mut blob = vec![packet_id as u8, (packet_id >> 8) as u8];
+        blob.extend_from_slice(body);
+        ClientRequest {
+            attribute: vec![Attribute {
+                name: PACKET_ATTRIBUTE.to_string(),
+                value: Variant {
+                    blob_value: Some(blob),
+                    ..Default::default()
+                },
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn build_service() -> (GameUtilitiesService, SessionHandle) {
+        let handle = SessionHandle::detached(
+            SocketAddr::from(([127, 0, 0, 1], 1000)),
+            ProcessId { label: 1, epoch: 1 },
+        );
+        let mut service = GameUtilitiesService::new(handle.clone(), Arc::new(MemoryStorage::new()));
+        service.register_handler(GetAccountInfo::PACKET_ID, Arc::new(EchoHandler));
+        (service, handle)
+    }
+
+    fn assert_invalid(service: &mut GameUtilitiesService, request: ClientRequest) {
+        match service.process_client_request(request).wait() {
+            Err(RPCError::InvalidRequest { .. }) => {}
+            other => panic!("Expected an invalid request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dispatches_packets_by_id() {
+        let (mut service, handle) = build_service();
+        handle.set_identity(Some(Identity::new(Default::default(), None)));
+        let packet_id = GetAccountInfo::PACKET_ID;
+
+        let message = GetAccountInfo {
+            request: Some(get_account_info::Request::DeckList as i32),
+        };
+        let packet = UtilPacket::from_message(packet_id, &message);
+        assert_eq!(Some("GetAccountInfo"), packet.message_name());
+        match PegasusUtilPacket::decode(packet_id, Cursor::new(packet.body())) {
+            Ok(Some(PegasusUtilPacket::GetAccountInfo(decoded))) => assert_eq!(message, decoded),
+            other => panic!("Expected an account info request, got {:?}", other),
+        }
+        let request = client_request(packet_id as u16, packet.body());
+        let response = service.process_client_request(request).wait().unwrap();
+        assert_eq!(
+            Some(i64::from(packet_id + 1)),
+            response.attribute[0].value.int_value
+        );
+        assert_eq!(
+            Some(packet.body().to_vec()),
+            response.attribute[1].value.blob_value
+        );
+
+        let request = client_request(1, &[]);
+        assert!(service.process_client_request(request).wait().is_err());
+    }
+
+    #[test]
+    fn refuses_packet_before_logon() {
+        let (mut service, handle) = build_service();
+        let packet_id = GetAccountInfo::PACKET_ID as u16;
+        assert_invalid(&mut service, client_request(packet_id, &[]));
+
+        handle.set_identity(Some(Identity::new(Default::default(), None)));
+        let request = client_request(packet_id, &[]);
+        assert!(service.process_client_request(request).wait().is_ok());
+    }
+
+    #[test]
+    fn refuses_request_without_packet() {
+        let (mut service, handle) = build_service();
+        handle.set_identity(Some(Identity::new(Default::default(), None)));
+
+        // The packet attribute is missing.
+        let mut request = client_request(GetAccountInfo::PACKET_ID as u16, &[]);
+        request.attribute[0].name = "q".to_string();
+        assert_invalid(&mut service, request);
+        assert_invalid(&mut service, ClientRequest::default());
+
+        // The blob is too short to hold a packet ID.
+        let mut request = client_request(0, &[]);
+        request.attribute[0].value.blob_value = Some(vec![201]);
+        assert_invalid(&mut service, request);
+    }
+}
@@ -1,4 +1,10 @@
 //! Services which are part of the BNet protocol.
 
+pub mod account_service;
+pub mod auth_module;
+pub mod authentication_service;
 pub mod connection_service;
+pub mod game_utilities_service;
+pub mod router;
 pub mod service_info;
+pub mod util_handlers;
@@ -0,0 +1,482 @@
+//! Routing of incoming requests towards the services handling them.
+//!
+//! Clients address services by a session specific ID. These IDs are negotiated during
+//! the handshake, see [`ConnectionService::connect_direct`], and stored within a
+//! [`ServiceBindings`] table.
+//! The [`ServiceRouter`] resolves the ID through this table into the hash of the service
+//! and forwards the request to the service registered with that hash.
+//!
+//! Services generated from the proto schemas are registered through their dispatcher,
+//! eg `ConnectionServiceDispatcher`, which decodes the request body and encodes the response.
+//!
+//! # Example
+//! ```
+//! # extern crate firestarter;
+//! # extern crate firestarter_generated;
+//! use std::sync::{Arc, Mutex};
+//! use firestarter_generated::proto::bnet::protocol::connection::{
+//!     ConnectionService, ConnectionServiceDispatcher,
+//! };
+//! use firestarter::rpc::system::RPCError;
+//! use firestarter::service::bnet::router::{ServiceBindings, ServiceRouter};
+//!
+//! // Each method of this service responds with a "not implemented" failure.
+//! struct Unimplemented;
+//! impl ConnectionService for Unimplemented {
+//!     type Error = RPCError;
+//! }
+//!
+//! let bindings = Arc::new(Mutex::new(ServiceBindings::new()));
+//! let mut router = ServiceRouter::new(bindings);
+//! router.register(ConnectionServiceDispatcher(Unimplemented));
+//! ```
+
+use firestarter_generated::proto::bnet::protocol::connection::ConnectionServiceMethod;
+use firestarter_generated::proto::bnet::protocol::{ErrorInfo, ObjectAddress, ProcessId};
+use firestarter_generated::rpc::ServiceDispatch;
+use futures::future;
+use futures::prelude::*;
+use slog;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::Deadline;
+
+use protocol::bnet::frame::BNetPacket;
+use rpc::system::{RPCError, Service, ServiceFuture};
+use rpc::transport::{Request, Response};
+use service::bnet::service_info::ExportedServiceID;
+
+/// Hash of the connection service, which is implicitly bound to ID 0 on both sides.
+const CONNECTION_SERVICE_HASH: u32 = ConnectionServiceMethod::SERVICE_HASH;
+
+#[derive(Debug, Clone)]
+/// Table of negotiated service IDs for one session.
+///
+/// Exported services are provided by the server, imported services are provided by the
+/// client.
+pub struct ServiceBindings {
+    // Maps the ID used by the client onto the hash of our service.
+    exported: HashMap<u32, u32>,
+    // Maps the hash of a client service onto the ID we must use to address it.
+    imported: HashMap<u32, u32>,
+    // Hashes of bound services which are unknown to the server.
+    unsupported: HashSet<u32>,
+}
+
+impl ServiceBindings {
+    /// Creates a new table which only contains the connection service bindings.
+    pub fn new() -> Self {
+        let connection_id = ExportedServiceID::ConnectionService as u32;
+        Self {
+            exported: hashmap!{connection_id => CONNECTION_SERVICE_HASH},
+            imported: hashmap!{CONNECTION_SERVICE_HASH => connection_id},
+            unsupported: HashSet::new(),
+        }
+    }
+
+    /// Bind the ID, as used by the client, to one of our services.
+    pub fn bind_exported(&mut self, id: u32, hash: u32) {
+        self.exported.insert(id, hash);
+    }
+
+    /// Bind the hash of a client service to the ID we must use when addressing it.
+    pub fn bind_imported(&mut self, hash: u32, id: u32) {
+        self.imported.insert(hash, id);
+    }
+
+    /// Retrieve the hash of our service which is bound to the provided ID.
+    pub fn exported_hash(&self, id: u32) -> Option<u32> {
+        self.exported.get(&id).cloned()
+    }
+
+    /// Retrieve the ID, as used by the client, of our service with the provided hash.
+    pub fn exported_id(&self, hash: u32) -> Option<u32> {
+        self.exported
+            .iter()
+            .find(|&(_, bound_hash)| *bound_hash == hash)
+            .map(|(id, _)| *id)
+    }
+
+    /// Record that the server doesn't know the bound service with the provided hash.
+    pub fn mark_unsupported(&mut self, hash: u32) {
+        self.unsupported.insert(hash);
+    }
+
+    /// Returns true if the bound service with the provided hash is unknown to the server.
+    pub fn is_unsupported(&self, hash: u32) -> bool {
+        self.unsupported.contains(&hash)
+    }
+
+    /// Retrieve the ID for addressing the client service with the provided hash.
+    pub fn imported_id(&self, hash: u32) -> Option<u32> {
+        self.imported.get(&hash).cloned()
+    }
+}
+
+impl Default for ServiceBindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Object which routes requests to registered services.
+pub struct ServiceRouter {
+    bindings: Arc<Mutex<ServiceBindings>>,
+    services: HashMap<u32, Box<dyn Service<BNetPacket> + Send>>,
+    // Identifies the server within the error information of failed responses.
+    server_id: ProcessId,
+}
+
+impl fmt::Debug for ServiceRouter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let service_names: Vec<_> = self.services.values().map(|s| s.name()).collect();
+        f.debug_struct("ServiceRouter")
+            .field("bindings", &self.bindings)
+            .field("services", &service_names)
+            .field("server_id", &self.server_id)
+            .finish()
+    }
+}
+
+impl ServiceRouter {
+    /// Creates a new router without services, resolving IDs through the provided bindings.
+    pub fn new(bindings: Arc<Mutex<ServiceBindings>>) -> Self {
+        Self {
+            bindings,
+            services: HashMap::new(),
+            server_id: ProcessId::default(),
+        }
+    }
+
+    /// Set the process ID of the server, as communicated to the client during the handshake.
+    pub fn set_server_id(&mut self, server_id: ProcessId) {
+        self.server_id = server_id;
+    }
+
+    /// Register a service, any previously registered service with the same hash
+    /// is replaced.
+    pub fn register<S>(&mut self, service: S)
+    where
+        S: Service<BNetPacket> + Send + 'static,
+    {
+        self.services.insert(service.hash(), Box::new(service));
+    }
+
+    /// Forward the request to the addressed service.
+    ///
+    /// Requests which fail, eg because of unknown services or methods, are answered with
+    /// a failure response which allows the session to continue. The status of the response
+    /// is derived from the error, see [`RPCError::status`].
+    /// Services which were bound but are unknown to the server, see
+    /// [`ServiceBindings::mark_unsupported`], are answered with `RpcNotImplemented`.
+    /// Requests carrying a timeout (in milliseconds) are cancelled when that timeout
+    /// expires, which is also answered with a failure response.
+    pub fn dispatch(
+        &mut self,
+        request: Request<BNetPacket>,
+        logger: &slog::Logger,
+    ) -> ServiceFuture<BNetPacket> {
+        let header = request.as_ref().into_inner().header().clone();
+        let (hash_opt, unsupported) = {
+            let bindings = self.bindings.lock().unwrap();
+            let hash_opt = bindings.exported_hash(header.service_id);
+            let unsupported = hash_opt.map(|hash| bindings.is_unsupported(hash));
+            (hash_opt, unsupported.unwrap_or(false))
+        };
+        let service_opt = hash_opt.and_then(|hash| self.services.get_mut(&hash));
+
+        let result = match (service_opt, header.method_id) {
+            (Some(service), Some(method_id)) => service.call(method_id, request),
+            (Some(service), None) => Box::new(future::err(RPCError::InvalidRequest {
+                service_name: service.name(),
+                method_id: 0,
+            })),
+            (None, _) if unsupported => Box::new(future::err(RPCError::UnsupportedService {
+                service_hash: hash_opt.unwrap_or(0),
+            })),
+            (None, _) => Box::new(future::err(RPCError::UnknownRequest {
+                service_name: "Unbound",
+            })),
+        };
+
+        let result: ServiceFuture<BNetPacket> = match header.timeout {
+            Some(timeout) if timeout > 0 => {
+                let token = header.token;
+                let deadline = Instant::now() + Duration::from_millis(timeout);
+                Box::new(
+                    Deadline::new(result, deadline).map_err(move |deadline_err| {
+                        deadline_err
+                            .into_inner()
+                            .unwrap_or(RPCError::Timeout { token })
+                    }),
+                )
+            }
+            _ => result,
+        };
+
+        let logger = logger.clone();
+        let server_id = self.server_id.clone();
+        let result = result.or_else(move |error| {
+            let status = error.status();
+            warn!(logger, "Rejected request";
+                "error" => %error,
+                "status" => %status,
+                "service_id" => header.service_id,
+                "method_id" => ?header.method_id,
+            );
+
+            let error_info = ErrorInfo {
+                object_address: ObjectAddress {
+                    host: server_id,
+                    object_id: header.object_id,
+                },
+                status: status.code(),
+                service_hash: hash_opt.unwrap_or(0),
+                method_id: header.method_id.unwrap_or(0),
+            };
+            let response = Response::from_status(&header, status).with_error(error_info);
+            Ok(Some(response))
+        });
+        Box::new(result)
+    }
+}
+
+impl<D> Service<BNetPacket> for D
+where
+    D: ServiceDispatch<Error = RPCError>,
+{
+    fn name(&self) -> &'static str {
+        self.service_name()
+    }
+
+    fn hash(&self) -> u32 {
+        self.service_hash()
+    }
+
+    fn call(&mut self, method_id: u32, request: Request<BNetPacket>) -> ServiceFuture<BNetPacket> {
+        let body = request.as_ref().into_inner().body().clone();
+        match self.dispatch(method_id, body) {
+            Some(result) => {
+                Box::new(result.map(move |body_opt| {
+                    body_opt.map(|body| Response::from_request(request, body))
+                }))
+            }
+            None => Box::new(future::err(RPCError::InvalidRequest {
+                service_name: self.service_name(),
+                method_id,
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use firestarter_generated::proto::bnet::protocol::connection::{
+        self, ConnectionServiceDispatcher,
+    };
+    use firestarter_generated::proto::bnet::protocol::Header;
+    use log;
+    use rpc::status::StatusCode;
+
+    const ECHO_SERVICE_NAME: &str = "firestarter.test.EchoService";
+    const UNSUPPORTED_HASH: u32 = 0xdead_beef;
+
+    struct EchoService;
+
+    struct UnimplementedService;
+
+    impl connection::ConnectionService for UnimplementedService {
+        type Error = RPCError;
+    }
+
+    impl Service<BNetPacket> for EchoService {
+        fn name(&self) -> &'static str {
+            ECHO_SERVICE_NAME
+        }
+
+        fn call(
+            &mut self,
+            method_id: u32,
+            request: Request<BNetPacket>,
+        ) -> ServiceFuture<BNetPacket> {
+            // This method never completes.
+            if method_id == 3 {
+                return Box::new(future::empty());
+            }
+            // This method always fails.
+            if method_id == 4 {
+                return Box::new(future::err(StatusCode::Denied.into()));
+            }
+            if method_id != 1 {
+                return Box::new(future::err(RPCError::InvalidRequest {
+                    service_name: ECHO_SERVICE_NAME,
+                    method_id,
+                }));
+            }
+
+            let body = request.as_ref().into_inner().body().clone();
+            Box::new(future::ok(Some(Response::from_request(request, body))))
+        }
+    }
+
+    fn build_request(service_id: u32, method_id: u32) -> Request<BNetPacket> {
+        let header = Header {
+            service_id,
+            method_id: Some(method_id),
+            token: 9,
+            size: Some(4),
+            timeout: Some(50),
+            ..Default::default()
+        };
+        Request::new(BNetPacket::new(header, Bytes::from(&b"ping"[..])))
+    }
+
+    fn build_router() -> ServiceRouter {
+        let mut bindings = ServiceBindings::new();
+        bindings.bind_exported(5, ::rpc::util::hash_service_name(ECHO_SERVICE_NAME));
+
+        let mut router = ServiceRouter::new(Arc::new(Mutex::new(bindings)));
+        router.set_server_id(ProcessId { label: 1, epoch: 2 });
+        router.register(EchoService);
+        router.register(ConnectionServiceDispatcher(UnimplementedService));
+        router
+    }
+
+    #[test]
+    fn routes_bound_service() {
+        let mut router = build_router();
+        let response = router
+            .dispatch(build_request(5, 1), &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(None, response.header().status);
+        assert_eq!(9, response.header().token);
+        assert_eq!(&b"ping"[..], &response.body()[..]);
+    }
+
+    #[test]
+    fn rejects_unknown_service() {
+        let mut router = build_router();
+        let response = router
+            .dispatch(build_request(6, 1), &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            Some(StatusCode::RpcInvalidService.code()),
+            response.header().status
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_service() {
+        let mut router = build_router();
+        {
+            let mut bindings = router.bindings.lock().unwrap();
+            bindings.bind_exported(7, UNSUPPORTED_HASH);
+            bindings.mark_unsupported(UNSUPPORTED_HASH);
+        }
+        let response = router
+            .dispatch(build_request(7, 1), &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        let header = response.header();
+        assert_eq!(Some(StatusCode::RpcNotImplemented.code()), header.status);
+        assert_eq!(UNSUPPORTED_HASH, header.error[0].service_hash);
+    }
+
+    #[test]
+    fn rejects_unknown_method() {
+        let mut router = build_router();
+        let response = router
+            .dispatch(build_request(5, 2), &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            Some(StatusCode::RpcInvalidMethod.code()),
+            response.header().status
+        );
+    }
+
+    #[test]
+    fn reports_handler_failure() {
+        let mut router = build_router();
+        let response = router
+            .dispatch(build_request(5, 4), &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        let header = response.header();
+        assert_eq!(Some(StatusCode::Denied.code()), header.status);
+        assert_eq!(1, header.error.len());
+
+        let error_info = &header.error[0];
+        assert_eq!(1, error_info.object_address.host.label);
+        assert_eq!(StatusCode::Denied.code(), error_info.status);
+        assert_eq!(
+            ::rpc::util::hash_service_name(ECHO_SERVICE_NAME),
+            error_info.service_hash
+        );
+        assert_eq!(4, error_info.method_id);
+    }
+
+    #[test]
+    fn cancels_expired_request() {
+        let mut router = build_router();
+        let response_future = router.dispatch(build_request(5, 3), &log::default_logger());
+        let mut runtime = ::tokio::runtime::current_thread::Runtime::new().unwrap();
+        let response = runtime
+            .block_on(response_future)
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            Some(StatusCode::RpcRequestTimedOut.code()),
+            response.header().status
+        );
+        assert_eq!(9, response.header().token);
+    }
+
+    #[test]
+    fn rejects_unimplemented_method() {
+        let mut router = build_router();
+        let header = Header {
+            service_id: ExportedServiceID::ConnectionService as u32,
+            method_id: Some(ConnectionServiceMethod::Echo.id()),
+            token: 3,
+            size: Some(0),
+            ..Default::default()
+        };
+        let request = Request::new(BNetPacket::new(header, Bytes::new()));
+        let response = router
+            .dispatch(request, &log::default_logger())
+            .wait()
+            .unwrap()
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            Some(StatusCode::RpcNotImplemented.code()),
+            response.header().status
+        );
+        assert_eq!(3, response.header().token);
+    }
+}
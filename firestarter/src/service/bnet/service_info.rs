@@ -79,6 +79,33 @@ pub enum ExportedServiceID {
     ResponseService = 254,
 }
 
+impl ExportedServiceID {
+    /// Retrieve the fully qualified name of the service.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportedServiceID::ConnectionService => "bnet.protocol.connection.ConnectionService",
+            ExportedServiceID::AccountService => "bnet.protocol.account.AccountService",
+            ExportedServiceID::AchievementsService => "bnet.protocol.achievements.AchievementsService",
+            ExportedServiceID::AuthenticationServer => "bnet.protocol.authentication.AuthenticationServer",
+            ExportedServiceID::ChallengeService => "bnet.protocol.challenge.ChallengeService",
+            ExportedServiceID::ChannelInvitationService => "bnet.protocol.channel_invitation.ChannelInvitationService",
+            ExportedServiceID::Channel => "bnet.protocol.channel.Channel",
+            ExportedServiceID::ChannelOwner => "bnet.protocol.channel.ChannelOwner",
+            ExportedServiceID::ExchangeService => "bnet.protocol.exchange.ExchangeService",
+            ExportedServiceID::FriendsService => "bnet.protocol.friends.FriendsService",
+            ExportedServiceID::GameMaster => "bnet.protocol.game_master.GameMaster",
+            ExportedServiceID::GameUtilities => "bnet.protocol.game_utilities.GameUtilities",
+            ExportedServiceID::NotificationService => "bnet.protocol.notification.NotificationService",
+            ExportedServiceID::PresenceService => "bnet.protocol.presence.PresenceService",
+            ExportedServiceID::ReportService => "bnet.protocol.report.ReportService",
+            ExportedServiceID::Resources => "bnet.protocol.resources.Resources",
+            ExportedServiceID::SearchService => "bnet.protocol.search.SearchService",
+            ExportedServiceID::UserManagerService => "bnet.protocol.user_manager.UserManagerService",
+            ExportedServiceID::ResponseService => "bnet.protocol.ResponseService",
+        }
+    }
+}
+
 #[repr(u32)]
 #[derive(Debug, Clone, Copy)]
 pub enum ImportedServiceID {
@@ -97,6 +124,26 @@ pub enum ImportedServiceID {
     ResponseService = 254,
 }
 
+impl ImportedServiceID {
+    /// Retrieve the fully qualified name of the service.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportedServiceID::AccountNotify => "bnet.protocol.account.AccountNotify",
+            ImportedServiceID::AchievementsNotify => "bnet.protocol.achievements.AchievementsNotify",
+            ImportedServiceID::AuthenticationClient => "bnet.protocol.authentication.AuthenticationClient",
+            ImportedServiceID::ChallengeNotify => "bnet.protocol.challenge.ChallengeNotify",
+            ImportedServiceID::ChannelInvitationNotify => "bnet.protocol.channel_invitation.ChannelInvitationNotify",
+            ImportedServiceID::ChannelSubscriber => "bnet.protocol.channel.ChannelSubscriber",
+            ImportedServiceID::ExchangeNotify => "bnet.protocol.exchange.ExchangeNotify",
+            ImportedServiceID::DiagService => "bnet.protocol.diag.DiagService",
+            ImportedServiceID::FriendsNotify => "bnet.protocol.friends.FriendsNotify",
+            ImportedServiceID::NotificationListener => "bnet.protocol.notification.NotificationListener",
+            ImportedServiceID::UserManagerNotify => "bnet.protocol.user_manager.UserManagerNotify",
+            ImportedServiceID::ResponseService => "bnet.protocol.ResponseService",
+        }
+    }
+}
+
 lazy_static! {
     pub static ref SERVICES_EXPORTED_BINDING: HashMap<u32, ExportedServiceID> = {
         hashmap!{
@@ -141,3 +188,19 @@ lazy_static! {
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rpc::util::hash_service_name;
+
+    #[test]
+    fn names_match_hashes() {
+        for (hash, id) in SERVICES_EXPORTED_BINDING.iter() {
+            assert_eq!(*hash, hash_service_name(id.name()), "{}", id.name());
+        }
+        for (hash, id) in SERVICES_IMPORTED_BINDING.iter() {
+            assert_eq!(*hash, hash_service_name(id.name()), "{}", id.name());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Handlers of the Pegasus util packets which the client sends during logon.
+//!
+//! The replies are built from the state of the player within the storage, see
+//! [`UtilContext::storage`]. [`login_handlers`] lists all handlers of this module by the
+//! packet ID they handle, the server registers these by default.
+
+use firestarter_generated::proto::pegasusshared::{CardDef, CardStack, Date};
+use firestarter_generated::proto::pegasusutil::get_account_info::Request;
+use firestarter_generated::proto::pegasusutil::{
+    ArcaneDustBalance, Collection, DeckInfo, DeckList, GetAccountInfo, GoldBalance,
+};
+use std::sync::Arc;
+
+use rpc::status::StatusCode;
+use server::storage::{CardStack as CardStackData, Deck, StorageError};
+use service::bnet::game_utilities_service::{UtilContext, UtilHandler, UtilHandlerMap, UtilPacket};
+
+/// Amount of gold a player can hold, the client warns when it's reached.
+pub const GOLD_CAP: i64 = i32::MAX as i64;
+
+/// Handlers of all util packets the client sends during logon, by packet ID.
+pub fn login_handlers() -> UtilHandlerMap {
+    let mut handlers = UtilHandlerMap::new();
+    handlers.insert(GetAccountInfo::PACKET_ID, Arc::new(AccountInfoHandler));
+    handlers
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Handler of `GetAccountInfo`, which answers the requests backed by the storage.
+///
+/// The deck list, collection, gold balance and arcane dust balance are supported. Other
+/// requests are answered with [`StatusCode::NotImplemented`].
+pub struct AccountInfoHandler;
+
+impl AccountInfoHandler {
+    fn loaded<T>(context: &UtilContext, result: Result<T, StorageError>) -> Result<T, StatusCode> {
+        result.map_err(|error| {
+            warn!(context.session().logger(), "Failed to load player state"; "error" => %error);
+            StatusCode::Internal
+        })
+    }
+}
+
+impl UtilHandler for AccountInfoHandler {
+    fn handle(
+        &self,
+        context: &UtilContext,
+        packet: &UtilPacket,
+    ) -> Result<Option<UtilPacket>, StatusCode> {
+        let request = packet
+            .decode::<GetAccountInfo>()?
+            .request
+            .and_then(Request::from_i32)
+            .ok_or(StatusCode::InvalidArgs)?;
+        let storage = context.storage();
+        let account = context.identity().account().low;
+
+        let reply = match request {
+            Request::DeckList => {
+                let decks = Self::loaded(context, storage.load_decks(account))?;
+                let message = DeckList {
+                    decks: decks.iter().map(deck_info).collect(),
+                };
+                UtilPacket::from_message(DeckList::PACKET_ID, &message)
+            }
+            Request::Collection => {
+                let collection = Self::loaded(context, storage.load_collection(account))?;
+                let message = Collection {
+                    stacks: collection.iter().map(card_stack).collect(),
+                };
+                UtilPacket::from_message(Collection::PACKET_ID, &message)
+            }
+            Request::GoldBalance => {
+                let currencies = Self::loaded(context, storage.load_currencies(account))?;
+                let message = GoldBalance {
+                    capped_balance: Some(currencies.gold()),
+                    bonus_balance: Some(0),
+                    cap: Some(GOLD_CAP),
+                    cap_warning: Some(GOLD_CAP),
+                };
+                UtilPacket::from_message(GoldBalance::PACKET_ID, &message)
+            }
+            Request::ArcaneDustBalance => {
+                let currencies = Self::loaded(context, storage.load_currencies(account))?;
+                let message = ArcaneDustBalance {
+                    balance: Some(currencies.dust()),
+                };
+                UtilPacket::from_message(ArcaneDustBalance::PACKET_ID, &message)
+            }
+            other => {
+                debug!(context.session().logger(), "Unsupported account info"; "request" => ?other);
+                return Err(StatusCode::NotImplemented);
+            }
+        };
+        Ok(Some(reply))
+    }
+}
+
+fn deck_info(deck: &Deck) -> DeckInfo {
+    DeckInfo {
+        id: Some(deck.id()),
+        name: Some(deck.name().to_string()),
+        card_back: Some(deck.card_back()),
+        hero: Some(deck.hero()),
+        deck_type: Some(deck.deck_type()),
+        validity: Some(0),
+        hero_premium: Some(deck.hero_premium()),
+        card_back_override: Some(false),
+    }
+}
+
+fn card_stack(stack: &CardStackData) -> CardStack {
+    let count = stack.count() as i32;
+    // The storage doesn't track when cards were added, so all cards are marked as seen.
+    CardStack {
+        card_def: Some(CardDef {
+            asset: Some(stack.asset()),
+            premium: Some(stack.premium()),
+        }),
+        latest_insert_date: Some(Date {
+            year: Some(1970),
+            month: Some(1),
+            day: Some(1),
+            hours: Some(0),
+            min: Some(0),
+            sec: Some(0),
+        }),
+        count: Some(count),
+        num_seen: Some(count),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use firestarter_generated::proto::bnet::protocol::ProcessId;
+    use prost::Message;
+    use protocol::bnet::session::{Identity, SessionHandle};
+    use server::accounts::{Account, Credential};
+    use server::storage::memory::MemoryStorage;
+    use server::storage::{Currencies, SharedStorage};
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+
+    fn account_info(context: &UtilContext, request: Request) -> Result<UtilPacket, StatusCode> {
+        let message = GetAccountInfo {
+            request: Some(request as i32),
+        };
+        let packet = UtilPacket::from_message(GetAccountInfo::PACKET_ID, &message);
+        let handler = &login_handlers()[&GetAccountInfo::PACKET_ID];
+        handler
+            .handle(context, &packet)
+            .map(|reply| reply.expect("Account info is always answered"))
+    }
+
+    #[test]
+    fn answers_account_info_from_storage() {
+        let session = SessionHandle::detached(
+            SocketAddr::from(([127, 0, 0, 1], 1000)),
+            ProcessId { label: 1, epoch: 1 },
+        );
+        let account = Account::new(7, "alice@example.com".into(), Credential::Token(vec![1]));
+        let identity = Identity::new(account.entity_id(), None);
+        let storage: SharedStorage = Arc::new(MemoryStorage::new());
+        storage
+            .save_collection(7, &[CardStackData::new(178, 0, 2)])
+            .unwrap();
+        storage
+            .save_currencies(7, &Currencies::new(100, 40))
+            .unwrap();
+        let context = UtilContext::new(&session, &identity, &storage);
+
+        let reply = account_info(&context, Request::Collection).unwrap();
+        assert_eq!(Collection::PACKET_ID, reply.id());
+        let collection = Collection::decode(Cursor::new(reply.body())).unwrap();
+        assert_eq!(
+            Some(178),
+            collection.stacks[0].card_def.as_ref().unwrap().asset
+        );
+        assert_eq!(Some(2), collection.stacks[0].count);
+
+        let reply = account_info(&context, Request::GoldBalance).unwrap();
+        let gold = GoldBalance::decode(Cursor::new(reply.body())).unwrap();
+        assert_eq!(Some(100), gold.capped_balance);
+
+        let reply = account_info(&context, Request::DeckList).unwrap();
+        let decks = DeckList::decode(Cursor::new(reply.body())).unwrap();
+        assert!(decks.decks.is_empty());
+
+        assert_eq!(
+            Err(StatusCode::NotImplemented),
+            account_info(&context, Request::Motd)
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Generator for the packet registries of the Pegasus proto packages.
+//!
+//! Each message of these packages which is sent on its own carries a nested
+//! `enum PacketID { ID = N }`. For each such message the following items are appended to
+//! the compiled package:
+//!
+//! - `PACKET_ID` and `packet_id()` on the message itself;
+//! - one variant within the `{Package}Packet` enum, which decodes messages by packet ID
+//!   and maps packet IDs to message names.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Compiled packages which carry packet IDs, together with the name of their packet enum.
+pub const PACKET_PACKAGES: &[(&str, &str)] = &[
+    ("bobnetproto", "BobNetProtoPacket"),
+    ("pegasusgame", "PegasusGamePacket"),
+    ("pegasusutil", "PegasusUtilPacket"),
+];
+
+/// Message carrying a packet ID.
+struct Packet {
+    message: String,
+    id: i32,
+}
+
+/// Append the packet registry to each compiled package within the provided directory.
+pub fn append_packet_registries(proto_out_dir: &Path) {
+    for &(package, packet_enum) in PACKET_PACKAGES {
+        let path = proto_out_dir.join(format!("{}.rs", package));
+        let mut code = fs::read_to_string(&path).expect("Failed reading compiled proto-file");
+        let mut packets = find_packets(&code);
+        // Packets are found by matching the formatting of prost, which could change.
+        assert!(
+            !packets.is_empty(),
+            "No packet IDs found within package {}",
+            package
+        );
+        packets.sort_by_key(|packet| packet.id);
+        for pair in packets.windows(2) {
+            assert!(
+                pair[0].id != pair[1].id,
+                "Messages {} and {} share packet ID {}",
+                pair[0].message,
+                pair[1].message,
+                pair[0].id
+            );
+        }
+
+        write_packet_ids(&mut code, &packets);
+        write_packet_enum(&mut code, package, packet_enum, &packets);
+        fs::write(&path, code).expect("Failed writing compiled proto-file");
+    }
+}
+
+/// Find all top level messages with a packet ID within the compiled package.
+///
+/// Prost emits the nested types of a message within a module right after the message.
+fn find_packets(code: &str) -> Vec<Packet> {
+    let mut packets = vec![];
+    let mut message = None;
+    let mut in_packet_enum = false;
+    for line in code.lines() {
+        if line.starts_with("pub struct ") {
+            message = line
+                .trim_start_matches("pub struct ")
+                .split_whitespace()
+                .next()
+                .map(String::from);
+        } else if line == "    pub enum PacketId {" {
+            in_packet_enum = true;
+        } else if in_packet_enum && line.trim() == "}" {
+            in_packet_enum = false;
+        } else if in_packet_enum && line.trim().starts_with("Id = ") {
+            let id = line
+                .trim()
+                .trim_start_matches("Id = ")
+                .trim_end_matches(',')
+                .parse()
+                .expect("Packet ID is not an integer");
+            let message = message
+                .clone()
+                .expect("Packet ID enum outside of a message");
+            packets.push(Packet { message, id });
+        }
+    }
+    packets
+}
+
+fn write_packet_ids(buf: &mut String, packets: &[Packet]) {
+    for packet in packets {
+        writeln!(buf, "impl {} {{", packet.message).unwrap();
+        writeln!(buf, "    /// Packet ID of this message.").unwrap();
+        writeln!(buf, "    pub const PACKET_ID: i32 = {};", packet.id).unwrap();
+        writeln!(buf, "    /// Retrieve the packet ID of this message.").unwrap();
+        writeln!(buf, "    pub fn packet_id(&self) -> i32 {{").unwrap();
+        writeln!(buf, "        Self::PACKET_ID").unwrap();
+        writeln!(buf, "    }}").unwrap();
+        writeln!(buf, "}}").unwrap();
+    }
+}
+
+// Paths towards the prelude are fully qualified in the generated code, because some
+// packages define messages named `Option` or `Result`.
+fn write_packet_enum(buf: &mut String, package: &str, packet_enum: &str, packets: &[Packet]) {
+    writeln!(
+        buf,
+        "/// Messages of the `{}` package which carry a packet ID.",
+        package
+    )
+    .unwrap();
+    writeln!(buf, "#[derive(Clone, Debug, PartialEq)]").unwrap();
+    writeln!(buf, "pub enum {} {{", packet_enum).unwrap();
+    for packet in packets {
+        writeln!(buf, "    /// Packet ID {}.", packet.id).unwrap();
+        writeln!(buf, "    {0}({0}),", packet.message).unwrap();
+    }
+    writeln!(buf, "}}").unwrap();
+
+    writeln!(buf, "impl {} {{", packet_enum).unwrap();
+    writeln!(
+        buf,
+        "    /// Packet ID and message name of each packet, in order of packet ID."
+    )
+    .unwrap();
+    writeln!(
+        buf,
+        "    pub const PACKETS: &'static [(i32, &'static str)] = &["
+    )
+    .unwrap();
+    for packet in packets {
+        writeln!(buf, "        ({}, \"{}\"),", packet.id, packet.message).unwrap();
+    }
+    writeln!(buf, "    ];").unwrap();
+
+    writeln!(
+        buf,
+        "    /// Retrieve the name of the message with the provided packet ID."
+    )
+    .unwrap();
+    writeln!(
+        buf,
+        "    pub fn message_name(id: i32) -> ::std::option::Option<&'static str> {{"
+    )
+    .unwrap();
+    writeln!(buf, "        Self::PACKETS").unwrap();
+    writeln!(buf, "            .binary_search_by_key(&id, |&(id, _)| id)").unwrap();
+    writeln!(buf, "            .ok()").unwrap();
+    writeln!(buf, "            .map(|index| Self::PACKETS[index].1)").unwrap();
+    writeln!(buf, "    }}").unwrap();
+
+    writeln!(
+        buf,
+        "    /// Decode the message with the provided packet ID."
+    )
+    .unwrap();
+    writeln!(buf, "    ///").unwrap();
+    writeln!(
+        buf,
+        "    /// `None` is returned if no message has the provided packet ID."
+    )
+    .unwrap();
+    writeln!(
+        buf,
+        "    pub fn decode<B: ::bytes::IntoBuf>(id: i32, buf: B) -> ::std::result::Result<::std::option::Option<Self>, ::prost::DecodeError> {{"
+    )
+    .unwrap();
+    writeln!(buf, "        let packet = match id {{").unwrap();
+    for packet in packets {
+        writeln!(
+            buf,
+            "            {} => {}::{}(::prost::Message::decode(buf)?),",
+            packet.id, packet_enum, packet.message
+        )
+        .unwrap();
+    }
+    writeln!(
+        buf,
+        "            _ => return ::std::result::Result::Ok(::std::option::Option::None),"
+    )
+    .unwrap();
+    writeln!(buf, "        }};").unwrap();
+    writeln!(
+        buf,
+        "        ::std::result::Result::Ok(::std::option::Option::Some(packet))"
+    )
+    .unwrap();
+    writeln!(buf, "    }}").unwrap();
+
+    writeln!(buf, "    /// Encode the message, without its packet ID.").unwrap();
+    writeln!(
+        buf,
+        "    pub fn encode<B: ::bytes::BufMut>(&self, buf: &mut B) -> ::std::result::Result<(), ::prost::EncodeError> {{"
+    )
+    .unwrap();
+    writeln!(buf, "        match *self {{").unwrap();
+    for packet in packets {
+        writeln!(
+            buf,
+            "            {}::{}(ref message) => ::prost::Message::encode(message, buf),",
+            packet_enum, packet.message
+        )
+        .unwrap();
+    }
+    writeln!(buf, "        }}").unwrap();
+    writeln!(buf, "    }}").unwrap();
+
+    writeln!(buf, "    /// Retrieve the packet ID of the message.").unwrap();
+    writeln!(buf, "    pub fn packet_id(&self) -> i32 {{").unwrap();
+    writeln!(buf, "        match *self {{").unwrap();
+    for packet in packets {
+        writeln!(
+            buf,
+            "            {}::{}(_) => {},",
+            packet_enum, packet.message, packet.id
+        )
+        .unwrap();
+    }
+    writeln!(buf, "        }}").unwrap();
+    writeln!(buf, "    }}").unwrap();
+
+    writeln!(buf, "    /// Retrieve the name of the message.").unwrap();
+    writeln!(buf, "    pub fn name(&self) -> &'static str {{").unwrap();
+    writeln!(buf, "        match *self {{").unwrap();
+    for packet in packets {
+        writeln!(
+            buf,
+            "            {}::{}(_) => \"{}\",",
+            packet_enum, packet.message, packet.message
+        )
+        .unwrap();
+    }
+    writeln!(buf, "        }}").unwrap();
+    writeln!(buf, "    }}").unwrap();
+    writeln!(buf, "}}").unwrap();
+}
@@ -1,12 +1,18 @@
 extern crate glob;
 extern crate prost_build;
 
+mod packet_generator;
+mod service_generator;
+
 use glob::{glob, Paths};
 use std::env;
 use std::fs::{DirBuilder, File};
 use std::io::{copy, BufReader, BufWriter, Write};
 use std::path::PathBuf;
 
+use packet_generator::append_packet_registries;
+use service_generator::BNetServiceGenerator;
+
 const ENV_CRATE_DIR: &'static str = "CARGO_MANIFEST_DIR";
 const ENV_OUT_DIR: &'static str = "OUT_DIR";
 
@@ -59,9 +65,12 @@ fn build_protos() -> Paths {
     let proto_source_dir_str = proto_source_dir
         .to_str()
         .expect("Directory name contains invalid utf8");
-    prost_build::compile_protos(&proto_path_strings[..], &[proto_source_dir_str])
+    prost_build::Config::new()
+        .service_generator(Box::new(BNetServiceGenerator))
+        .compile_protos(&proto_path_strings[..], &[proto_source_dir_str])
         .expect("Failed compiling proto schemas");
     env::set_var(ENV_OUT_DIR, &build_dir);
+    append_packet_registries(proto_out_dir);
     // Only run this script again if the source directory with the proto schemas was updated
     println!("cargo:rerun-if-changed={}", proto_out_dir.display());
 
@@ -115,7 +124,7 @@ fn concat_protos(files: Paths) {
                 (Some(mod_part), None) => {
                     // Open new module
                     file_writer
-                        .write(&module_block_open(*mod_part))
+                        .write_all(&module_block_open(*mod_part))
                         .expect("IO Write");
                     // We're holding an immutable borrow of 'current_module'.
                     // The immutable borrow blocks us from getting a mutable borrow.
@@ -171,7 +180,7 @@ fn concat_protos(files: Paths) {
             if should_close_modules == true {
                 let closed_modules = current_module.split_off(current_module_depth);
                 for _ in closed_modules {
-                    file_writer.write(module_block_end).expect("IO Write");
+                    file_writer.write_all(module_block_end).expect("IO Write");
                 }
             }
         }
@@ -180,6 +189,6 @@ fn concat_protos(files: Paths) {
     // The algorithm ends with some modules open, which we have to close now.
     let closed_modules = current_module;
     for _ in closed_modules {
-        file_writer.write(module_block_end).expect("IO Write");
+        file_writer.write_all(module_block_end).expect("IO Write");
     }
 }
@@ -0,0 +1,478 @@
+//! Generator for the Rust code of proto service definitions.
+//!
+//! See the `rpc` module of this crate for a description of the generated items.
+
+use prost_build::{Method, Service, ServiceGenerator};
+use std::fmt::Write;
+
+const FNV1A_INIT: u32 = 0x811c9dc5;
+const FNV1A_PRIME: u32 = 0x01000193;
+
+/// Proto type which indicates that a method doesn't respond.
+const NO_RESPONSE_TYPE: &str = ".bnet.protocol.NO_RESPONSE";
+
+/// Method IDs of services which are not numbered by ordinal position.
+///
+/// The extracted schemas lost the `method_id` option of `method_options.proto`, so the
+/// IDs of these services are restored from the original definitions.
+const METHOD_IDS: &[(&str, &[(&str, u32)])] = &[
+    (
+        "bnet.protocol.authentication.AuthenticationClient",
+        &[
+            ("ModuleLoad", 1),
+            ("ModuleMessage", 2),
+            ("AccountSettings", 3),
+            ("ServerStateChange", 4),
+            ("LogonComplete", 5),
+            ("MemModuleLoad", 6),
+            ("LogonUpdate", 10),
+            ("VersionInfoUpdated", 11),
+            ("LogonQueueUpdate", 12),
+            ("LogonQueueEnd", 13),
+            ("GameAccountSelected", 14),
+        ],
+    ),
+    (
+        "bnet.protocol.authentication.AuthenticationServer",
+        &[
+            ("Logon", 1),
+            ("ModuleNotify", 2),
+            ("ModuleMessage", 3),
+            ("SelectGameAccount_DEPRECATED", 4),
+            ("GenerateSSOToken", 5),
+            ("SelectGameAccount", 6),
+            ("VerifyWebCredentials", 7),
+        ],
+    ),
+    (
+        "bnet.protocol.account.AccountService",
+        &[
+            ("GetGameAccount", 12),
+            ("GetAccount", 13),
+            ("CreateGameAccount", 14),
+            ("IsIgrAddress", 15),
+            ("CacheExpire", 20),
+            ("CredentialUpdate", 21),
+            ("FlagUpdate", 22),
+            ("GetWalletList", 23),
+            ("GetEBalance", 24),
+            ("Subscribe", 25),
+            ("Unsubscribe", 26),
+            ("GetEBalanceRestrictions", 27),
+            ("GetAccountState", 30),
+            ("GetGameAccountState", 31),
+            ("GetLicenses", 32),
+            ("GetGameTimeRemainingInfo", 33),
+            ("GetGameSessionInfo", 34),
+            ("GetCAISInfo", 35),
+            ("ForwardCacheExpire", 36),
+        ],
+    ),
+];
+
+/// Hashes the provided string with FNV-1a (32-bit variant).
+///
+/// This is how BNet services are identified on the wire.
+fn hash_service_name(name: &str) -> u32 {
+    let mut hash = FNV1A_INIT;
+    for byte in name.bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(FNV1A_PRIME);
+    }
+    hash
+}
+
+/// Converts a snake_case identifier into an UpperCamelCase identifier.
+fn to_upper_camel(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let mut word = String::new();
+            word.push(first);
+            word.extend(chars);
+            word
+        })
+        .collect()
+}
+
+fn expects_response(method: &Method) -> bool {
+    method.output_proto_type != NO_RESPONSE_TYPE
+}
+
+fn trim_proto_type(proto_type: &str) -> &str {
+    proto_type.trim_start_matches('.')
+}
+
+#[derive(Debug, Default)]
+/// Service generator which plugs into the prost builder.
+pub struct BNetServiceGenerator;
+
+impl ServiceGenerator for BNetServiceGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        let full_name = format!("{}.{}", service.package, service.proto_name);
+        let method_ids = method_ids(&full_name, &service);
+        let context = ServiceContext {
+            hash: hash_service_name(&full_name),
+            full_name,
+            service: &service,
+            method_ids,
+        };
+
+        context.write_method_enum(buf);
+        context.write_trait(buf);
+        context.write_dispatcher(buf);
+        context.write_stub(buf);
+    }
+}
+
+/// Resolves the ID of each method of the service, in order of definition.
+///
+/// Services without entry in [`METHOD_IDS`] are numbered by ordinal position, starting from 1.
+fn method_ids(full_name: &str, service: &Service) -> Vec<u32> {
+    let known_ids = METHOD_IDS
+        .iter()
+        .find(|&&(name, _)| name == full_name)
+        .map(|&(_, ids)| ids);
+    service
+        .methods
+        .iter()
+        .enumerate()
+        .map(|(idx, method)| match known_ids {
+            Some(ids) => ids
+                .iter()
+                .find(|&&(name, _)| name == method.proto_name)
+                .map(|&(_, id)| id)
+                .unwrap_or_else(|| {
+                    panic!("Missing method ID of {}.{}", full_name, method.proto_name)
+                }),
+            None => idx as u32 + 1,
+        })
+        .collect()
+}
+
+struct ServiceContext<'a> {
+    full_name: String,
+    hash: u32,
+    service: &'a Service,
+    method_ids: Vec<u32>,
+}
+
+impl<'a> ServiceContext<'a> {
+    fn method_enum(&self) -> String {
+        format!("{}Method", self.service.name)
+    }
+
+    fn write_method_enum(&self, buf: &mut String) {
+        let name = &self.service.name;
+        let method_enum = self.method_enum();
+        let methods = &self.service.methods;
+
+        writeln!(buf, "/// Methods of the `{}` service.", self.full_name).unwrap();
+        writeln!(buf, "///").unwrap();
+        writeln!(
+            buf,
+            "/// The discriminant is the method ID, which is the ordinal position of the method"
+        )
+        .unwrap();
+        writeln!(
+            buf,
+            "/// within the service definition (starting from 1), unless the service is known"
+        )
+        .unwrap();
+        writeln!(buf, "/// to number its methods differently.").unwrap();
+        writeln!(buf, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]").unwrap();
+        writeln!(buf, "pub enum {} {{", method_enum).unwrap();
+        for (method, id) in methods.iter().zip(&self.method_ids) {
+            writeln!(
+                buf,
+                "    /// `{}({}) returns ({})`",
+                method.proto_name,
+                trim_proto_type(&method.input_proto_type),
+                trim_proto_type(&method.output_proto_type)
+            )
+            .unwrap();
+            writeln!(buf, "    {} = {},", to_upper_camel(&method.name), id).unwrap();
+        }
+        writeln!(buf, "}}").unwrap();
+
+        writeln!(buf, "impl {} {{", method_enum).unwrap();
+        writeln!(buf, "    /// Fully qualified name of the {} service.", name).unwrap();
+        writeln!(
+            buf,
+            "    pub const SERVICE_NAME: &'static str = \"{}\";",
+            self.full_name
+        )
+        .unwrap();
+        writeln!(buf, "    /// Hash of the fully qualified service name.").unwrap();
+        writeln!(buf, "    pub const SERVICE_HASH: u32 = {};", self.hash).unwrap();
+        writeln!(buf, "    /// All methods, in order of their ID.").unwrap();
+        writeln!(buf, "    pub const ALL: &'static [{}] = &[", method_enum).unwrap();
+        for method in methods {
+            writeln!(
+                buf,
+                "        {}::{},",
+                method_enum,
+                to_upper_camel(&method.name)
+            )
+            .unwrap();
+        }
+        writeln!(buf, "    ];").unwrap();
+
+        writeln!(buf, "    /// Retrieve the method with the provided ID.").unwrap();
+        writeln!(buf, "    pub fn from_id(id: u32) -> Option<Self> {{").unwrap();
+        writeln!(buf, "        match id {{").unwrap();
+        for (method, id) in methods.iter().zip(&self.method_ids) {
+            writeln!(
+                buf,
+                "            {} => Some({}::{}),",
+                id,
+                method_enum,
+                to_upper_camel(&method.name)
+            )
+            .unwrap();
+        }
+        writeln!(buf, "            _ => None,").unwrap();
+        writeln!(buf, "        }}").unwrap();
+        writeln!(buf, "    }}").unwrap();
+
+        writeln!(buf, "    /// The ID of this method.").unwrap();
+        writeln!(buf, "    pub fn id(self) -> u32 {{").unwrap();
+        writeln!(buf, "        self as u32").unwrap();
+        writeln!(buf, "    }}").unwrap();
+
+        self.write_method_property(
+            buf,
+            "name",
+            "&'static str",
+            "The name of this method.",
+            |m| format!("\"{}\"", m.proto_name),
+        );
+        self.write_method_property(
+            buf,
+            "input_type",
+            "&'static str",
+            "The fully qualified name of the request message.",
+            |m| format!("\"{}\"", trim_proto_type(&m.input_proto_type)),
+        );
+        self.write_method_property(
+            buf,
+            "output_type",
+            "&'static str",
+            "The fully qualified name of the response message.",
+            |m| format!("\"{}\"", trim_proto_type(&m.output_proto_type)),
+        );
+        self.write_method_property(
+            buf,
+            "expects_response",
+            "bool",
+            "Returns false if the receiver must not respond to this method.",
+            |m| expects_response(m).to_string(),
+        );
+        writeln!(buf, "}}").unwrap();
+    }
+
+    fn write_method_property<F>(
+        &self,
+        buf: &mut String,
+        fn_name: &str,
+        return_type: &str,
+        doc: &str,
+        value: F,
+    ) where
+        F: Fn(&Method) -> String,
+    {
+        let method_enum = self.method_enum();
+        writeln!(buf, "    /// {}", doc).unwrap();
+        writeln!(buf, "    pub fn {}(self) -> {} {{", fn_name, return_type).unwrap();
+        writeln!(buf, "        match self {{").unwrap();
+        for method in &self.service.methods {
+            writeln!(
+                buf,
+                "            {}::{} => {},",
+                method_enum,
+                to_upper_camel(&method.name),
+                value(method)
+            )
+            .unwrap();
+        }
+        writeln!(buf, "        }}").unwrap();
+        writeln!(buf, "    }}").unwrap();
+    }
+
+    fn write_trait(&self, buf: &mut String) {
+        let name = &self.service.name;
+        let method_enum = self.method_enum();
+
+        self.service.comments.append_with_indent(0, buf);
+        writeln!(buf, "/// Server side of the `{}` service.", self.full_name).unwrap();
+        writeln!(buf, "///").unwrap();
+        writeln!(
+            buf,
+            "/// Methods without implementation fail with an `UnimplementedMethod` error."
+        )
+        .unwrap();
+        writeln!(buf, "pub trait {} {{", name).unwrap();
+        writeln!(buf, "    /// Error type returned by all methods.").unwrap();
+        writeln!(
+            buf,
+            "    type Error: From<::prost::DecodeError> + From<::prost::EncodeError> \
+             + From<::rpc::UnimplementedMethod> + Send + 'static;"
+        )
+        .unwrap();
+
+        for (method, id) in self.service.methods.iter().zip(&self.method_ids) {
+            writeln!(buf).unwrap();
+            method.comments.append_with_indent(1, buf);
+            writeln!(
+                buf,
+                "    /// See [`{}::{}`].",
+                method_enum,
+                to_upper_camel(&method.name)
+            )
+            .unwrap();
+            writeln!(
+                buf,
+                "    fn {}(&mut self, _request: {}) -> ::rpc::RpcFuture<{}, Self::Error> {{",
+                method.name,
+                method.input_type,
+                self.output_type(method)
+            )
+            .unwrap();
+            writeln!(
+                buf,
+                "        ::rpc::unimplemented({}::SERVICE_NAME, {})",
+                method_enum,
+                id
+            )
+            .unwrap();
+            writeln!(buf, "    }}").unwrap();
+        }
+        writeln!(buf, "}}").unwrap();
+    }
+
+    fn write_dispatcher(&self, buf: &mut String) {
+        let name = &self.service.name;
+        let method_enum = self.method_enum();
+
+        writeln!(
+            buf,
+            "/// Dispatches encoded requests to an implementation of [`{}`].",
+            name
+        )
+        .unwrap();
+        writeln!(buf, "#[derive(Debug)]").unwrap();
+        writeln!(buf, "pub struct {}Dispatcher<T>(pub T);", name).unwrap();
+        writeln!(
+            buf,
+            "impl<T: {}> ::rpc::ServiceDispatch for {}Dispatcher<T> {{",
+            name, name
+        )
+        .unwrap();
+        writeln!(buf, "    type Error = T::Error;").unwrap();
+        writeln!(buf, "    fn service_name(&self) -> &'static str {{").unwrap();
+        writeln!(buf, "        {}::SERVICE_NAME", method_enum).unwrap();
+        writeln!(buf, "    }}").unwrap();
+        writeln!(buf, "    fn service_hash(&self) -> u32 {{").unwrap();
+        writeln!(buf, "        {}::SERVICE_HASH", method_enum).unwrap();
+        writeln!(buf, "    }}").unwrap();
+        writeln!(
+            buf,
+            "    fn dispatch(&mut self, method_id: u32, body: ::bytes::Bytes) \
+             -> Option<::rpc::RpcFuture<Option<::bytes::Bytes>, T::Error>> {{"
+        )
+        .unwrap();
+        writeln!(buf, "        let service = &mut self.0;").unwrap();
+        writeln!(
+            buf,
+            "        let future = match {}::from_id(method_id)? {{",
+            method_enum
+        )
+        .unwrap();
+        for method in &self.service.methods {
+            let helper = if expects_response(method) {
+                "respond"
+            } else {
+                "acknowledge"
+            };
+            writeln!(
+                buf,
+                "            {}::{} => ::rpc::{}(body, |r| service.{}(r)),",
+                method_enum,
+                to_upper_camel(&method.name),
+                helper,
+                method.name
+            )
+            .unwrap();
+        }
+        writeln!(buf, "        }};").unwrap();
+        writeln!(buf, "        Some(future)").unwrap();
+        writeln!(buf, "    }}").unwrap();
+        writeln!(buf, "}}").unwrap();
+    }
+
+    fn write_stub(&self, buf: &mut String) {
+        let name = &self.service.name;
+        let method_enum = self.method_enum();
+
+        writeln!(
+            buf,
+            "/// Invokes methods of the `{}` service over an RPC channel.",
+            self.full_name
+        )
+        .unwrap();
+        writeln!(buf, "#[derive(Debug, Clone)]").unwrap();
+        writeln!(buf, "pub struct {}Stub<C> {{", name).unwrap();
+        writeln!(buf, "    channel: C,").unwrap();
+        writeln!(buf, "}}").unwrap();
+        writeln!(buf, "impl<C: ::rpc::RpcChannel> {}Stub<C> {{", name).unwrap();
+        writeln!(
+            buf,
+            "    /// Creates a new stub sending requests over the channel."
+        )
+        .unwrap();
+        writeln!(buf, "    pub fn new(channel: C) -> Self {{").unwrap();
+        writeln!(buf, "        Self {{ channel }}").unwrap();
+        writeln!(buf, "    }}").unwrap();
+        writeln!(buf, "    /// Extract the channel from this stub.").unwrap();
+        writeln!(buf, "    pub fn into_inner(self) -> C {{").unwrap();
+        writeln!(buf, "        self.channel").unwrap();
+        writeln!(buf, "    }}").unwrap();
+
+        for method in &self.service.methods {
+            let variant = to_upper_camel(&method.name);
+            let helper = if expects_response(method) {
+                "request"
+            } else {
+                "notify"
+            };
+            writeln!(buf, "    /// See [`{}::{}`].", method_enum, variant).unwrap();
+            writeln!(
+                buf,
+                "    pub fn {}(&mut self, request: &{}) -> ::rpc::RpcFuture<{}, C::Error> {{",
+                method.name,
+                method.input_type,
+                self.output_type(method)
+            )
+            .unwrap();
+            writeln!(
+                buf,
+                "        ::rpc::{}(&mut self.channel, {}::SERVICE_HASH, {}::{} as u32, request)",
+                helper, method_enum, method_enum, variant
+            )
+            .unwrap();
+            writeln!(buf, "    }}").unwrap();
+        }
+        writeln!(buf, "}}").unwrap();
+    }
+
+    fn output_type(&self, method: &Method) -> String {
+        if expects_response(method) {
+            method.output_type.clone()
+        } else {
+            "()".into()
+        }
+    }
+}
@@ -1,12 +1,106 @@
 //! Crate containing build-time resources.
 
+extern crate bytes;
+extern crate futures;
 extern crate prost;
 
 #[macro_use]
 extern crate prost_derive;
 
+pub mod rpc;
+
 #[allow(dead_code)]
 pub mod proto {
     // Provisioning is done by the build script.
     include!(concat!(env!("OUT_DIR"), "/concat_compiled_proto.rs"));
 }
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+    use proto::bnet::protocol::account::AccountServiceMethod;
+    use proto::bnet::protocol::authentication::{
+        AuthenticationClientMethod, AuthenticationServerMethod,
+    };
+    use proto::bnet::protocol::connection::ConnectionServiceMethod;
+    use proto::bobnetproto::{AuroraHandshake, BobNetProtoPacket};
+    use proto::pegasusgame::{ChooseEntities, PegasusGamePacket};
+    use proto::pegasusutil::{get_account_info, GetAccountInfo, PegasusUtilPacket};
+
+    fn encode<M: Message>(message: &M) -> Vec<u8> {
+        let mut buf = vec![];
+        message.encode(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn pins_known_method_ids() {
+        assert_eq!(1, AuthenticationClientMethod::ModuleLoad.id());
+        assert_eq!(6, AuthenticationClientMethod::MemModuleLoad.id());
+        assert_eq!(10, AuthenticationClientMethod::LogonUpdate.id());
+        assert_eq!(13, AuthenticationClientMethod::LogonQueueEnd.id());
+        assert_eq!(14, AuthenticationClientMethod::GameAccountSelected.id());
+        assert_eq!(1, AuthenticationServerMethod::Logon.id());
+        assert_eq!(5, AuthenticationServerMethod::GenerateSsoToken.id());
+        assert_eq!(7, AuthenticationServerMethod::VerifyWebCredentials.id());
+        assert_eq!(12, AccountServiceMethod::GetGameAccount.id());
+        assert_eq!(20, AccountServiceMethod::CacheExpire.id());
+        assert_eq!(30, AccountServiceMethod::GetAccountState.id());
+        assert_eq!(36, AccountServiceMethod::ForwardCacheExpire.id());
+        assert_eq!(3, ConnectionServiceMethod::Echo.id());
+        assert_eq!(
+            Some(AuthenticationClientMethod::LogonQueueEnd),
+            AuthenticationClientMethod::from_id(13)
+        );
+        assert_eq!(None, AuthenticationClientMethod::from_id(7));
+    }
+
+    #[test]
+    fn registers_bobnetproto_packets() {
+        let message = AuroraHandshake {
+            game_handle: Some(5),
+            ..Default::default()
+        };
+        let buf = encode(&message);
+
+        assert_eq!(168, message.packet_id());
+        assert_eq!(
+            Some("AuroraHandshake"),
+            BobNetProtoPacket::message_name(168)
+        );
+        let packet = BobNetProtoPacket::decode(168, &buf[..]).unwrap().unwrap();
+        assert_eq!(168, packet.packet_id());
+        assert_eq!(BobNetProtoPacket::AuroraHandshake(message), packet);
+        assert_eq!(None, BobNetProtoPacket::decode(0, &buf[..]).unwrap());
+    }
+
+    #[test]
+    fn registers_pegasusgame_packets() {
+        let message = ChooseEntities {
+            id: Some(1),
+            entities: vec![4, 8],
+        };
+        let buf = encode(&message);
+
+        assert_eq!(3, message.packet_id());
+        assert_eq!(Some("ChooseEntities"), PegasusGamePacket::message_name(3));
+        let packet = PegasusGamePacket::decode(3, &buf[..]).unwrap().unwrap();
+        assert_eq!(3, packet.packet_id());
+        assert_eq!(PegasusGamePacket::ChooseEntities(message), packet);
+        assert_eq!(None, PegasusGamePacket::message_name(0));
+    }
+
+    #[test]
+    fn registers_pegasusutil_packets() {
+        let message = GetAccountInfo {
+            request: Some(get_account_info::Request::Collection as i32),
+        };
+        let buf = encode(&message);
+
+        assert_eq!(201, message.packet_id());
+        assert_eq!(Some("GetAccountInfo"), PegasusUtilPacket::message_name(201));
+        let packet = PegasusUtilPacket::decode(201, &buf[..]).unwrap().unwrap();
+        assert_eq!(201, packet.packet_id());
+        assert_eq!(PegasusUtilPacket::GetAccountInfo(message), packet);
+    }
+}
@@ -0,0 +1,184 @@
+//! Support items for the generated service code.
+//!
+//! Each service defined within the proto schemas is compiled into the following items,
+//! placed next to the messages of its package:
+//!
+//! - `{Service}Method`, an enum of all methods with their ordinal method ID;
+//! - `{Service}`, a trait with one method per RPC which must be implemented by the server;
+//! - `{Service}Dispatcher`, a wrapper implementing [`ServiceDispatch`] for implementors of
+//!   the trait above;
+//! - `{Service}Stub`, a client which invokes the methods of the service over a [`RpcChannel`].
+
+use bytes::Bytes;
+use futures::future;
+use futures::Future;
+use prost::{DecodeError, EncodeError, Message};
+use std::error::Error;
+use std::fmt;
+
+/// Future returned by generated service methods.
+pub type RpcFuture<T, E> = Box<dyn Future<Item = T, Error = E> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Error returned by service methods which have no implementation.
+pub struct UnimplementedMethod {
+    /// The fully qualified name of the addressed service.
+    pub service_name: &'static str,
+    /// The ID of the addressed method.
+    pub method_id: u32,
+}
+
+impl fmt::Display for UnimplementedMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Method {} of service {} is not implemented",
+            self.method_id, self.service_name
+        )
+    }
+}
+
+impl Error for UnimplementedMethod {}
+
+/// Object which decodes requests for a specific service, invokes the addressed method
+/// and encodes its result.
+pub trait ServiceDispatch {
+    /// Error type returned by the service methods.
+    type Error;
+
+    /// The fully qualified name of the service.
+    fn service_name(&self) -> &'static str;
+
+    /// The FNV-1a hash of the fully qualified service name.
+    fn service_hash(&self) -> u32;
+
+    /// Decode the body and invoke the method with the provided ID.
+    ///
+    /// `None` is returned if the service has no method with the provided ID.
+    /// The future resolves into the encoded response, or `None` if the method
+    /// doesn't respond.
+    fn dispatch(
+        &mut self,
+        method_id: u32,
+        body: Bytes,
+    ) -> Option<RpcFuture<Option<Bytes>, Self::Error>>;
+}
+
+/// Transport used by the generated client stubs.
+pub trait RpcChannel {
+    /// Error type returned by the transport.
+    type Error: From<DecodeError> + From<EncodeError> + Send + 'static;
+
+    /// Send the encoded request towards the method of the service with the provided hash.
+    ///
+    /// The future resolves into the encoded response, or `None` if no response was
+    /// expected.
+    fn call(
+        &mut self,
+        service_hash: u32,
+        method_id: u32,
+        body: Bytes,
+        expects_response: bool,
+    ) -> RpcFuture<Option<Bytes>, Self::Error>;
+}
+
+/// Returns a future which fails because the method is not implemented.
+pub fn unimplemented<T, E>(service_name: &'static str, method_id: u32) -> RpcFuture<T, E>
+where
+    T: Send + 'static,
+    E: From<UnimplementedMethod> + Send + 'static,
+{
+    let error = UnimplementedMethod {
+        service_name,
+        method_id,
+    };
+    Box::new(future::err(error.into()))
+}
+
+/// Decodes the request, invokes the handler and encodes its response.
+pub fn respond<Req, Resp, E, F>(body: Bytes, handler: F) -> RpcFuture<Option<Bytes>, E>
+where
+    Req: Message + Default,
+    Resp: Message + Send + 'static,
+    E: From<DecodeError> + From<EncodeError> + Send + 'static,
+    F: FnOnce(Req) -> RpcFuture<Resp, E>,
+{
+    let request = match Req::decode(body) {
+        Ok(request) => request,
+        Err(error) => return Box::new(future::err(error.into())),
+    };
+
+    let response =
+        handler(request).and_then(|response| encode(&response).map(Some).map_err(Into::into));
+    Box::new(response)
+}
+
+/// Decodes the request and invokes the handler, which doesn't respond.
+pub fn acknowledge<Req, E, F>(body: Bytes, handler: F) -> RpcFuture<Option<Bytes>, E>
+where
+    Req: Message + Default,
+    E: From<DecodeError> + Send + 'static,
+    F: FnOnce(Req) -> RpcFuture<(), E>,
+{
+    let request = match Req::decode(body) {
+        Ok(request) => request,
+        Err(error) => return Box::new(future::err(error.into())),
+    };
+
+    Box::new(handler(request).map(|_| None))
+}
+
+/// Encodes the request, sends it over the channel and decodes the response.
+pub fn request<C, Req, Resp>(
+    channel: &mut C,
+    service_hash: u32,
+    method_id: u32,
+    request: &Req,
+) -> RpcFuture<Resp, C::Error>
+where
+    C: RpcChannel,
+    Req: Message,
+    Resp: Message + Default + Send + 'static,
+{
+    let body = match encode(request) {
+        Ok(body) => body,
+        Err(error) => return Box::new(future::err(error.into())),
+    };
+
+    let response = channel
+        .call(service_hash, method_id, body, true)
+        .and_then(|body_opt| {
+            let body = body_opt.unwrap_or_else(Bytes::new);
+            Resp::decode(body).map_err(Into::into)
+        });
+    Box::new(response)
+}
+
+/// Encodes the request and sends it over the channel, without waiting for a response.
+pub fn notify<C, Req>(
+    channel: &mut C,
+    service_hash: u32,
+    method_id: u32,
+    request: &Req,
+) -> RpcFuture<(), C::Error>
+where
+    C: RpcChannel,
+    Req: Message,
+{
+    let body = match encode(request) {
+        Ok(body) => body,
+        Err(error) => return Box::new(future::err(error.into())),
+    };
+
+    Box::new(
+        channel
+            .call(service_hash, method_id, body, false)
+            .map(|_| ()),
+    )
+}
+
+fn encode<M: Message>(message: &M) -> Result<Bytes, EncodeError> {
+    let mut buffer = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut buffer)?;
+    Ok(Bytes::from(buffer))
+}